@@ -0,0 +1,66 @@
+use actix_web::{
+    App,
+    rt::System,
+    test::{TestRequest, call_and_read_body, init_service},
+};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use svg_server::Settings;
+
+/// Element counts of the benchmarked pages, from an icon to a large diagram
+const SIZES: [usize; 3] = [10, 1_000, 20_000];
+
+fn page(elements: usize) -> String {
+    let mut svg =
+        String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1200" height="800">"#);
+    for i in 0..elements {
+        svg.push_str(&format!(
+            r##"<rect x="{}" y="{}" width="10" height="6" fill="#eeeeee"/>"##,
+            i % 100 * 12,
+            i / 100 * 8
+        ));
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Full requests through the service: file read, transforms, template rendering, ETag
+fn render(c: &mut Criterion) {
+    let root = std::env::temp_dir().join(format!("svg-server-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    for size in SIZES {
+        std::fs::write(root.join(format!("page-{size}.svg")), page(size)).unwrap();
+    }
+
+    let system = System::new();
+    let app = system.block_on(init_service(
+        App::new().service(svg_server::service(Settings::new(&root))),
+    ));
+
+    for size in SIZES {
+        let mut group = c.benchmark_group(format!("{size} elements"));
+        group.throughput(Throughput::Bytes(page(size).len() as u64));
+        for (name, query) in [
+            ("page", ""),
+            ("page high contrast", "?contrast=high"),
+            ("page simulated", "?simulate=tritanopia"),
+            ("raw", ""),
+        ] {
+            let uri = match name {
+                "raw" => format!("/raw/page-{size}"),
+                _ => format!("/page-{size}{query}"),
+            };
+            group.bench_function(name, |b| {
+                b.iter(|| {
+                    system.block_on(call_and_read_body(
+                        &app,
+                        TestRequest::get().uri(&uri).to_request(),
+                    ))
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
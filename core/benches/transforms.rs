@@ -0,0 +1,75 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use svg_server_core::{
+    contrast::{ContrastConfig, svg_high_contrast},
+    resize::svg_size_full_width,
+    simulate::{Simulation, svg_simulate},
+    sprite::svg_sprite,
+    statistics::SvgStatistics,
+    svg::SvgRoot,
+};
+
+/// Element counts of the benchmarked documents, from an icon to a large diagram
+const SIZES: [usize; 3] = [10, 1_000, 20_000];
+
+/// A diagram-like document with `elements` shapes, mixing attributes and CSS colors
+fn document(elements: usize) -> String {
+    let mut svg = String::from(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="1200" height="800"><style>.label { fill: #cccccc; font-family: "Open Sans" }</style>"#,
+    );
+    for i in 0..elements {
+        let (x, y) = (i % 100 * 12, i / 100 * 8);
+        svg.push_str(&match i % 4 {
+            0 => format!(r##"<rect x="{x}" y="{y}" width="10" height="6" fill="#eeeeee" stroke="#dddddd" stroke-width="0.5"/>"##),
+            1 => format!(r#"<path d="M{x} {y}L{} {}C{x} {y} 4 4 {x} {y}Z" style="stroke: rgb(200, 200, 200)"/>"#, x + 10, y + 6),
+            2 => format!(r#"<text class="label" x="{x}" y="{y}">node {i}</text>"#),
+            _ => format!(r##"<circle cx="{x}" cy="{y}" r="3" fill="#336699" filter="url(#f)"/>"##),
+        });
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn transforms(c: &mut Criterion) {
+    let config = ContrastConfig::default();
+
+    for size in SIZES {
+        let svg = document(size);
+        let mut group = c.benchmark_group(format!("{size} elements"));
+        group.throughput(Throughput::Bytes(svg.len() as u64));
+
+        group.bench_function("parse root", |b| b.iter(|| SvgRoot::parse(black_box(&svg))));
+        group.bench_function("resize", |b| {
+            b.iter(|| svg_size_full_width(black_box(&svg)))
+        });
+        group.bench_function("high contrast", |b| {
+            b.iter(|| svg_high_contrast(black_box(&svg), &config))
+        });
+        group.bench_function("simulate", |b| {
+            b.iter(|| svg_simulate(black_box(&svg), Simulation::Deuteranopia))
+        });
+        group.bench_function("statistics", |b| {
+            b.iter(|| SvgStatistics::collect(black_box(&svg)))
+        });
+        // Everything a page rendered with ?contrast=high&simulate=... goes through
+        group.bench_function("pipeline", |b| {
+            b.iter(|| {
+                let resized = svg_size_full_width(&svg).unwrap();
+                let contrasted = svg_high_contrast(&resized, &config);
+                svg_simulate(&contrasted, Simulation::Protanopia).unwrap()
+            })
+        });
+        group.finish();
+    }
+
+    let icons: Vec<_> = (0..50)
+        .map(|i| (format!("icon-{i}"), document(10)))
+        .collect();
+    c.bench_function("sprite of 50 icons", |b| {
+        b.iter(|| svg_sprite(black_box(&icons)))
+    });
+}
+
+criterion_group!(benches, transforms);
+criterion_main!(benches);
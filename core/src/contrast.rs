@@ -0,0 +1,254 @@
+use std::{collections::HashMap, fmt, sync::LazyLock};
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// Contrast modes that can be applied to a served SVG
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Contrast {
+    High,
+}
+
+/// An opaque sRGB color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(u8, u8, u8);
+
+impl Color {
+    /// Parse `#rgb`, `#rrggbb` and `rgb(r, g, b)` colors
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        if let Some(hex) = value.strip_prefix('#') {
+            // Also rules out multi-byte characters the slicing below would split
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            let digit = |i: usize| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok();
+            return match hex.len() {
+                3 => Some(Color(digit(0)? * 17, digit(1)? * 17, digit(2)? * 17)),
+                6 => Some(Color(
+                    u8::from_str_radix(&hex[0..2], 16).ok()?,
+                    u8::from_str_radix(&hex[2..4], 16).ok()?,
+                    u8::from_str_radix(&hex[4..6], 16).ok()?,
+                )),
+                _ => None,
+            };
+        }
+
+        let args = value.strip_prefix("rgb(")?.strip_suffix(')')?;
+        let mut channels = args.split(',').map(|c| c.trim().parse::<u8>().ok());
+        let color = Color(channels.next()??, channels.next()??, channels.next()??);
+        channels.next().is_none().then_some(color)
+    }
+
+    /// WCAG relative luminance
+    fn luminance(self) -> f64 {
+        let channel = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(self.0) + 0.7152 * channel(self.1) + 0.0722 * channel(self.2)
+    }
+
+    /// WCAG contrast ratio between two colors, from 1 to 21
+    fn contrast_ratio(self, other: Color) -> f64 {
+        let (a, b) = (self.luminance(), other.luminance());
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// Distance between the hues of two colors, ignoring how light or dark they are
+    fn hue_distance(self, other: Color) -> i32 {
+        let centered = |c: Color| {
+            let (r, g, b) = (i32::from(c.0), i32::from(c.1), i32::from(c.2));
+            let mean = (r + g + b) / 3;
+            (r - mean, g - mean, b - mean)
+        };
+        let (a, b) = (centered(self), centered(other));
+        (a.0 - b.0).pow(2) + (a.1 - b.1).pow(2) + (a.2 - b.2).pow(2)
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Color::parse(&value).ok_or(format!("Invalid color '{value}'"))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Settings for the `contrast=high` transform
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContrastConfig {
+    /// Color that fills and strokes are measured against
+    pub background: Color,
+    /// Minimum contrast ratio a fill or stroke needs to be left untouched
+    pub min_ratio: f64,
+    /// Strokes thinner than this, in user units, are thickened to it
+    pub min_stroke_width: f64,
+    /// High-contrast colors that low-contrast ones are mapped onto
+    pub palette: Vec<Color>,
+    /// Explicit color replacements, applied regardless of contrast
+    pub map: HashMap<Color, Color>,
+}
+
+impl Default for ContrastConfig {
+    fn default() -> Self {
+        ContrastConfig {
+            background: Color(0xff, 0xff, 0xff),
+            min_ratio: 4.5,
+            min_stroke_width: 1.0,
+            palette: vec![
+                Color(0x00, 0x00, 0x00),
+                Color(0x00, 0x3d, 0xa5),
+                Color(0xa5, 0x00, 0x00),
+                Color(0x00, 0x5c, 0x00),
+                Color(0x6a, 0x00, 0x9c),
+                Color(0x7a, 0x44, 0x00),
+            ],
+            map: HashMap::new(),
+        }
+    }
+}
+
+impl ContrastConfig {
+    /// Replacement for a color, if it needs one
+    fn map_color(&self, color: Color) -> Option<Color> {
+        if let Some(mapped) = self.map.get(&color) {
+            return Some(*mapped);
+        }
+
+        if color.contrast_ratio(self.background) >= self.min_ratio {
+            return None;
+        }
+
+        let closest = self
+            .palette
+            .iter()
+            .filter(|candidate| candidate.contrast_ratio(self.background) >= self.min_ratio)
+            .min_by_key(|candidate| candidate.hue_distance(color))
+            .copied();
+
+        // Fall back to whichever of black and white stands out most
+        closest.or_else(|| {
+            let (black, white) = (Color(0, 0, 0), Color(0xff, 0xff, 0xff));
+            if black.contrast_ratio(self.background) >= white.contrast_ratio(self.background) {
+                Some(black)
+            } else {
+                Some(white)
+            }
+        })
+    }
+}
+
+static PAINT_ATTR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(\s)(fill|stroke)(\s*=\s*")([^"]*)(")"#).unwrap());
+
+static PAINT_STYLE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"([{;"'\s])(fill|stroke)(\s*:\s*)([^;"'}]+)"#).unwrap());
+
+static STROKE_WIDTH_ATTR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(\s)(stroke-width)(\s*=\s*")([0-9]*\.?[0-9]+)(?:px)?(")"#).unwrap()
+});
+
+static STROKE_WIDTH_STYLE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([{;"'\s])(stroke-width)(\s*:\s*)([0-9]*\.?[0-9]+)(?:px)?()"#).unwrap()
+});
+
+/// Map low-contrast fills and strokes onto the configured palette and thicken sub-pixel strokes
+pub fn svg_high_contrast(svg_content: &str, config: &ContrastConfig) -> String {
+    let recolor = |caps: &Captures| match Color::parse(&caps[4]).and_then(|c| config.map_color(c)) {
+        Some(color) => format!(
+            "{}{}{}{color}{}",
+            &caps[1],
+            &caps[2],
+            &caps[3],
+            caps_tail(caps)
+        ),
+        None => caps[0].to_owned(),
+    };
+
+    let thicken = |caps: &Captures| match caps[4].parse::<f64>() {
+        Ok(width) if width > 0.0 && width < config.min_stroke_width => format!(
+            "{}{}{}{}{}",
+            &caps[1],
+            &caps[2],
+            &caps[3],
+            config.min_stroke_width,
+            caps_tail(caps)
+        ),
+        _ => caps[0].to_owned(),
+    };
+
+    let svg_content = PAINT_ATTR_RE.replace_all(svg_content, recolor);
+    let svg_content = PAINT_STYLE_RE.replace_all(&svg_content, recolor);
+    let svg_content = STROKE_WIDTH_ATTR_RE.replace_all(&svg_content, thicken);
+    let svg_content = STROKE_WIDTH_STYLE_RE.replace_all(&svg_content, thicken);
+
+    svg_content.into_owned()
+}
+
+/// Closing quote of attribute matches, empty for style matches
+fn caps_tail<'c>(caps: &'c Captures) -> &'c str {
+    caps.get(5).map_or("", |m| m.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_parse() {
+        assert_eq!(Color::parse("#abc"), Some(Color(0xaa, 0xbb, 0xcc)));
+        assert_eq!(Color::parse(" #A0b1C2 "), Some(Color(0xa0, 0xb1, 0xc2)));
+        assert_eq!(Color::parse("rgb(1, 2, 3)"), Some(Color(1, 2, 3)));
+        assert_eq!(Color::parse("rgb(1, 2, 3, 4)"), None);
+        assert_eq!(Color::parse("red"), None);
+        assert_eq!(Color::parse("#abcd"), None);
+        assert_eq!(Color::parse("#+f+f+f"), None);
+        assert_eq!(Color::parse("#fд0f0"), None);
+    }
+
+    #[test]
+    fn low_contrast_colors_are_mapped() {
+        let config = ContrastConfig::default();
+        let svg = r##"<svg><rect fill="#dddddd" stroke="#000"/><path style="fill:#ffcc00;stroke:#eee"/></svg>"##;
+        assert_eq!(
+            svg_high_contrast(svg, &config),
+            r##"<svg><rect fill="#000000" stroke="#000"/><path style="fill:#7a4400;stroke:#000000"/></svg>"##
+        );
+    }
+
+    #[test]
+    fn explicit_mappings_win() {
+        let mut config = ContrastConfig::default();
+        config.map.insert(Color(0, 0, 0), Color(0x11, 0x22, 0x33));
+        let svg = r##"<svg><rect fill="#000"/></svg>"##;
+        assert_eq!(
+            svg_high_contrast(svg, &config),
+            r##"<svg><rect fill="#112233"/></svg>"##
+        );
+    }
+
+    #[test]
+    fn thin_strokes_are_thickened() {
+        let config = ContrastConfig::default();
+        let svg = r#"<svg><style>.a{stroke-width:0.25px}</style><path stroke-width="0.5"/><path stroke-width="2"/></svg>"#;
+        assert_eq!(
+            svg_high_contrast(svg, &config),
+            r#"<svg><style>.a{stroke-width:1}</style><path stroke-width="1"/><path stroke-width="2"/></svg>"#
+        );
+    }
+}
@@ -0,0 +1,17 @@
+//! The SVG transforms behind svg-server, free of any I/O.
+//!
+//! Everything here works on SVG source held in memory, so the crate builds for
+//! `wasm32-unknown-unknown` and the same transforms can run in a browser-based
+//! previewer as on the server.
+
+pub mod contrast;
+pub mod links;
+pub mod outline;
+pub mod recolor;
+pub mod resize;
+pub mod search;
+pub mod simulate;
+pub mod sprite;
+pub mod standalone;
+pub mod statistics;
+pub mod svg;
@@ -0,0 +1,292 @@
+use std::{collections::BTreeSet, sync::LazyLock};
+
+use quick_xml::{Reader, events::Event};
+use regex::{Captures, Regex};
+
+static ANCHOR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(?:[\w.-]+:)?a\s[^>]*>").unwrap());
+
+static HREF_ATTR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(\s(?:xlink:)?href\s*=\s*)(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// Every link a document makes, and the ids links into it can target
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SvgLinks {
+    /// `href` and `xlink:href` values in document order, unescaped
+    pub links: Vec<Link>,
+    /// Values of every `id` attribute
+    pub ids: BTreeSet<String>,
+    /// Words of the text content that read like page names, `/name` or `dir:name`
+    pub mentions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// Local name of the linking element, e.g. `a`, `use` or `image`
+    pub element: String,
+    pub href: String,
+}
+
+/// What a link points at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkTarget<'a> {
+    /// An element of the same document, by id
+    Fragment(&'a str),
+    /// An `http://` or `https://` URL
+    External(&'a str),
+    /// Another page of the same site, by its `:` or `/` separated name
+    Page(&'a str),
+    /// Anything else (`mailto:`, `data:`, the site root, ...)
+    Other,
+}
+
+impl SvgLinks {
+    pub fn collect(svg_content: &str) -> Result<Self, String> {
+        let mut links = SvgLinks::default();
+        let mut reader = Reader::from_str(svg_content);
+        let mut in_code = false;
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| format!("Invalid SVG: {e}"))?;
+            let element = match &event {
+                Event::Start(element) | Event::Empty(element) => element,
+                Event::Text(text) if !in_code => {
+                    links.collect_mentions(&text.unescape().unwrap_or_default());
+                    continue;
+                }
+                Event::End(_) => {
+                    in_code = false;
+                    continue;
+                }
+                Event::Eof => return Ok(links),
+                _ => continue,
+            };
+            if matches!(event, Event::Start(_)) {
+                in_code = matches!(element.local_name().as_ref(), b"style" | b"script");
+            }
+
+            for attr in element.attributes().flatten() {
+                let value = attr.unescape_value().unwrap_or_default();
+                let is_href = attr.key.local_name().as_ref() == b"href"
+                    && attr
+                        .key
+                        .prefix()
+                        .is_none_or(|prefix| prefix.as_ref() == b"xlink");
+                if attr.key.as_ref() == b"id" {
+                    links.ids.insert(value.into_owned());
+                } else if is_href {
+                    links.links.push(Link {
+                        element: String::from_utf8_lossy(element.local_name().as_ref())
+                            .into_owned(),
+                        href: value.trim().to_owned(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl SvgLinks {
+    fn collect_mentions(&mut self, text: &str) {
+        let words = text
+            .split(|c: char| c.is_whitespace() || ",;()[]{}<>\"'`".contains(c))
+            .map(|word| word.trim_end_matches(['.', '!', '?', ':']));
+        for word in words {
+            let page_like = match word.strip_prefix('/') {
+                Some(name) => !name.is_empty() && !name.starts_with('/'),
+                None => word.contains(':') && !word.contains("://"),
+            };
+            let first = word.trim_start_matches('/').chars().next();
+            if page_like && first.is_some_and(char::is_alphanumeric) {
+                self.mentions.push(word.to_owned());
+            }
+        }
+    }
+}
+
+impl Link {
+    pub fn target(&self) -> LinkTarget<'_> {
+        link_target(&self.href)
+    }
+}
+
+/// Classify an `href`; page names lose any query, fragment and leading `/` or `./`
+pub fn link_target(href: &str) -> LinkTarget<'_> {
+    if let Some(id) = href.strip_prefix('#') {
+        return LinkTarget::Fragment(id);
+    }
+    let lower = href.to_ascii_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") {
+        return LinkTarget::External(href);
+    }
+
+    // Page names contain `:` as well, so only well-known schemes are told apart
+    let has_scheme = href.split_once(':').is_some_and(|(scheme, _)| {
+        ["mailto", "tel", "data", "javascript", "ftp", "file"]
+            .iter()
+            .any(|known| scheme.eq_ignore_ascii_case(known))
+    });
+    if has_scheme || href.starts_with("//") {
+        return LinkTarget::Other;
+    }
+
+    let path = href.split(['?', '#']).next().unwrap_or_default();
+    let path = path
+        .strip_prefix("./")
+        .unwrap_or(path)
+        .trim_start_matches('/');
+    if path.is_empty() {
+        LinkTarget::Other
+    } else {
+        LinkTarget::Page(path)
+    }
+}
+
+/// Point relative `<a>` links to `.svg` files at the pages serving those files.
+///
+/// Each target is resolved against the directory of `page` (`/` separated) and passed
+/// to `link` as a `/` separated page name without the extension; the returned href
+/// keeps any query and fragment of the original. `link` must return text that is safe
+/// inside an attribute. Links to other elements (`<use>`, `<image>`), absolute URLs
+/// and paths leaving the root are left alone.
+pub fn svg_rewrite_links(svg_content: &str, page: &str, link: impl Fn(&str) -> String) -> String {
+    let dir: Vec<_> = page.split('/').collect();
+    let dir = &dir[..dir.len() - 1];
+
+    let rewrite_href = |caps: &Captures| {
+        let (quote, href) = match (caps.get(2), caps.get(3)) {
+            (Some(href), _) => ('"', href.as_str()),
+            (_, Some(href)) => ('\'', href.as_str()),
+            _ => return caps[0].to_owned(),
+        };
+        match resolve_svg_link(dir, href) {
+            Some((target, suffix)) => {
+                format!("{}{quote}{}{suffix}{quote}", &caps[1], link(&target))
+            }
+            None => caps[0].to_owned(),
+        }
+    };
+    ANCHOR_RE
+        .replace_all(svg_content, |tag: &Captures| {
+            HREF_ATTR_RE.replace_all(&tag[0], rewrite_href).into_owned()
+        })
+        .into_owned()
+}
+
+/// Page a relative `<a>` href to an `.svg` file in `page` points at, as
+/// [`svg_rewrite_links`] resolves it
+pub fn svg_link_page(page: &str, href: &str) -> Option<String> {
+    let dir: Vec<_> = page.split('/').collect();
+    resolve_svg_link(&dir[..dir.len() - 1], href).map(|(target, _)| target)
+}
+
+/// Page name a relative `.svg` href in `dir` points at, and the query and fragment after it
+fn resolve_svg_link<'h>(dir: &[&str], href: &'h str) -> Option<(String, &'h str)> {
+    let href = href.trim();
+    let path_end = href.find(['?', '#']).unwrap_or(href.len());
+    let (path, suffix) = href.split_at(path_end);
+    let stem = path
+        .len()
+        .checked_sub(4)
+        .filter(|&end| path.is_char_boundary(end) && path[end..].eq_ignore_ascii_case(".svg"))
+        .map(|end| &path[..end])?;
+    if stem.is_empty() || stem.starts_with('/') || stem.contains(':') {
+        return None;
+    }
+
+    let mut target: Vec<&str> = dir.to_vec();
+    for segment in stem.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                target.pop()?;
+            }
+            segment => target.push(segment),
+        }
+    }
+    (!target.is_empty()).then(|| (target.join("/"), suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hrefs_and_ids_are_collected() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+            <symbol id="db"/>
+            <a href="https://example.com/runbook?x=1&amp;y=2"><rect id="box"/></a>
+            <use xlink:href="#db"/>
+            <a href=" /network:overview "/>
+            <rect data-href="ignored"/>
+            <text>See /home, storage:db and <tspan>cache:redis.</tspan> (https://x.y)</text>
+            <style>a:hover { fill: red }</style>
+        </svg>"##;
+        let links = SvgLinks::collect(svg).unwrap();
+        let hrefs: Vec<_> = links
+            .links
+            .iter()
+            .map(|link| (link.element.as_str(), link.href.as_str()))
+            .collect();
+        assert_eq!(
+            hrefs,
+            [
+                ("a", "https://example.com/runbook?x=1&y=2"),
+                ("use", "#db"),
+                ("a", "/network:overview"),
+            ]
+        );
+        assert_eq!(
+            links.ids,
+            BTreeSet::from(["db".to_owned(), "box".to_owned()])
+        );
+        assert_eq!(links.mentions, ["/home", "storage:db", "cache:redis"]);
+    }
+
+    #[test]
+    fn relative_svg_links_point_at_pages() {
+        let svg = concat!(
+            r#"<svg><a href="storage.svg#db"/><a xlink:href='../home.SVG'/><svg:a href="sub/x.svg?v=2"/>"#,
+            r#"<a href="../../root.svg"/><a href="https://example.com/a.svg"/><a href="/abs.svg"/>"#,
+            r#"<image href="storage.svg"/><a href="notes.txt"/></svg>"#,
+        );
+        assert_eq!(
+            svg_rewrite_links(svg, "architecture/overview", |page| format!(
+                "/{}",
+                page.replace('/', ":")
+            )),
+            concat!(
+                r#"<svg><a href="/architecture:storage#db"/><a xlink:href='/home'/><svg:a href="/architecture:sub:x?v=2"/>"#,
+                r#"<a href="../../root.svg"/><a href="https://example.com/a.svg"/><a href="/abs.svg"/>"#,
+                r#"<image href="storage.svg"/><a href="notes.txt"/></svg>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn links_are_classified() {
+        assert_eq!(link_target("#db"), LinkTarget::Fragment("db"));
+        assert_eq!(
+            link_target("HTTPS://example.com"),
+            LinkTarget::External("HTTPS://example.com")
+        );
+        assert_eq!(
+            link_target("/network:overview#top"),
+            LinkTarget::Page("network:overview")
+        );
+        assert_eq!(
+            link_target("./storage/db?x"),
+            LinkTarget::Page("storage/db")
+        );
+        for other in [
+            "mailto:ops@example.com",
+            "data:image/png;base64,",
+            "/",
+            "//cdn",
+        ] {
+            assert_eq!(link_target(other), LinkTarget::Other, "{other}");
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use quick_xml::{
+    Reader,
+    events::{BytesStart, Event},
+};
+use serde::Serialize;
+
+/// Elements whose content is never drawn where it stands, or not as text
+const SKIPPED: [&[u8]; 14] = [
+    b"defs",
+    b"style",
+    b"script",
+    b"metadata",
+    b"symbol",
+    b"clipPath",
+    b"mask",
+    b"pattern",
+    b"marker",
+    b"linearGradient",
+    b"radialGradient",
+    b"filter",
+    b"font",
+    b"font-face",
+];
+
+/// What a document says and how it is grouped, for readers who can't see the drawing
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SvgOutline {
+    /// `<title>` of the root element
+    pub title: Option<String>,
+    /// `<desc>` of the root element
+    pub description: Option<String>,
+    pub items: Vec<OutlineItem>,
+}
+
+/// A piece of text, a labelled shape or group, or a link, in document order
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct OutlineItem {
+    /// Its text, `aria-label` or `<title>`, whitespace collapsed
+    pub label: String,
+    /// Where it links to, unescaped, for `<a>` elements
+    pub href: Option<String>,
+    /// Items inside a labelled group or link
+    pub items: Vec<OutlineItem>,
+}
+
+/// An element being read, until its end tag
+#[derive(Default)]
+struct Open {
+    /// Whether everything inside is one piece of text, as in `<text>`
+    text: bool,
+    /// Whether it is a `<title>` or `<desc>`, labelling or describing its parent
+    caption: Option<Caption>,
+    label: Option<String>,
+    href: Option<String>,
+    content: String,
+    items: Vec<OutlineItem>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Caption {
+    Title,
+    Description,
+}
+
+/// Outline the text of a document: the `<text>` elements, the shapes and groups given a
+/// `<title>` or `aria-label`, and the links, nested as their groups are. Groups without
+/// a label don't add a level, and `aria-hidden` elements, definitions and styles are
+/// left out.
+pub fn svg_outline(svg_content: &str) -> Result<SvgOutline, String> {
+    let mut reader = Reader::from_str(svg_content);
+    let mut outline = SvgOutline::default();
+    let mut open: Vec<Open> = Vec::new();
+    let mut started = false;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid SVG: {e}"))?;
+        match event {
+            Event::Start(element) if skipped(&element) => {
+                reader
+                    .read_to_end(element.name())
+                    .map_err(|e| format!("Invalid SVG: {e}"))?;
+            }
+            Event::Empty(element) if skipped(&element) => {}
+            Event::Start(element) => {
+                started = true;
+                open.push(opened(&element, open.last())?);
+            }
+            Event::Empty(element) => {
+                started = true;
+                let element = opened(&element, open.last())?;
+                close(element, &mut open, &mut outline);
+            }
+            Event::Text(text) => {
+                if let Some(element) = open.last_mut() {
+                    let text = text
+                        .unescape()
+                        .map_err(|e| format!("Invalid SVG text: {e}"))?;
+                    element.content.push_str(&text);
+                }
+            }
+            Event::CData(text) => {
+                if let Some(element) = open.last_mut() {
+                    element.content.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Event::End(_) => {
+                if let Some(element) = open.pop() {
+                    close(element, &mut open, &mut outline);
+                }
+            }
+            Event::Eof if !started => return Err("No SVG start found".to_owned()),
+            Event::Eof => return Ok(outline),
+            _ => {}
+        }
+    }
+}
+
+fn skipped(element: &BytesStart) -> bool {
+    let hidden = element
+        .try_get_attribute("aria-hidden")
+        .ok()
+        .flatten()
+        .is_some_and(|hidden| hidden.value.as_ref() == b"true");
+    hidden || SKIPPED.contains(&element.local_name().as_ref())
+}
+
+/// State of `element` as it opens inside `parent`
+fn opened(element: &BytesStart, parent: Option<&Open>) -> Result<Open, String> {
+    let attribute = |name: &str| -> Result<Option<String>, String> {
+        match element.try_get_attribute(name).ok().flatten() {
+            Some(attribute) => attribute
+                .unescape_value()
+                .map(|value| Some(value.into_owned()))
+                .map_err(|e| format!("Invalid SVG attribute: {e}")),
+            None => Ok(None),
+        }
+    };
+    let local_name = element.local_name();
+    let name = local_name.as_ref();
+    let in_text = parent.is_some_and(|parent| parent.text || parent.caption.is_some());
+    Ok(Open {
+        text: in_text || matches!(name, b"text" | b"foreignObject"),
+        caption: match name {
+            _ if in_text => None,
+            b"title" => Some(Caption::Title),
+            b"desc" => Some(Caption::Description),
+            _ => None,
+        },
+        label: attribute("aria-label")?.map(|label| collapse(&label)),
+        href: match name {
+            b"a" => attribute("href")?.or(attribute("xlink:href")?),
+            _ => None,
+        },
+        ..Open::default()
+    })
+}
+
+/// Hand what `element` held over to its parent, or to `outline` at the root
+fn close(mut element: Open, open: &mut [Open], outline: &mut SvgOutline) {
+    let content = collapse(&element.content);
+    let in_root = open.len() == 1;
+    match (element.caption, open.last_mut()) {
+        // Text inside text, such as a <tspan>, is part of its parent's
+        (None, Some(parent)) if parent.text => {
+            parent.content.push(' ');
+            parent.content.push_str(&content);
+            return;
+        }
+        (None, _) => {}
+        (Some(_), _) if content.is_empty() => return,
+        (Some(Caption::Title), Some(parent)) => {
+            parent.label.get_or_insert(content);
+            return;
+        }
+        // Only the document's description is kept, the others repeating their titles
+        (Some(Caption::Description), _) => {
+            if in_root {
+                outline.description.get_or_insert(content);
+            }
+            return;
+        }
+        (Some(Caption::Title), None) => return,
+    }
+
+    if element.text && element.label.is_none() && !content.is_empty() {
+        element.label = Some(content);
+    }
+    // A link around a single piece of text reads as that text
+    if element.href.is_some()
+        && element.label.is_none()
+        && let [only] = &element.items[..]
+        && only.href.is_none()
+        && only.items.is_empty()
+    {
+        element.label = element.items.pop().map(|only| only.label);
+    }
+
+    let items = match open.last_mut() {
+        Some(parent) => &mut parent.items,
+        None => {
+            // The root's own label is its title
+            if outline.title.is_none() {
+                outline.title = element.label.take();
+            }
+            outline.items.append(&mut element.items);
+            return;
+        }
+    };
+    match (element.label, element.href) {
+        (None, None) => items.append(&mut element.items),
+        (label, href) => items.push(OutlineItem {
+            label: label.or(href.clone()).unwrap_or_default(),
+            href,
+            items: element.items,
+        }),
+    }
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(label: &str, href: Option<&str>, items: Vec<OutlineItem>) -> OutlineItem {
+        OutlineItem {
+            label: label.to_owned(),
+            href: href.map(str::to_owned),
+            items,
+        }
+    }
+
+    #[test]
+    fn text_and_labels_are_outlined_as_grouped() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+            <title>Network</title><desc>How  requests
+                reach the database</desc>
+            <defs><text id="unused">Hidden</text></defs>
+            <style>text { fill: red }</style>
+            <g><g id="edge"><title>Edge</title>
+                <rect><title>Load balancer</title></rect>
+                <text x="1">Router <tspan>&amp; firewall</tspan></text>
+            </g></g>
+            <a xlink:href="db.svg"><text>Database</text></a>
+            <a href="https://example.com/runbook" aria-label="Runbook"><rect/><circle/></a>
+            <g aria-hidden="true"><text>Decoration</text></g>
+            <rect/><text>  </text>
+        </svg>"##;
+        assert_eq!(
+            svg_outline(svg).unwrap(),
+            SvgOutline {
+                title: Some("Network".to_owned()),
+                description: Some("How requests reach the database".to_owned()),
+                items: vec![
+                    item(
+                        "Edge",
+                        None,
+                        vec![
+                            item("Load balancer", None, vec![]),
+                            item("Router & firewall", None, vec![]),
+                        ]
+                    ),
+                    item("Database", Some("db.svg"), vec![]),
+                    item("Runbook", Some("https://example.com/runbook"), vec![]),
+                ],
+            }
+        );
+        assert!(svg_outline("").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{str::FromStr, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+use crate::{contrast::Color, search::COLOR_PROPERTIES};
+
+static ATTRIBUTE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    let properties = COLOR_PROPERTIES.join("|");
+    Regex::new(&format!(
+        r#"(\s(?:{properties})\s*=\s*)(?:"([^"]*)"|'([^']*)')"#
+    ))
+    .unwrap()
+});
+
+static STYLE_ATTRIBUTE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(\sstyle\s*=\s*)(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+static STYLE_ELEMENT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)(<style[^>]*>)(.*?)(</style>)").unwrap());
+
+static DECLARATION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    let properties = COLOR_PROPERTIES.join("|");
+    Regex::new(&format!(r"((?:^|[;{{\s])(?:{properties})\s*:\s*)([^;}}]+)")).unwrap()
+});
+
+/// One color to replace by another, written `old=#112233:new=#445566`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorMapping {
+    pub old: Color,
+    pub new: Color,
+}
+
+impl FromStr for ColorMapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = || format!("Invalid mapping '{s}', expected old=#112233:new=#445566");
+        let (old, new) = s.split_once(':').ok_or_else(expected)?;
+        let (old, new) = (
+            old.trim().strip_prefix("old=").ok_or_else(expected)?,
+            new.trim().strip_prefix("new=").ok_or_else(expected)?,
+        );
+        Ok(ColorMapping {
+            old: Color::parse(old).ok_or(format!("Invalid color '{old}'"))?,
+            new: Color::parse(new).ok_or(format!("Invalid color '{new}'"))?,
+        })
+    }
+}
+
+/// Replace colors by `mappings` wherever a fill, stroke or other color property is set:
+/// attributes, inline styles and `<style>` elements. Everything else is left byte for
+/// byte, and replacements are written `#rrggbb`.
+///
+/// Returns the new source and how many values were replaced. A value is mapped once,
+/// by the first mapping for its color, so `a→b` and `b→c` don't chain.
+pub fn svg_recolor(svg_content: &str, mappings: &[ColorMapping]) -> (String, usize) {
+    let mut replaced = 0;
+    let mut map = |value: &str| -> Option<String> {
+        let mapping = mappings
+            .iter()
+            .find(|mapping| Color::parse(value) == Some(mapping.old))?;
+        replaced += 1;
+        // Keep whitespace around the value, e.g. before a closing brace
+        let (start, end) = (
+            value.len() - value.trim_start().len(),
+            value.trim_end().len(),
+        );
+        Some(format!(
+            "{}{}{}",
+            &value[..start],
+            mapping.new,
+            &value[end..]
+        ))
+    };
+
+    let mut declarations = |css: &str| -> String {
+        DECLARATION_RE
+            .replace_all(css, |caps: &Captures| {
+                let value = &caps[2];
+                format!(
+                    "{}{}",
+                    &caps[1],
+                    map(value).unwrap_or_else(|| value.to_owned())
+                )
+            })
+            .into_owned()
+    };
+    let styled = STYLE_ATTRIBUTE_RE.replace_all(svg_content, |caps: &Captures| {
+        match (caps.get(2), caps.get(3)) {
+            (Some(value), _) => format!("{}\"{}\"", &caps[1], declarations(value.as_str())),
+            (_, Some(value)) => format!("{}'{}'", &caps[1], declarations(value.as_str())),
+            _ => caps[0].to_owned(),
+        }
+    });
+    let styled = STYLE_ELEMENT_RE.replace_all(&styled, |caps: &Captures| {
+        format!("{}{}{}", &caps[1], declarations(&caps[2]), &caps[3])
+    });
+    let styled = styled.into_owned();
+
+    let recolored = ATTRIBUTE_RE.replace_all(&styled, |caps: &Captures| {
+        let (quote, value) = match (caps.get(2), caps.get(3)) {
+            (Some(value), _) => ('"', value.as_str()),
+            (_, Some(value)) => ('\'', value.as_str()),
+            _ => return caps[0].to_owned(),
+        };
+        match map(value) {
+            Some(value) => format!("{}{quote}{value}{quote}", &caps[1]),
+            None => caps[0].to_owned(),
+        }
+    });
+    let recolored = recolored.into_owned();
+    (recolored, replaced)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(s: &str) -> ColorMapping {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn mapped_colors_are_replaced_everywhere_they_are_set() {
+        let svg = concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg">"##,
+            "<style>.brand { fill: #E30613 }\n.line{stroke:#e30613;fill:#fff}</style>",
+            r##"<rect fill="#e30613" stroke='rgb(227, 6, 19)' style="fill:#F00; opacity: .5"/>"##,
+            r##"<stop stop-color="#112233"/><text fill="#123456">#e30613</text>"##,
+            "</svg>",
+        );
+        let (recolored, replaced) = svg_recolor(
+            svg,
+            &[
+                mapping("old=#e30613:new=#0057b8"),
+                mapping("old=#f00:new=#e30613"),
+                mapping("old=#112233:new=#445566"),
+            ],
+        );
+        assert_eq!(
+            recolored,
+            concat!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg">"##,
+                "<style>.brand { fill: #0057b8 }\n.line{stroke:#0057b8;fill:#fff}</style>",
+                r##"<rect fill="#0057b8" stroke='#0057b8' style="fill:#e30613; opacity: .5"/>"##,
+                r##"<stop stop-color="#445566"/><text fill="#123456">#e30613</text>"##,
+                "</svg>",
+            )
+        );
+        assert_eq!(replaced, 6);
+    }
+
+    #[test]
+    fn mappings_are_parsed() {
+        assert_eq!(
+            mapping("old=#112233:new=rgb(68,85,102)"),
+            ColorMapping {
+                old: Color::parse("#112233").unwrap(),
+                new: Color::parse("#445566").unwrap(),
+            }
+        );
+        assert!("#112233=#445566".parse::<ColorMapping>().is_err());
+        assert!("old=#112233:new=blue".parse::<ColorMapping>().is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::svg::SvgRoot;
+
+static HEIGHT_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"height\s*=\s*"[^"]*""#).unwrap());
+
+static WIDTH_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"width\s*=\s*"[^"]*""#).unwrap());
+
+static SIZE_ATTR_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\s(?:width|height)\s*=\s*(?:"[^"]*"|'[^']*')"#).unwrap());
+
+/// Give the root `<svg>` an explicit pixel size, from its `viewBox` (or `width` and
+/// `height`) and scaled to `width` when given, for viewers that need one up front
+pub fn svg_size_fixed(svg_content: &str, width: Option<f64>) -> Result<String, String> {
+    let root = SvgRoot::parse(svg_content)?;
+    let view_box = root
+        .view_box()
+        .ok_or("SVG has neither a viewBox nor a width and height".to_owned())?;
+    let dimensions: Vec<f64> = view_box
+        .split([' ', ','])
+        .filter(|part| !part.is_empty())
+        .filter_map(|part| part.parse().ok())
+        .collect();
+    let (view_width, view_height) = match dimensions[..] {
+        [_, _, w, h] if w > 0.0 && h > 0.0 => (w, h),
+        _ => return Err(format!("Invalid viewBox '{view_box}'")),
+    };
+    let (width, height) = match width {
+        Some(width) => (width, width * view_height / view_width),
+        None => (view_width, view_height),
+    };
+
+    let svg_start = svg_content
+        .find("<svg")
+        .ok_or("No SVG start found".to_owned())?;
+    let svg_end = svg_content[svg_start..]
+        .find('>')
+        .ok_or("No SVG end found".to_owned())?;
+    let tag = &svg_content[svg_start + 4..svg_start + svg_end];
+
+    let mut sized = String::with_capacity(svg_content.len() + 64);
+    sized.push_str(&svg_content[..svg_start + 4]);
+    sized.push_str(&format!(
+        " width=\"{}\" height=\"{}\"",
+        round(width),
+        round(height)
+    ));
+    if root.attribute("viewBox").is_none() {
+        sized.push_str(&format!(" viewBox=\"{view_box}\""));
+    }
+    sized.push_str(&SIZE_ATTR_RE.replace_all(tag, ""));
+    sized.push_str(&svg_content[svg_start + svg_end..]);
+    Ok(sized)
+}
+
+/// Pixel sizes with at most two decimals
+fn round(length: f64) -> f64 {
+    (length * 100.0).round() / 100.0
+}
+
+/// Make the root `<svg>` fill its container's width, dropping any fixed height
+pub fn svg_size_full_width(svg_content: &str) -> Result<String, String> {
+    let svg_start = svg_content
+        .find("<svg")
+        .ok_or("No SVG start found".to_owned())?;
+    let svg_end = svg_content[svg_start..]
+        .find('>')
+        .ok_or("No SVG end found".to_owned())?;
+
+    // Get the full SVG tag line
+    let svg_tag_line = &svg_content[svg_start..svg_start + svg_end + 1];
+    // Remove height attribute
+    let new_svg_tag_line = HEIGHT_RE.replace_all(svg_tag_line, "").to_string();
+
+    // Replace width attribute with 100%
+    let new_svg_tag_line = WIDTH_RE
+        .replace_all(&new_svg_tag_line, "width=\"100%\"")
+        .to_string();
+
+    Ok(svg_content.replace(svg_tag_line, &new_svg_tag_line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn root_fills_width() {
+        let svg = r#"<svg width="200" height="100" viewBox="0 0 200 100"><rect width="5" height="5"/></svg>"#;
+        let resized = svg_size_full_width(svg).unwrap();
+        assert_eq!(
+            resized,
+            r#"<svg width="100%"  viewBox="0 0 200 100"><rect width="5" height="5"/></svg>"#
+        );
+    }
+
+    #[test]
+    fn root_gets_a_fixed_size() {
+        let svg =
+            r#"<svg viewBox="0 0 200 100" width="100%" stroke-width="2"><rect width="5"/></svg>"#;
+        assert_eq!(
+            svg_size_fixed(svg, None).unwrap(),
+            r#"<svg width="200" height="100" viewBox="0 0 200 100" stroke-width="2"><rect width="5"/></svg>"#
+        );
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="30px" height="20"/>"#;
+        assert_eq!(
+            svg_size_fixed(svg, Some(50.0)).unwrap(),
+            r#"<svg width="50" height="33.33" viewBox="0 0 30 20" xmlns="http://www.w3.org/2000/svg"/>"#
+        );
+        assert!(svg_size_fixed("<svg/>", None).is_err());
+    }
+
+    #[test]
+    fn missing_root_is_an_error() {
+        assert!(svg_size_full_width("<html></html>").is_err());
+    }
+}
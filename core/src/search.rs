@@ -0,0 +1,275 @@
+use std::collections::BTreeSet;
+
+use quick_xml::{Reader, events::Event};
+use serde::{Deserialize, Serialize};
+
+use crate::contrast::Color;
+
+/// Properties whose value is a color, as attributes or inline style declarations
+pub(crate) const COLOR_PROPERTIES: [&str; 6] = [
+    "fill",
+    "stroke",
+    "stop-color",
+    "flood-color",
+    "lighting-color",
+    "color",
+];
+
+/// What a document can be searched by
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SvgMetadata {
+    /// Colors it paints with, as `#rrggbb`
+    pub colors: BTreeSet<String>,
+    /// Names of the elements it contains
+    pub elements: BTreeSet<String>,
+    /// Size of the root `<svg>` in CSS pixels, from its `width` and `height` or its
+    /// `viewBox`
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+}
+
+impl SvgMetadata {
+    /// Collect the metadata of a whole document
+    pub fn collect(svg_content: &str) -> Result<Self, String> {
+        let mut metadata = SvgMetadata::default();
+        let mut reader = Reader::from_str(svg_content);
+        loop {
+            let element = match reader.read_event() {
+                Ok(Event::Start(element) | Event::Empty(element)) => element,
+                Ok(Event::Eof) => break,
+                Ok(_) => continue,
+                Err(e) => return Err(format!("Invalid SVG: {e}")),
+            };
+
+            let is_root = metadata.elements.is_empty();
+            let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+            let (mut width, mut height, mut view_box) = (None, None, None);
+            for attr in element.attributes().flatten() {
+                let value = attr.unescape_value().unwrap_or_default();
+                let key = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+                match key.as_str() {
+                    "style" => {
+                        for declaration in value.split(';') {
+                            if let Some((property, value)) = declaration.split_once(':') {
+                                metadata.add_color(property.trim(), value);
+                            }
+                        }
+                    }
+                    "width" if is_root => width = length(&value),
+                    "height" if is_root => height = length(&value),
+                    "viewBox" if is_root => view_box = Some(value.into_owned()),
+                    property => metadata.add_color(property, &value),
+                }
+            }
+
+            if is_root {
+                let view_box: Vec<f64> = view_box
+                    .unwrap_or_default()
+                    .split([' ', ','])
+                    .filter_map(|part| part.parse().ok())
+                    .collect();
+                let (view_width, view_height) = match view_box[..] {
+                    [_, _, w, h] => (Some(w), Some(h)),
+                    _ => (None, None),
+                };
+                metadata.width = width.or(view_width);
+                metadata.height = height.or(view_height);
+            }
+            metadata.elements.insert(name);
+        }
+
+        if metadata.elements.is_empty() {
+            return Err("No SVG start found".to_owned());
+        }
+        Ok(metadata)
+    }
+
+    fn add_color(&mut self, property: &str, value: &str) {
+        if !COLOR_PROPERTIES.contains(&property) {
+            return;
+        }
+        if let Some(color) = Color::parse(value) {
+            self.colors.insert(color.to_string());
+        }
+    }
+}
+
+/// A plain number of pixels, `px` suffix optional; other units can't be compared
+fn length(value: &str) -> Option<f64> {
+    let value = value.trim();
+    value.strip_suffix("px").unwrap_or(value).parse().ok()
+}
+
+/// Which side of a number a size has to be on
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    /// Paints with this `#rrggbb` color
+    Color(String),
+    /// Contains one of these elements, compared case-insensitively
+    Uses(Vec<String>),
+    Width(Comparison, f64),
+    Height(Comparison, f64),
+    /// Page name contains this, compared case-insensitively
+    Name(String),
+}
+
+/// Space-separated terms that a page has to match all of:
+///
+/// - `color:#ff0000` (or `#f00`, `rgb(255,0,0)`) for pages painting with a color
+/// - `uses:gradient` for pages containing an element: `gradient` and `clip` stand for
+///   either gradient element and `clipPath`, any other name for that element
+/// - `width>2000`, `height<=100` and so on for the size of the root `<svg>`, in pixels
+/// - anything else, such as `network:` for a directory, for pages whose name contains it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    terms: Vec<Term>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let terms = query
+            .split_whitespace()
+            .map(term)
+            .collect::<Result<Vec<_>, _>>()?;
+        if terms.is_empty() {
+            return Err("Empty search query".to_owned());
+        }
+        Ok(SearchQuery { terms })
+    }
+
+    /// Whether the page named `page` (`/` separated) with `metadata` matches every term
+    pub fn matches(&self, page: &str, metadata: &SvgMetadata) -> bool {
+        self.terms.iter().all(|term| match term {
+            Term::Color(color) => metadata.colors.contains(color),
+            Term::Uses(elements) => metadata.elements.iter().any(|element| {
+                elements
+                    .iter()
+                    .any(|wanted| element.eq_ignore_ascii_case(wanted))
+            }),
+            Term::Width(comparison, value) => compare(metadata.width, *comparison, *value),
+            Term::Height(comparison, value) => compare(metadata.height, *comparison, *value),
+            Term::Name(part) => page
+                .to_lowercase()
+                .contains(&part.to_lowercase().replace(':', "/")),
+        })
+    }
+}
+
+fn compare(size: Option<f64>, comparison: Comparison, value: f64) -> bool {
+    let Some(size) = size else {
+        return false;
+    };
+    match comparison {
+        Comparison::Less => size < value,
+        Comparison::LessOrEqual => size <= value,
+        Comparison::Equal => size == value,
+        Comparison::GreaterOrEqual => size >= value,
+        Comparison::Greater => size > value,
+    }
+}
+
+fn term(term: &str) -> Result<Term, String> {
+    for dimension in ["width", "height"] {
+        // Pages can be named like `widths` too
+        let Some(rest) = term
+            .strip_prefix(dimension)
+            .filter(|rest| rest.starts_with(['<', '>', '=', ':']))
+        else {
+            continue;
+        };
+        let (comparison, number) = [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equal),
+            (":", Comparison::Equal),
+        ]
+        .into_iter()
+        .find_map(|(operator, comparison)| Some((comparison, rest.strip_prefix(operator)?)))
+        .expect("every operator character is listed");
+        let value = length(number).ok_or(format!("Invalid number in '{term}'"))?;
+        return Ok(match dimension {
+            "width" => Term::Width(comparison, value),
+            _ => Term::Height(comparison, value),
+        });
+    }
+
+    let Some((field, value)) = term.split_once(':') else {
+        return Ok(Term::Name(term.to_owned()));
+    };
+    match field {
+        "color" => Color::parse(value)
+            .map(|color| Term::Color(color.to_string()))
+            .ok_or(format!("Invalid color '{value}'")),
+        "uses" => Ok(Term::Uses(match value {
+            "gradient" => vec!["linearGradient".to_owned(), "radialGradient".to_owned()],
+            "clip" => vec!["clipPath".to_owned()],
+            element => vec![element.to_owned()],
+        })),
+        // Page names are written with `:` between directories, like `network:db`
+        _ => Ok(Term::Name(term.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 2400 800">
+        <defs><linearGradient id="g"><stop stop-color="#F00"/></linearGradient></defs>
+        <rect fill="url(#g)" stroke="rgb(0, 0, 255)"/>
+        <text style="fill: #336699; font-size: 12px">Legend</text>
+    </svg>"##;
+
+    #[test]
+    fn metadata_lists_colors_elements_and_size() {
+        let metadata = SvgMetadata::collect(SVG).unwrap();
+        assert_eq!(
+            metadata.colors,
+            BTreeSet::from(["#0000ff", "#336699", "#ff0000"].map(str::to_owned))
+        );
+        assert!(metadata.elements.contains("linearGradient"));
+        assert_eq!(
+            (metadata.width, metadata.height),
+            (Some(2400.0), Some(800.0))
+        );
+
+        let sized = SvgMetadata::collect(r#"<svg width="300px" height="200"/>"#).unwrap();
+        assert_eq!((sized.width, sized.height), (Some(300.0), Some(200.0)));
+        assert!(SvgMetadata::collect("plain text").is_err());
+    }
+
+    #[test]
+    fn every_term_has_to_match() {
+        let metadata = SvgMetadata::collect(SVG).unwrap();
+        let matches = |query: &str| {
+            SearchQuery::parse(query)
+                .unwrap()
+                .matches("brand/legacy-logo", &metadata)
+        };
+        assert!(matches("color:#ff0000"));
+        assert!(matches("color:rgb(51,102,153) uses:gradient"));
+        assert!(matches("width>2000 height<=800 brand:LEGACY"));
+        assert!(matches("uses:TEXT"));
+        assert!(!matches("width>2000 height<800"));
+        assert!(!matches("color:#00ff00"));
+        assert!(!matches("uses:clip"));
+        assert!(!matches("network"));
+
+        assert!(SearchQuery::parse("  ").is_err());
+        assert!(SearchQuery::parse("color:brand-red").is_err());
+        assert!(SearchQuery::parse("width=>2000").is_err());
+        assert!(!matches("widths"));
+        assert!(SearchQuery::parse("width>wide").is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+const FILTER_ID: &str = "svg-server-simulate";
+
+/// Color-vision deficiencies that can be simulated on a served SVG
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Simulation {
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Simulation {
+    /// `feColorMatrix` values for full-severity dichromacy (Machado et al., 2009)
+    fn matrix(self) -> &'static str {
+        match self {
+            Simulation::Deuteranopia => {
+                "0.367322 0.860646 -0.227968 0 0 \
+                 0.280085 0.672501 0.047413 0 0 \
+                 -0.011820 0.042940 0.968881 0 0 \
+                 0 0 0 1 0"
+            }
+            Simulation::Protanopia => {
+                "0.152286 1.052583 -0.204868 0 0 \
+                 0.114503 0.786281 0.099216 0 0 \
+                 -0.003882 -0.048116 1.051998 0 0 \
+                 0 0 0 1 0"
+            }
+            Simulation::Tritanopia => {
+                "1.255528 -0.076749 -0.178779 0 0 \
+                 -0.078411 0.930809 0.147602 0 0 \
+                 0.004733 0.691367 0.303900 0 0 \
+                 0 0 0 1 0"
+            }
+        }
+    }
+}
+
+/// Wrap the SVG contents in a group filtered through the simulation's color matrix
+pub fn svg_simulate(svg_content: &str, simulation: Simulation) -> Result<String, String> {
+    let svg_start = svg_content
+        .find("<svg")
+        .ok_or("No SVG start found".to_owned())?;
+    let svg_end = svg_content[svg_start..]
+        .find('>')
+        .ok_or("No SVG end found".to_owned())?;
+    let content_start = svg_start + svg_end + 1;
+
+    // A self-closing root has nothing to filter
+    if svg_content[..content_start].ends_with("/>") {
+        return Ok(svg_content.to_owned());
+    }
+
+    let content_end = svg_content
+        .rfind("</svg>")
+        .filter(|end| *end >= content_start)
+        .ok_or("No SVG closing tag found".to_owned())?;
+
+    let filter = format!(
+        r#"<defs><filter id="{FILTER_ID}"><feColorMatrix type="matrix" values="{}"/></filter></defs><g filter="url(#{FILTER_ID})">"#,
+        simulation.matrix()
+    );
+
+    let mut simulated = String::with_capacity(svg_content.len() + filter.len() + 4);
+    simulated.push_str(&svg_content[..content_start]);
+    simulated.push_str(&filter);
+    simulated.push_str(&svg_content[content_start..content_end]);
+    simulated.push_str("</g>");
+    simulated.push_str(&svg_content[content_end..]);
+
+    Ok(simulated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_is_wrapped_in_filter() {
+        let svg = r#"<svg viewBox="0 0 1 1"><rect/></svg>"#;
+        let simulated = svg_simulate(svg, Simulation::Protanopia).unwrap();
+        assert!(
+            simulated
+                .starts_with(r#"<svg viewBox="0 0 1 1"><defs><filter id="svg-server-simulate">"#)
+        );
+        assert!(simulated.ends_with(r#"<g filter="url(#svg-server-simulate)"><rect/></g></svg>"#));
+    }
+
+    #[test]
+    fn self_closing_root_is_unchanged() {
+        let svg = r#"<svg viewBox="0 0 1 1"/>"#;
+        assert_eq!(svg_simulate(svg, Simulation::Tritanopia).unwrap(), svg);
+    }
+}
@@ -0,0 +1,146 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use quick_xml::escape::escape;
+use regex::{Captures, Regex};
+
+use crate::svg::SvgRoot;
+
+static ID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\sid\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+static ID_REF_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(\sid\s*=\s*["'])([^"']*)(["'])|(url\(\s*['"]?#)([^)'"\s]+)(['"]?\s*\))|(href\s*=\s*["']#)([^"']+)(["'])"#,
+    )
+    .unwrap()
+});
+
+/// Symbol id for a page name, e.g. `icons:Arrow Left` becomes `icons-arrow-left`
+pub fn symbol_id(page: &str) -> String {
+    page.chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9' | '_' | '-') => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+/// Merge SVG documents into one `<svg>` of `<symbol>` elements.
+///
+/// Each `(id, svg_content)` pair becomes a symbol with that id. Ids inside a document
+/// are prefixed with its symbol id and a `.`, which symbol ids never contain, and
+/// references to them rewritten, so documents that reuse the same gradient or clip-path
+/// ids don't clash once merged.
+pub fn svg_sprite(icons: &[(String, String)]) -> Result<String, String> {
+    let mut namespaces: Vec<(String, String)> = Vec::new();
+    let mut symbol_ids = HashSet::new();
+    let mut symbols = String::new();
+
+    for (name, svg_content) in icons {
+        let root = SvgRoot::parse(svg_content).map_err(|e| format!("{name}: {e}"))?;
+
+        // Repeated names get a numeric suffix rather than an invalid duplicate id
+        let mut id = symbol_id(name);
+        let mut suffix = 2;
+        while !symbol_ids.insert(id.clone()) {
+            id = format!("{}-{suffix}", symbol_id(name));
+            suffix += 1;
+        }
+
+        for (key, value) in &root.attributes {
+            if key.starts_with("xmlns:") && !namespaces.iter().any(|(k, _)| k == key) {
+                namespaces.push((key.clone(), value.clone()));
+            }
+        }
+
+        symbols.push_str(&format!(r#"<symbol id="{}""#, escape(&id)));
+        if let Some(view_box) = root.view_box() {
+            symbols.push_str(&format!(r#" viewBox="{}""#, escape(&view_box)));
+        }
+        if let Some(aspect) = root.attribute("preserveAspectRatio") {
+            symbols.push_str(&format!(r#" preserveAspectRatio="{}""#, escape(aspect)));
+        }
+        symbols.push('>');
+        symbols.push_str(&prefix_ids(&svg_content[root.content], &id));
+        symbols.push_str("</symbol>");
+    }
+
+    let mut sprite = String::from(r#"<svg xmlns="http://www.w3.org/2000/svg""#);
+    for (key, value) in namespaces {
+        sprite.push_str(&format!(r#" {key}="{}""#, escape(&value)));
+    }
+    sprite.push('>');
+    sprite.push_str(&symbols);
+    sprite.push_str("</svg>");
+
+    Ok(sprite)
+}
+
+/// Prefix every id declared in `content`, and every local reference to one, with
+/// `prefix` and a `.`
+fn prefix_ids(content: &str, prefix: &str) -> String {
+    let declared: HashSet<&str> = ID_RE
+        .captures_iter(content)
+        .filter_map(|caps| caps.get(1).or(caps.get(2)))
+        .map(|id| id.as_str())
+        .collect();
+
+    if declared.is_empty() {
+        return content.to_owned();
+    }
+
+    ID_REF_RE
+        .replace_all(content, |caps: &Captures| {
+            // Each alternative captures (before, id, after) in consecutive groups
+            let group = (1..=7).step_by(3).find(|g| caps.get(*g).is_some()).unwrap();
+            let id = &caps[group + 1];
+            if declared.contains(id) {
+                format!("{}{prefix}.{id}{}", &caps[group], &caps[group + 2])
+            } else {
+                caps[0].to_owned()
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbol_ids_are_slugs() {
+        assert_eq!(symbol_id("icons:Arrow Left"), "icons-arrow-left");
+        assert_eq!(symbol_id("a_b-1"), "a_b-1");
+    }
+
+    #[test]
+    fn local_ids_are_prefixed() {
+        let icons = [
+            (
+                "a".to_owned(),
+                r##"<svg xmlns:xlink="http://www.w3.org/1999/xlink" width="24" height="24"><linearGradient id="g"/><linearGradient id="2-g"/><rect fill="url(#g)" clip-path="url(#other)"/><use xlink:href="#g"/></svg>"##.to_owned(),
+            ),
+            (
+                "a".to_owned(),
+                r##"<svg viewBox="0 0 10 10"><linearGradient id="g"/><rect fill="url('#g')"/></svg>"##.to_owned(),
+            ),
+        ];
+
+        // "2-g" of "a" and "g" of "a-2" both read "a-2-g" with a dash in between
+        assert_eq!(
+            svg_sprite(&icons).unwrap(),
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#,
+                r##"<symbol id="a" viewBox="0 0 24 24"><linearGradient id="a.g"/><linearGradient id="a.2-g"/><rect fill="url(#a.g)" clip-path="url(#other)"/><use xlink:href="#a.g"/></symbol>"##,
+                r##"<symbol id="a-2" viewBox="0 0 10 10"><linearGradient id="a-2.g"/><rect fill="url('#a-2.g')"/></symbol>"##,
+                "</svg>"
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_documents_are_reported_by_name() {
+        let icons = [("broken".to_owned(), "<html/>".to_owned())];
+        assert!(svg_sprite(&icons).unwrap_err().starts_with("broken:"));
+    }
+}
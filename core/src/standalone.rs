@@ -0,0 +1,369 @@
+use std::{collections::HashMap, ops::Range};
+
+use quick_xml::{
+    Reader,
+    escape::{escape, unescape},
+    events::{BytesStart, Event},
+};
+
+/// Longest chain of `<use>` elements inlined; deeper ones, as in reference cycles, are
+/// left as they are
+const MAX_DEPTH: usize = 8;
+
+/// Most `<use>` elements inlined in one document, so references nesting references
+/// can't multiply its size out of bounds
+const MAX_INLINED: usize = 10_000;
+
+/// Elements dropped from standalone copies, as they run code or embed HTML
+const UNSAFE_ELEMENTS: [&[u8]; 2] = [b"script", b"foreignObject"];
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+/// Make an SVG document stand on its own, for pasting into design tools that don't
+/// resolve references and shouldn't run anything:
+///
+/// - every `<use>` of an element in the document is replaced by a copy of it, in a
+///   `<g>` carrying the `<use>`'s attributes and offset, `<symbol>`s becoming `<svg>`s
+///   of the `<use>`'s size;
+/// - `<script>` and `<foreignObject>` elements, `on*` event handlers and `javascript:`
+///   links are removed;
+/// - the prolog, doctype and anything after the root are dropped, and the root gets
+///   the namespace declarations it relies on.
+///
+/// Definitions stay in place, as gradients, clip paths and the like are still referred
+/// to by `url(#id)`.
+pub fn svg_standalone(svg_content: &str) -> Result<String, String> {
+    let mut copy = Standalone {
+        source: svg_content,
+        ids: element_ids(svg_content)?,
+        inlined: 0,
+        out: String::with_capacity(svg_content.len()),
+    };
+    copy.write(0..svg_content.len(), 0, None)?;
+    if copy.out.is_empty() {
+        return Err("No SVG start found".to_owned());
+    }
+    Ok(copy.out)
+}
+
+struct Standalone<'a> {
+    source: &'a str,
+    /// Span of every element with an id, the first one winning
+    ids: HashMap<String, Range<usize>>,
+    inlined: usize,
+    out: String,
+}
+
+/// Size a `<use>` gives the `<symbol>` or `<svg>` it copies, raw attribute values
+#[derive(Default)]
+struct UseSize {
+    width: Option<String>,
+    height: Option<String>,
+}
+
+impl Standalone<'_> {
+    /// Copy the elements in `range` of the source, the whole document when `size` is
+    /// `None`, or else an element inlined at `depth` in place of a `<use>` of that size
+    fn write(
+        &mut self,
+        range: Range<usize>,
+        depth: usize,
+        size: Option<UseSize>,
+    ) -> Result<(), String> {
+        let source = self.source;
+        let fragment = &source[range];
+        let document = size.is_none();
+        let mut reader = Reader::from_str(fragment);
+        let mut level = 0usize;
+        let mut renamed = false;
+
+        loop {
+            let before = reader.buffer_position() as usize;
+            let event = reader
+                .read_event()
+                .map_err(|e| format!("Invalid SVG: {e}"))?;
+            let after = reader.buffer_position() as usize;
+
+            let (start, empty) = match event {
+                Event::Eof => return Ok(()),
+                Event::Start(start) => (start, false),
+                Event::Empty(start) => (start, true),
+                Event::End(_) => {
+                    level = level.saturating_sub(1);
+                    match level {
+                        0 if renamed => self.out.push_str("</svg>"),
+                        _ => self.out.push_str(&fragment[before..after]),
+                    }
+                    if document && level == 0 {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                Event::Decl(_) | Event::DocType(_) | Event::PI(_) => continue,
+                // The prolog and whatever surrounds the root
+                _ if document && level == 0 => continue,
+                _ => {
+                    self.out.push_str(&fragment[before..after]);
+                    continue;
+                }
+            };
+
+            let local_name = start.local_name();
+            if UNSAFE_ELEMENTS.contains(&local_name.as_ref()) {
+                if !empty {
+                    reader
+                        .read_to_end(start.name())
+                        .map_err(|e| format!("Invalid SVG: {e}"))?;
+                }
+                continue;
+            }
+
+            if local_name.as_ref() == b"use"
+                && depth < MAX_DEPTH
+                && self.inlined < MAX_INLINED
+                && let Some(target) = self.use_target(&start)?
+            {
+                // Children of a <use>, such as its <title>, go with it
+                if !empty {
+                    reader
+                        .read_to_end(start.name())
+                        .map_err(|e| format!("Invalid SVG: {e}"))?;
+                }
+                self.inlined += 1;
+                self.inline_use(&start, target, depth)?;
+                continue;
+            }
+
+            let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+            match (&size, level) {
+                (None, 0) => {
+                    let mut extra = Vec::new();
+                    if start.try_get_attribute("xmlns").ok().flatten().is_none() {
+                        extra.push(("xmlns", SVG_NAMESPACE.to_owned()));
+                    }
+                    let declared = start.try_get_attribute("xmlns:xlink").ok().flatten();
+                    if source.contains("xlink:") && declared.is_none() {
+                        extra.push(("xmlns:xlink", XLINK_NAMESPACE.to_owned()));
+                    }
+                    self.write_tag(&start, &name, empty, &[], &extra)?;
+                }
+                // The copied element itself, whose id would now be a duplicate
+                (Some(size), 0) if matches!(local_name.as_ref(), b"symbol" | b"svg") => {
+                    let extra = [
+                        ("width", size.width.clone().unwrap_or("100%".to_owned())),
+                        ("height", size.height.clone().unwrap_or("100%".to_owned())),
+                    ];
+                    let skip: &[&[u8]] = &[b"id", b"width", b"height"];
+                    self.write_tag(&start, "svg", empty, skip, &extra)?;
+                    renamed = !empty;
+                }
+                (Some(_), 0) => self.write_tag(&start, &name, empty, &[b"id"], &[])?,
+                _ => self.write_tag(&start, &name, empty, &[], &[])?,
+            }
+            if !empty {
+                level += 1;
+            } else if !document && level == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Span of the element a `<use>` refers to, if it is in the document
+    fn use_target(&self, element: &BytesStart) -> Result<Option<Range<usize>>, String> {
+        let href = element
+            .try_get_attribute("href")
+            .ok()
+            .flatten()
+            .or(element.try_get_attribute("xlink:href").ok().flatten());
+        let Some(href) = href else {
+            return Ok(None);
+        };
+        let href = href
+            .unescape_value()
+            .map_err(|e| format!("Invalid SVG attribute: {e}"))?;
+        Ok(href
+            .strip_prefix('#')
+            .and_then(|id| self.ids.get(id))
+            .cloned())
+    }
+
+    /// Replace a `<use>` by a group holding a copy of `target`
+    fn inline_use(
+        &mut self,
+        element: &BytesStart,
+        target: Range<usize>,
+        depth: usize,
+    ) -> Result<(), String> {
+        let value = |name: &str| -> Option<String> {
+            let attribute = element.try_get_attribute(name).ok().flatten()?;
+            Some(attribute.unescape_value().ok()?.into_owned())
+        };
+        let offset = |name| {
+            value(name)
+                .and_then(|v| v.trim().trim_end_matches("px").parse::<f64>().ok())
+                .unwrap_or(0.0)
+        };
+        let (x, y) = (offset("x"), offset("y"));
+
+        // The offset applies after the <use>'s own transform
+        let mut transform = value("transform").unwrap_or_default();
+        if x != 0.0 || y != 0.0 {
+            transform = format!("{transform} translate({x} {y})").trim().to_owned();
+        }
+        let extra: Vec<_> = (!transform.is_empty())
+            .then_some(("transform", transform))
+            .into_iter()
+            .collect();
+        let skip: &[&[u8]] = &[
+            b"href",
+            b"xlink:href",
+            b"x",
+            b"y",
+            b"width",
+            b"height",
+            b"transform",
+        ];
+        self.write_tag(element, "g", false, skip, &extra)?;
+        let size = UseSize {
+            width: value("width"),
+            height: value("height"),
+        };
+        self.write(target, depth + 1, Some(size))?;
+        self.out.push_str("</g>");
+        Ok(())
+    }
+
+    /// Write a start tag as `name`, without the attributes in `skip` or unsafe ones,
+    /// followed by `extra` ones
+    fn write_tag(
+        &mut self,
+        element: &BytesStart,
+        name: &str,
+        empty: bool,
+        skip: &[&[u8]],
+        extra: &[(&str, String)],
+    ) -> Result<(), String> {
+        self.out.push('<');
+        self.out.push_str(name);
+        for attribute in element.attributes() {
+            let attribute = attribute.map_err(|e| format!("Invalid SVG attribute: {e}"))?;
+            let key = attribute.key.as_ref();
+            if skip.contains(&key) || attribute.key.local_name().as_ref().starts_with(b"on") {
+                continue;
+            }
+            let raw = String::from_utf8_lossy(&attribute.value);
+            if matches!(key, b"href" | b"xlink:href") && is_script_link(&raw) {
+                continue;
+            }
+            self.out.push_str(&format!(
+                " {}=\"{}\"",
+                String::from_utf8_lossy(key),
+                raw.replace('"', "&quot;")
+            ));
+        }
+        for (key, value) in extra {
+            self.out.push_str(&format!(" {key}=\"{}\"", escape(value)));
+        }
+        self.out.push_str(if empty { "/>" } else { ">" });
+        Ok(())
+    }
+}
+
+/// Whether a raw `href` runs script when followed, however it is spaced or escaped
+fn is_script_link(raw: &str) -> bool {
+    let href = unescape(raw).map_or_else(|_| raw.to_owned(), |href| href.into_owned());
+    let scheme: String = href
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_ascii_control())
+        .take("javascript:".len())
+        .collect();
+    scheme.eq_ignore_ascii_case("javascript:")
+}
+
+/// Span of every element with an `id`, from its start tag to its end tag
+fn element_ids(svg_content: &str) -> Result<HashMap<String, Range<usize>>, String> {
+    let mut reader = Reader::from_str(svg_content);
+    let mut open: Vec<Option<(String, usize)>> = Vec::new();
+    let mut ids = HashMap::new();
+    let id = |start: &BytesStart| -> Option<String> {
+        let id = start.try_get_attribute("id").ok().flatten()?;
+        Some(id.unescape_value().ok()?.into_owned())
+    };
+
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("Invalid SVG: {e}"))?;
+        let after = reader.buffer_position() as usize;
+        match event {
+            Event::Start(start) => open.push(id(&start).map(|id| (id, before))),
+            Event::Empty(start) => {
+                if let Some(id) = id(&start) {
+                    ids.entry(id).or_insert(before..after);
+                }
+            }
+            Event::End(_) => {
+                if let Some(Some((id, start))) = open.pop() {
+                    ids.entry(id).or_insert(start..after);
+                }
+            }
+            Event::Eof => return Ok(ids),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_are_replaced_by_what_they_refer_to() {
+        let svg = concat!(
+            "<?xml version=\"1.0\"?>\n<!-- Exported -->\n",
+            r##"<svg viewBox="0 0 40 20" xmlns:xlink="http://www.w3.org/1999/xlink">"##,
+            r##"<defs><linearGradient id="fade"/><symbol id="dot" viewBox="0 0 2 2"><circle id="c" r="1" fill="url(#fade)"/></symbol></defs>"##,
+            r##"<use xlink:href="#dot" x="10" y="5" width="4" height="4" class="node"><title>Dot</title></use>"##,
+            r##"<use href="#c" transform="scale(2)"/><use href="#missing"/>"##,
+            "</svg>\n<!-- trailer -->",
+        );
+        assert_eq!(
+            svg_standalone(svg).unwrap(),
+            concat!(
+                r##"<svg viewBox="0 0 40 20" xmlns:xlink="http://www.w3.org/1999/xlink" xmlns="http://www.w3.org/2000/svg">"##,
+                r##"<defs><linearGradient id="fade"/><symbol id="dot" viewBox="0 0 2 2"><circle id="c" r="1" fill="url(#fade)"/></symbol></defs>"##,
+                r##"<g class="node" transform="translate(10 5)"><svg viewBox="0 0 2 2" width="4" height="4"><circle id="c" r="1" fill="url(#fade)"/></svg></g>"##,
+                r##"<g transform="scale(2)"><circle r="1" fill="url(#fade)"/></g><use href="#missing"/>"##,
+                "</svg>",
+            )
+        );
+    }
+
+    #[test]
+    fn scripts_are_removed() {
+        let svg = concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" onload="alert(1)"><script>alert(2)</script>"#,
+            r#"<a href=" JavaScript:alert(3)"><rect onclick='go()' width="1"/></a>"#,
+            r#"<a href="https://example.com"><foreignObject><p>Hi</p></foreignObject></a></svg>"#,
+        );
+        assert_eq!(
+            svg_standalone(svg).unwrap(),
+            concat!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg"><a><rect width="1"/></a>"#,
+                r#"<a href="https://example.com"></a></svg>"#,
+            )
+        );
+    }
+
+    #[test]
+    fn reference_cycles_stop() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg"><g id="loop"><use href="#loop"/></g></svg>"##;
+        let copy = svg_standalone(svg).unwrap();
+        // A group for each <use>, around a copy of the group it refers to
+        assert_eq!(copy.matches("<g>").count(), 2 * MAX_DEPTH);
+        assert_eq!(copy.matches(r##"<use href="#loop"/>"##).count(), 1);
+        assert!(svg_standalone("<!-- empty -->").is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use quick_xml::{Reader, events::Event};
+use serde::Serialize;
+
+/// What a document is made of, and roughly how hard it is for a browser to draw
+#[derive(Debug, Default, Serialize)]
+pub struct SvgStatistics {
+    /// Document size in bytes
+    pub size_bytes: usize,
+    /// Total number of elements, root included
+    pub elements: usize,
+    /// Element counts keyed by element name
+    pub elements_by_type: BTreeMap<String, usize>,
+    pub paths: PathStatistics,
+    /// `linearGradient` and `radialGradient` definitions
+    pub gradients: usize,
+    pub patterns: usize,
+    pub filters: FilterStatistics,
+    /// Elements with a `mask`
+    pub masked_elements: usize,
+    /// Elements with a `clip-path`
+    pub clipped_elements: usize,
+    /// Relative drawing cost, see [`SvgStatistics::render_cost`]
+    pub render_cost: f64,
+}
+
+/// Geometry complexity of `path`, `polyline` and `polygon` elements
+#[derive(Debug, Default, Serialize)]
+pub struct PathStatistics {
+    /// Number of path-like elements
+    pub count: usize,
+    /// Nodes (segments or points) across all of them
+    pub nodes: usize,
+    /// Nodes in the most complex single element
+    pub max_nodes: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct FilterStatistics {
+    /// `filter` definitions
+    pub definitions: usize,
+    /// `fe*` primitives across all definitions
+    pub primitives: usize,
+    /// Elements drawn through a filter
+    pub filtered_elements: usize,
+}
+
+impl SvgStatistics {
+    /// Collect statistics for a whole document
+    pub fn collect(svg_content: &str) -> Result<Self, String> {
+        let mut stats = SvgStatistics {
+            size_bytes: svg_content.len(),
+            ..Default::default()
+        };
+
+        let mut reader = Reader::from_str(svg_content);
+        loop {
+            let element = match reader.read_event() {
+                Ok(Event::Start(element) | Event::Empty(element)) => element,
+                Ok(Event::Eof) => break,
+                Ok(_) => continue,
+                Err(e) => return Err(format!("Invalid SVG: {e}")),
+            };
+
+            let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+            stats.elements += 1;
+
+            let mut nodes = None;
+            let mut style = String::new();
+            let (mut filtered, mut masked, mut clipped) = (false, false, false);
+            for attr in element.attributes().flatten() {
+                let value = attr.unescape_value().unwrap_or_default();
+                match attr.key.local_name().as_ref() {
+                    b"d" if name == "path" => nodes = Some(path_nodes(&value)),
+                    b"points" if name == "polyline" || name == "polygon" => {
+                        nodes = Some(numbers(&value).count() / 2)
+                    }
+                    b"filter" => filtered = value.trim() != "none",
+                    b"mask" => masked = value.trim() != "none",
+                    b"clip-path" => clipped = value.trim() != "none",
+                    b"style" => style = value.into_owned(),
+                    _ => {}
+                }
+            }
+
+            // Presentation attributes can also be set through inline styles
+            for declaration in style.split(';') {
+                let Some((property, value)) = declaration.split_once(':') else {
+                    continue;
+                };
+                let used = value.trim() != "none";
+                match property.trim() {
+                    "filter" => filtered |= used,
+                    "mask" => masked |= used,
+                    "clip-path" => clipped |= used,
+                    _ => {}
+                }
+            }
+
+            if let Some(nodes) = nodes {
+                stats.paths.count += 1;
+                stats.paths.nodes += nodes;
+                stats.paths.max_nodes = stats.paths.max_nodes.max(nodes);
+            }
+            stats.filters.filtered_elements += usize::from(filtered);
+            stats.masked_elements += usize::from(masked);
+            stats.clipped_elements += usize::from(clipped);
+
+            match name.as_str() {
+                "linearGradient" | "radialGradient" => stats.gradients += 1,
+                "pattern" => stats.patterns += 1,
+                "filter" => stats.filters.definitions += 1,
+                primitive if primitive.starts_with("fe") => stats.filters.primitives += 1,
+                _ => {}
+            }
+
+            *stats.elements_by_type.entry(name).or_default() += 1;
+        }
+
+        if stats.elements == 0 {
+            return Err("No SVG start found".to_owned());
+        }
+
+        stats.render_cost = stats.render_cost();
+        Ok(stats)
+    }
+
+    /// A unitless estimate of drawing cost.
+    ///
+    /// Every element costs 1 and every path node 0.1. Filtered elements cost 25 per
+    /// primitive of an average filter, masked elements 15, clipped elements 3, and each
+    /// `text` and `image` element 2 and 10. The weights only matter relative to each
+    /// other; they point at what dominates a slow diagram rather than predict timings.
+    pub fn render_cost(&self) -> f64 {
+        let count = |name: &str| self.elements_by_type.get(name).copied().unwrap_or(0) as f64;
+        let primitives_per_filter = if self.filters.definitions > 0 {
+            (self.filters.primitives as f64 / self.filters.definitions as f64).max(1.0)
+        } else {
+            1.0
+        };
+
+        self.elements as f64
+            + 0.1 * self.paths.nodes as f64
+            + 25.0 * primitives_per_filter * self.filters.filtered_elements as f64
+            + 15.0 * self.masked_elements as f64
+            + 3.0 * self.clipped_elements as f64
+            + 2.0 * count("text")
+            + 10.0 * count("image")
+    }
+}
+
+/// Numbers in a path or points list, accepting the compact forms SVG allows (`1-2`, `.5.5`)
+fn numbers(value: &str) -> impl Iterator<Item = &str> {
+    let bytes = value.as_bytes();
+    let mut pos = 0;
+
+    std::iter::from_fn(move || {
+        while pos < bytes.len() && !matches!(bytes[pos], b'0'..=b'9' | b'.' | b'-' | b'+') {
+            pos += 1;
+        }
+        if pos >= bytes.len() {
+            return None;
+        }
+
+        let start = pos;
+        let (mut seen_dot, mut seen_exp) = (false, false);
+        pos += 1;
+        if bytes[start] == b'.' {
+            seen_dot = true;
+        }
+        while pos < bytes.len() {
+            match bytes[pos] {
+                b'0'..=b'9' => {}
+                b'.' if !seen_dot && !seen_exp => seen_dot = true,
+                b'e' | b'E' if !seen_exp => seen_exp = true,
+                b'-' | b'+' if matches!(bytes[pos - 1], b'e' | b'E') => {}
+                _ => break,
+            }
+            pos += 1;
+        }
+        Some(&value[start..pos])
+    })
+}
+
+/// Number of segments drawn by a path's `d` attribute, counting implicitly repeated commands
+pub fn path_nodes(d: &str) -> usize {
+    let mut nodes = 0;
+    let mut command_starts = d
+        .match_indices(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E')
+        .peekable();
+
+    while let Some((start, command)) = command_starts.next() {
+        let end = command_starts.peek().map_or(d.len(), |(next, _)| *next);
+        let args = numbers(&d[start + 1..end]).count();
+        let arity = match command.to_ascii_uppercase().as_str() {
+            "M" | "L" | "T" => 2,
+            "H" | "V" => 1,
+            "S" | "Q" => 4,
+            "C" => 6,
+            "A" => 7,
+            _ => 0,
+        };
+        nodes += if arity == 0 { 1 } else { args.div_ceil(arity) };
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_nodes_count_implicit_commands() {
+        assert_eq!(path_nodes("M0 0 L10 10 20 20Z"), 4);
+        assert_eq!(path_nodes("m0,0c1,1,2,2,3,3 4,4,5,5,6,6"), 3);
+        assert_eq!(path_nodes("M.5.5-1-1"), 2);
+        assert_eq!(path_nodes("M1e-3 2E+2h5v5"), 3);
+        assert_eq!(path_nodes(""), 0);
+    }
+
+    #[test]
+    fn elements_and_effects_are_counted() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg">
+            <defs>
+                <linearGradient id="g"/>
+                <filter id="f"><feGaussianBlur/><feOffset/></filter>
+            </defs>
+            <path d="M0 0L1 1L2 2" filter="url(#f)"/>
+            <polygon points="0,0 1,1 2,0" style="clip-path: url(#c); mask:none"/>
+            <text>hi</text>
+        </svg>"##;
+        let stats = SvgStatistics::collect(svg).unwrap();
+
+        assert_eq!(stats.elements, 9);
+        assert_eq!(stats.elements_by_type["feGaussianBlur"], 1);
+        assert_eq!(stats.paths.count, 2);
+        assert_eq!(stats.paths.nodes, 6);
+        assert_eq!(stats.paths.max_nodes, 3);
+        assert_eq!(stats.gradients, 1);
+        assert_eq!(stats.filters.definitions, 1);
+        assert_eq!(stats.filters.primitives, 2);
+        assert_eq!(stats.filters.filtered_elements, 1);
+        assert_eq!(stats.clipped_elements, 1);
+        assert_eq!(stats.masked_elements, 0);
+        assert!((stats.render_cost - (9.0 + 0.6 + 50.0 + 3.0 + 2.0)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,118 @@
+use std::ops::Range;
+
+use quick_xml::{Reader, events::Event};
+
+/// The root `<svg>` element of a document
+#[derive(Debug, Clone)]
+pub struct SvgRoot {
+    /// Root attributes in document order, values unescaped
+    pub attributes: Vec<(String, String)>,
+    /// Byte range of everything between the root start and end tags
+    pub content: Range<usize>,
+}
+
+impl SvgRoot {
+    /// Locate and parse the root element, skipping any prolog, comments and doctype
+    pub fn parse(svg_content: &str) -> Result<Self, String> {
+        let mut reader = Reader::from_str(svg_content);
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| format!("Invalid SVG: {e}"))?;
+
+            let (start, is_empty) = match event {
+                Event::Start(start) => (start, false),
+                Event::Empty(start) => (start, true),
+                Event::Eof => return Err("No SVG start found".to_owned()),
+                Event::Text(text) if !text.iter().all(u8::is_ascii_whitespace) => {
+                    return Err("Unexpected text before the SVG root".to_owned());
+                }
+                _ => continue,
+            };
+
+            if start.local_name().as_ref() != b"svg" {
+                return Err("Root element is not <svg>".to_owned());
+            }
+
+            let attributes = start
+                .attributes()
+                .map(|attr| {
+                    let attr = attr.map_err(|e| format!("Invalid SVG root attribute: {e}"))?;
+                    let value = attr
+                        .unescape_value()
+                        .map_err(|e| format!("Invalid SVG root attribute: {e}"))?;
+                    let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+                    Ok((key, value.into_owned()))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+
+            let content_start = reader.buffer_position() as usize;
+            let content = if is_empty {
+                content_start..content_start
+            } else {
+                let span = reader
+                    .read_to_end(start.name())
+                    .map_err(|e| format!("Invalid SVG: {e}"))?;
+                span.start as usize..span.end as usize
+            };
+
+            return Ok(SvgRoot {
+                attributes,
+                content,
+            });
+        }
+    }
+
+    /// Value of a root attribute
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The root `viewBox`, derived from `width`/`height` when missing
+    pub fn view_box(&self) -> Option<String> {
+        if let Some(view_box) = self.attribute("viewBox") {
+            return Some(view_box.to_owned());
+        }
+
+        let length = |name| {
+            let value = self.attribute(name)?.trim();
+            value
+                .strip_suffix("px")
+                .unwrap_or(value)
+                .parse::<f64>()
+                .ok()
+        };
+        Some(format!("0 0 {} {}", length("width")?, length("height")?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prolog_is_skipped() {
+        let svg = "<?xml version=\"1.0\"?>\n<!-- comment -->\n<!DOCTYPE svg>\n<svg width=\"2\" height=\"3px\"><g/></svg>\n";
+        let root = SvgRoot::parse(svg).unwrap();
+        assert_eq!(&svg[root.content.clone()], "<g/>");
+        assert_eq!(root.attribute("width"), Some("2"));
+        assert_eq!(root.view_box().as_deref(), Some("0 0 2 3"));
+    }
+
+    #[test]
+    fn empty_root_has_no_content() {
+        let root = SvgRoot::parse(r#"<svg viewBox="0 0 1 1"/>"#).unwrap();
+        assert!(root.content.is_empty());
+        assert_eq!(root.view_box().as_deref(), Some("0 0 1 1"));
+    }
+
+    #[test]
+    fn non_svg_root_is_an_error() {
+        assert!(SvgRoot::parse("<html><svg/></html>").is_err());
+        assert!(SvgRoot::parse("").is_err());
+    }
+}
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use svg_server_core::{statistics, svg::SvgRoot};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(svg) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    if let Ok(root) = SvgRoot::parse(svg) {
+        // The content range must always slice the input on character boundaries
+        let _ = &svg[root.content.clone()];
+        let _ = root.attribute("width");
+        let _ = root.view_box();
+    }
+    let _ = statistics::SvgStatistics::collect(svg);
+    let _ = statistics::path_nodes(svg);
+});
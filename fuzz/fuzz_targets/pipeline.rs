@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use svg_server_core::{
+    contrast::{ContrastConfig, svg_high_contrast},
+    links::svg_rewrite_links,
+    resize::svg_size_full_width,
+    simulate::{Simulation, svg_simulate},
+};
+
+// The same transforms, in the same order, as a page rendered with
+// ?contrast=high&simulate=...
+fuzz_target!(|data: &[u8]| {
+    let Ok(svg) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let linked = svg_rewrite_links(svg, "network/overview", |page| format!("/{page}"));
+    let Ok(resized) = svg_size_full_width(&linked) else {
+        return;
+    };
+    let contrasted = svg_high_contrast(&resized, &ContrastConfig::default());
+    for simulation in [
+        Simulation::Deuteranopia,
+        Simulation::Protanopia,
+        Simulation::Tritanopia,
+    ] {
+        let _ = svg_simulate(&contrasted, simulation);
+    }
+});
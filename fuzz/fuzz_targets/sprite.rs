@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use svg_server_core::sprite::svg_sprite;
+
+// NUL bytes separate the icons merged into one sprite, so id clashes and namespace
+// merging across documents get exercised
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let icons: Vec<_> = input
+        .split('\0')
+        .enumerate()
+        .map(|(i, svg)| (format!("icon-{}", i % 3), svg.to_owned()))
+        .collect();
+    let _ = svg_sprite(&icons);
+});
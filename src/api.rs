@@ -0,0 +1,153 @@
+use std::fmt;
+
+use actix_web::{
+    HttpRequest, HttpResponse, ResponseError,
+    http::{StatusCode, header::HeaderName},
+};
+use serde::Serialize;
+
+use crate::{ListingEnabled, trace::TraceContext};
+
+/// Header carrying the id that API errors are reported with
+pub(crate) const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Error body of every `/api/*` route
+#[derive(Debug, Serialize)]
+pub(crate) struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    /// Stable, machine-readable error kind
+    code: &'static str,
+    /// Human-readable description
+    message: String,
+    /// Id to quote when reporting the error, also sent as `X-Request-Id`
+    request_id: String,
+    /// Extra context specific to the error kind
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub(crate) fn new(
+        req: &HttpRequest,
+        status: StatusCode,
+        code: &'static str,
+        message: impl Into<String>,
+    ) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+            request_id: request_id(req),
+            details: None,
+        }
+    }
+
+    pub(crate) fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status)
+            .insert_header((REQUEST_ID, self.request_id.clone()))
+            .json(self)
+    }
+}
+
+/// The client's `X-Request-Id` when it is a reasonable one, so errors can be traced
+/// across proxies, otherwise the id of the trace the request is part of
+fn request_id(req: &HttpRequest) -> String {
+    req.headers()
+        .get(REQUEST_ID)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= 64
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        })
+        .map_or_else(|| TraceContext::of(req).trace_id, str::to_owned)
+}
+
+/// Fallback for `/api/*` paths no route matches
+pub(crate) async fn not_found(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(no_route(&req))
+}
+
+/// Refuse `req` as if no route matched unless listing is enabled, for the routes a
+/// [`ListingEnabled`] server alone serves
+pub(crate) fn listing_only(req: &HttpRequest) -> Result<(), ApiError> {
+    if !ListingEnabled::of(req) {
+        return Err(no_route(req));
+    }
+    Ok(())
+}
+
+fn no_route(req: &HttpRequest) -> ApiError {
+    let message = format!("No API route at {}", req.path());
+    ApiError::new(req, StatusCode::NOT_FOUND, "not_found", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{body::to_bytes, test::TestRequest};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn errors_carry_code_message_and_request_id() {
+        let req = TestRequest::default()
+            .insert_header((REQUEST_ID, "abc-123"))
+            .to_http_request();
+        let res = ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_page", "Nope")
+            .with_details(serde_json::json!({ "page": ".." }))
+            .error_response();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.headers().get(REQUEST_ID).unwrap(), "abc-123");
+        let body: serde_json::Value =
+            serde_json::from_slice(&to_bytes(res.into_body()).await.unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "code": "invalid_page",
+                "message": "Nope",
+                "request_id": "abc-123",
+                "details": { "page": ".." }
+            })
+        );
+    }
+
+    #[test]
+    fn unusable_request_ids_are_replaced() {
+        for id in ["", "has spaces", &"x".repeat(65)] {
+            let req = TestRequest::default()
+                .insert_header((REQUEST_ID, id))
+                .to_http_request();
+            let generated = request_id(&req);
+            assert_eq!(generated.len(), 32, "{id:?}");
+        }
+
+        let req = TestRequest::default()
+            .insert_header((
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ))
+            .to_http_request();
+        assert_eq!(request_id(&req), "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+}
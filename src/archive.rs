@@ -0,0 +1,131 @@
+use std::io::Write;
+
+use flate2::{Compression, write::GzEncoder};
+
+/// Size of tar headers and of the blocks file contents are padded to
+const BLOCK: usize = 512;
+
+/// Gzipped tarball written one file at a time, handing over the compressed bytes as
+/// they come so a response can stream them
+pub(crate) struct TarGz {
+    encoder: GzEncoder<Vec<u8>>,
+}
+
+impl TarGz {
+    pub(crate) fn new() -> Self {
+        TarGz {
+            encoder: GzEncoder::new(Vec::new(), Compression::default()),
+        }
+    }
+
+    /// Add `content` as the file at `path` (`/` separated), modified at `mtime`
+    /// seconds since the Unix epoch, returning the bytes compressed so far
+    pub(crate) fn append(
+        &mut self,
+        path: &str,
+        content: &[u8],
+        mtime: u64,
+    ) -> Result<Vec<u8>, String> {
+        let header = header(path, content.len() as u64, mtime)?;
+        let padding = (BLOCK - content.len() % BLOCK) % BLOCK;
+        self.encoder
+            .write_all(&header)
+            .and_then(|()| self.encoder.write_all(content))
+            .and_then(|()| self.encoder.write_all(&[0; BLOCK][..padding]))
+            .map_err(|e| e.to_string())?;
+        Ok(std::mem::take(self.encoder.get_mut()))
+    }
+
+    /// End the archive, returning its last bytes
+    pub(crate) fn finish(mut self) -> Result<Vec<u8>, String> {
+        self.encoder
+            .write_all(&[0; 2 * BLOCK])
+            .and_then(|()| self.encoder.finish())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// ustar header of a regular file, splitting paths over 100 bytes between the `name`
+/// and `prefix` fields
+fn header(path: &str, size: u64, mtime: u64) -> Result<[u8; BLOCK], String> {
+    let (prefix, name) = match path.len() {
+        0..=100 => ("", path),
+        _ => path
+            .char_indices()
+            .filter(|&(i, c)| c == '/' && i <= 155 && path.len() - i - 1 <= 100)
+            .map(|(i, _)| (&path[..i], &path[i + 1..]))
+            .next()
+            .ok_or(format!("Path '{path}' is too long for a tarball"))?,
+    };
+
+    let mut header = [0u8; BLOCK];
+    let mut field = |offset: usize, value: &[u8]| {
+        header[offset..offset + value.len()].copy_from_slice(value);
+    };
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{size:011o}\0").as_bytes());
+    field(136, format!("{mtime:011o}\0").as_bytes());
+    // Summed as spaces, then replaced by the sum
+    field(148, b"        ");
+    field(156, b"0");
+    field(257, b"ustar\x0000");
+    field(345, prefix.as_bytes());
+
+    let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+    header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn octal(field: &[u8]) -> u64 {
+        let digits = std::str::from_utf8(field).unwrap();
+        u64::from_str_radix(digits.trim_end_matches(['\0', ' ']), 8).unwrap()
+    }
+
+    #[test]
+    fn files_are_archived_in_blocks() {
+        let mut archive = TarGz::new();
+        let mut gz = archive
+            .append("icons/arrow.svg", b"<svg/>", 1_700_000_000)
+            .unwrap();
+        let long = format!("{}/logo.svg", "nested/".repeat(20).trim_end_matches('/'));
+        gz.extend(archive.append(&long, &[b'x'; 600], 0).unwrap());
+        gz.extend(archive.finish().unwrap());
+
+        let mut tar = Vec::new();
+        GzDecoder::new(&gz[..]).read_to_end(&mut tar).unwrap();
+        assert_eq!(tar.len(), 512 + 512 + 512 + 1024 + 1024);
+
+        let first = &tar[..512];
+        assert!(first.starts_with(b"icons/arrow.svg\0"));
+        assert_eq!(octal(&first[124..136]), 6);
+        assert_eq!(octal(&first[136..148]), 1_700_000_000);
+        assert_eq!(&first[257..265], b"ustar\x0000");
+        let mut summed = first.to_vec();
+        summed[148..156].fill(b' ');
+        let sum: u64 = summed.iter().map(|&b| u64::from(b)).sum();
+        assert_eq!(octal(&first[148..156]), sum);
+        assert_eq!(&tar[512..518], b"<svg/>");
+
+        let second = &tar[1024..1536];
+        let text = |field: &[u8]| String::from_utf8(field.to_vec()).unwrap().replace('\0', "");
+        let (name, prefix) = (text(&second[..100]), text(&second[345..500]));
+        assert!(name.ends_with("/logo.svg"));
+        assert_eq!(format!("{prefix}/{name}"), long);
+        assert_eq!(octal(&second[124..136]), 600);
+        assert!(tar[2560..].iter().all(|&b| b == 0));
+
+        let too_long = "x".repeat(101);
+        assert!(TarGz::new().append(&too_long, b"", 0).is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use actix_web::{
+    Error, HttpMessage, HttpRequest, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+
+use crate::guest::GuestAccess;
+
+/// Decides which requests the viewer serves.
+///
+/// Applications embedding the service can implement this for their own scheme and
+/// set it as [`Settings::authenticator`](crate::Settings::authenticator); otherwise
+/// the one described by the `[auth]` configuration section is used.
+pub trait Authenticator: Send + Sync {
+    /// Let `req` through, or answer it with the returned response instead
+    /// (typically `401 Unauthorized` or `403 Forbidden`).
+    ///
+    /// Requests let through on behalf of someone should carry a [`User`] in their
+    /// extensions, which is who comments are attributed to.
+    fn authenticate(&self, req: &HttpRequest) -> Result<(), HttpResponse>;
+}
+
+impl fmt::Debug for dyn Authenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Authenticator")
+    }
+}
+
+/// Who a request was authenticated as, inserted into its extensions by the
+/// [`Authenticator`]; comments can only be left by a request that has one
+#[derive(Debug, Clone, PartialEq)]
+pub struct User(pub String);
+
+/// Lets every request through
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Anonymous;
+
+impl Authenticator for Anonymous {
+    fn authenticate(&self, _req: &HttpRequest) -> Result<(), HttpResponse> {
+        Ok(())
+    }
+}
+
+/// Credentials accepted by the built-in authenticator; with none, access is open
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// HTTP Basic users and their passwords
+    pub users: BTreeMap<String, String>,
+    /// Tokens accepted in an `Authorization: Bearer` header
+    pub tokens: Vec<String>,
+    /// Realm named in authentication challenges
+    pub realm: String,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            users: BTreeMap::new(),
+            tokens: Vec::new(),
+            realm: "svg-server".to_owned(),
+        }
+    }
+}
+
+impl AuthConfig {
+    /// Authenticator checking these credentials, or letting everyone in when there are none
+    pub fn authenticator(&self) -> Arc<dyn Authenticator> {
+        if self.users.is_empty() && self.tokens.is_empty() {
+            Arc::new(Anonymous)
+        } else {
+            Arc::new(Credentials(self.clone()))
+        }
+    }
+}
+
+/// Accepts the configured Basic users and bearer tokens
+struct Credentials(AuthConfig);
+
+impl Credentials {
+    /// Whether `authorization` is accepted, and the Basic user it names if any
+    fn accepts(&self, authorization: &str) -> Option<Option<User>> {
+        let (scheme, credentials) = authorization.split_once(' ')?;
+        let credentials = credentials.trim();
+
+        if scheme.eq_ignore_ascii_case("bearer") {
+            let known = self
+                .0
+                .tokens
+                .iter()
+                .any(|token| secure_eq(token, credentials));
+            return known.then_some(None);
+        }
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+
+        let decoded = STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|decoded| String::from_utf8(decoded).ok())?;
+        let (user, password) = decoded.split_once(':')?;
+        let expected = self.0.users.get(user)?;
+        secure_eq(expected, password).then(|| Some(User(user.to_owned())))
+    }
+}
+
+impl Authenticator for Credentials {
+    fn authenticate(&self, req: &HttpRequest) -> Result<(), HttpResponse> {
+        let authorization = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok());
+        if let Some(user) = authorization.and_then(|authorization| self.accepts(authorization)) {
+            if let Some(user) = user {
+                req.extensions_mut().insert(user);
+            }
+            return Ok(());
+        }
+
+        // Browsers only prompt for a password on a Basic challenge
+        let scheme = if self.0.users.is_empty() {
+            "Bearer"
+        } else {
+            "Basic"
+        };
+        Err(HttpResponse::Unauthorized()
+            .insert_header((
+                header::WWW_AUTHENTICATE,
+                format!("{scheme} realm=\"{}\"", self.0.realm.replace('"', "")),
+            ))
+            .body("Authentication required"))
+    }
+}
+
+/// Compare secrets without returning early on the first differing byte
+pub(crate) fn secure_eq(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    expected.len() == given.len()
+        && expected
+            .iter()
+            .zip(given)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Middleware answering requests `authenticator` refuses before they reach a route,
+/// except for the `public` paths that authenticate their requests themselves and the
+/// reads a guest pass admits
+pub(crate) async fn require(
+    authenticator: Arc<dyn Authenticator>,
+    public: Arc<[String]>,
+    guests: Option<GuestAccess>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    // Relative to where the service is mounted
+    let is_public = public
+        .iter()
+        .any(|path| req.match_info().unprocessed() == path);
+    let authenticated = if is_public {
+        Ok(())
+    } else {
+        authenticator
+            .authenticate(req.request())
+            .or_else(|refused| {
+                if guests.is_some_and(|guests| guests.admits(req.request())) {
+                    Ok(())
+                } else {
+                    Err(refused)
+                }
+            })
+    };
+    match authenticated {
+        Ok(()) => Ok(next.call(req).await?.map_into_left_body()),
+        Err(refused) => Ok(req.into_response(refused).map_into_right_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            users: BTreeMap::from([("alice".to_owned(), "wonderland".to_owned())]),
+            tokens: vec!["s3cret-token".to_owned()],
+            ..AuthConfig::default()
+        }
+    }
+
+    #[test]
+    fn basic_users_and_bearer_tokens_are_accepted() {
+        let authenticator = config().authenticator();
+        let login = format!("Basic {}", STANDARD.encode("alice:wonderland"));
+        for (authorization, user) in [
+            (login.as_str(), Some(User("alice".to_owned()))),
+            ("Bearer s3cret-token", None),
+            ("bearer  s3cret-token", None),
+        ] {
+            let req = TestRequest::default()
+                .insert_header((header::AUTHORIZATION, authorization))
+                .to_http_request();
+            assert!(authenticator.authenticate(&req).is_ok(), "{authorization}");
+            assert_eq!(req.extensions().get::<User>(), user.as_ref());
+        }
+    }
+
+    #[test]
+    fn wrong_or_missing_credentials_are_challenged() {
+        let authenticator = config().authenticator();
+        let wrong = format!("Basic {}", STANDARD.encode("alice:looking-glass"));
+        for authorization in [
+            Some(wrong.as_str()),
+            Some("Bearer s3cret"),
+            Some("Basic !!"),
+            None,
+        ] {
+            let mut req = TestRequest::default();
+            if let Some(authorization) = authorization {
+                req = req.insert_header((header::AUTHORIZATION, authorization));
+            }
+            let refused = authenticator
+                .authenticate(&req.to_http_request())
+                .unwrap_err();
+            assert_eq!(refused.status(), 401);
+            assert_eq!(
+                refused.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+                "Basic realm=\"svg-server\""
+            );
+        }
+    }
+
+    #[test]
+    fn no_credentials_means_open_access() {
+        let req = TestRequest::default().to_http_request();
+        assert!(
+            AuthConfig::default()
+                .authenticator()
+                .authenticate(&req)
+                .is_ok()
+        );
+    }
+}
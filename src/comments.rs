@@ -0,0 +1,132 @@
+use std::{
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::rfc3339;
+
+/// Longest comment accepted, in characters
+pub const MAX_COMMENT_CHARS: usize = 4000;
+
+/// Keeps concurrent comments from interleaving within a file
+static APPENDING: Mutex<()> = Mutex::new(());
+
+/// A comment left on a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: String,
+    /// Name of the user who wrote it
+    pub author: String,
+    /// When it was written, as an RFC 3339 UTC timestamp
+    pub created: String,
+    pub body: String,
+}
+
+/// Comments on every page, one JSON Lines file per page mirroring the served tree
+/// (`network/core` is stored in `network/core.comments.jsonl`)
+#[derive(Debug, Clone)]
+pub struct CommentStore {
+    dir: PathBuf,
+}
+
+impl CommentStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        CommentStore { dir: dir.into() }
+    }
+
+    fn path(&self, page: &str) -> PathBuf {
+        self.dir.join(format!("{page}.comments.jsonl"))
+    }
+
+    /// Comments on `page` (`/` separated), oldest first
+    pub fn list(&self, page: &str) -> Result<Vec<Comment>, String> {
+        let path = self.path(page);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read '{}': {e}", path.display())),
+        };
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("Invalid comment in '{}': {e}", path.display()))
+            })
+            .collect()
+    }
+
+    /// Append a comment by `author` to `page`, returning it as stored; `body` is
+    /// expected to have passed [`check_body`]
+    pub fn add(&self, page: &str, author: &str, body: &str) -> Result<Comment, String> {
+        let comment = Comment {
+            id: format!("{:016x}", rand::random::<u64>()),
+            author: author.to_owned(),
+            created: rfc3339(SystemTime::now()),
+            body: body.to_owned(),
+        };
+        let mut line = serde_json::to_string(&comment).map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        let path = self.path(page);
+        let _appending = APPENDING.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create '{}': {e}", parent.display()))?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| format!("Failed to write '{}': {e}", path.display()))?;
+        Ok(comment)
+    }
+}
+
+/// `body` with surrounding whitespace trimmed, if it is acceptable as a comment
+pub fn check_body(body: &str) -> Result<&str, String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err("Comment is empty".to_owned());
+    }
+    if body.chars().count() > MAX_COMMENT_CHARS {
+        return Err(format!(
+            "Comment is longer than {MAX_COMMENT_CHARS} characters"
+        ));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_appended_per_page() {
+        let dir = std::env::temp_dir().join(format!("svg-server-comments-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = CommentStore::new(&dir);
+        assert_eq!(store.list("network/core"), Ok(Vec::new()));
+
+        let body = check_body("  Label the uplinks\n").unwrap();
+        assert_eq!(body, "Label the uplinks");
+        assert!(check_body(" \n").is_err());
+        assert!(check_body(&"x".repeat(MAX_COMMENT_CHARS + 1)).is_err());
+
+        let first = store.add("network/core", "alice", body).unwrap();
+        store.add("network/core", "bob", "Done").unwrap();
+        store.add("home", "bob", "Looks good").unwrap();
+
+        let comments = store.list("network/core").unwrap();
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0], first);
+        assert_eq!(comments[1].author, "bob");
+        assert!(dir.join("network/core.comments.jsonl").is_file());
+    }
+}
@@ -0,0 +1,174 @@
+use resvg::tiny_skia::Pixmap;
+
+use crate::raster::{MAX_DIMENSION, PNG_SIGNATURE};
+
+/// Side of the square windows structural similarity is computed over
+const WINDOW: usize = 8;
+
+/// Largest difference in a color channel, out of 255, for pixels to still count as the
+/// same, absorbing anti-aliasing differences between renderers
+const PIXEL_TOLERANCE: u8 = 16;
+
+/// How closely two images of the same size match
+#[derive(Debug)]
+pub(crate) struct Similarity {
+    /// Size both images are compared at, that of the reference
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// 1 minus the mean difference of the color channels, from 0 to 1
+    pub(crate) pixel: f64,
+    /// Mean structural similarity (SSIM) of the luma of 8×8 windows, 1 for identical
+    /// images and around 0 for unrelated ones
+    pub(crate) structural: f64,
+    /// Share of pixels differing by more than the anti-aliasing tolerance
+    pub(crate) differing: f64,
+}
+
+/// Decode an uploaded PNG, refusing sizes the server wouldn't render
+pub(crate) fn decode_reference(png: &[u8]) -> Result<Pixmap, String> {
+    // Checked ahead of decoding, which allocates the whole image
+    let header = png
+        .strip_prefix(PNG_SIGNATURE)
+        .filter(|rest| rest.len() >= 16 && &rest[4..8] == b"IHDR")
+        .ok_or("Not a PNG".to_owned())?;
+    let width = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    let height = u32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+    if width as f32 > MAX_DIMENSION || height as f32 > MAX_DIMENSION {
+        return Err(format!(
+            "Images are at most {MAX_DIMENSION} pixels wide and high"
+        ));
+    }
+    Pixmap::decode_png(png).map_err(|e| format!("Invalid PNG: {e}"))
+}
+
+/// Compare `rendered` with a `reference` of the same size, both drawn over white so a
+/// transparent background matches a white one
+pub(crate) fn similarity(rendered: &Pixmap, reference: &Pixmap) -> Similarity {
+    let (a, b) = (over_white(rendered), over_white(reference));
+    let mut channel_difference = 0u64;
+    let mut differing = 0usize;
+    for (x, y) in a.iter().zip(&b) {
+        let differences = [0, 1, 2].map(|i| x[i].abs_diff(y[i]));
+        channel_difference += differences.iter().map(|&d| u64::from(d)).sum::<u64>();
+        if differences.iter().any(|&d| d > PIXEL_TOLERANCE) {
+            differing += 1;
+        }
+    }
+
+    let pixels = a.len() as f64;
+    let (width, height) = (reference.width(), reference.height());
+    let structural = structural_similarity(&luma(&a), &luma(&b), width as usize);
+    Similarity {
+        width,
+        height,
+        pixel: rounded(1.0 - channel_difference as f64 / (pixels * 3.0 * 255.0)),
+        structural: rounded(structural),
+        differing: rounded(differing as f64 / pixels),
+    }
+}
+
+/// Scores to 4 decimals, finer differences being noise between renderers
+fn rounded(score: f64) -> f64 {
+    (score * 10_000.0).round() / 10_000.0
+}
+
+/// RGB of every pixel composited over white
+fn over_white(pixmap: &Pixmap) -> Vec<[u8; 3]> {
+    pixmap
+        .pixels()
+        .iter()
+        .map(|pixel| {
+            // Channels are premultiplied by alpha, so white shows through the rest
+            let white = 255 - pixel.alpha();
+            [pixel.red(), pixel.green(), pixel.blue()].map(|channel| channel + white)
+        })
+        .collect()
+}
+
+fn luma(pixels: &[[u8; 3]]) -> Vec<f64> {
+    pixels
+        .iter()
+        .map(|&[r, g, b]| 0.299 * f64::from(r) + 0.587 * f64::from(g) + 0.114 * f64::from(b))
+        .collect()
+}
+
+/// Mean SSIM over windows tiling the images, those along the edges cut short
+fn structural_similarity(a: &[f64], b: &[f64], width: usize) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let height = a.len() / width;
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in (0..height).step_by(WINDOW) {
+        for left in (0..width).step_by(WINDOW) {
+            let indices: Vec<_> = (top..(top + WINDOW).min(height))
+                .flat_map(|y| (left..(left + WINDOW).min(width)).map(move |x| y * width + x))
+                .collect();
+            let n = indices.len() as f64;
+            let mean_a = indices.iter().map(|&i| a[i]).sum::<f64>() / n;
+            let mean_b = indices.iter().map(|&i| b[i]).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for &i in &indices {
+                var_a += (a[i] - mean_a).powi(2);
+                var_b += (b[i] - mean_b).powi(2);
+                covariance += (a[i] - mean_a) * (b[i] - mean_b);
+            }
+            let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a.powi(2) + mean_b.powi(2) + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / f64::from(windows)
+}
+
+#[cfg(test)]
+mod tests {
+    use resvg::tiny_skia::{Color, Paint, Rect, Transform};
+
+    use super::*;
+
+    fn drawing(background: Option<Color>, square: Rect) -> Pixmap {
+        let mut pixmap = Pixmap::new(32, 32).unwrap();
+        if let Some(background) = background {
+            pixmap.fill(background);
+        }
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(0x33, 0x66, 0x99, 0xff);
+        pixmap.fill_rect(square, &paint, Transform::identity(), None);
+        pixmap
+    }
+
+    #[test]
+    fn matching_images_score_one() {
+        let square = Rect::from_xywh(8.0, 8.0, 16.0, 16.0).unwrap();
+        let transparent = drawing(None, square);
+        let white = drawing(Some(Color::WHITE), square);
+        let same = similarity(&transparent, &white);
+        assert_eq!(
+            (same.pixel, same.structural, same.differing),
+            (1.0, 1.0, 0.0)
+        );
+        assert_eq!((same.width, same.height), (32, 32));
+
+        let moved = drawing(None, Rect::from_xywh(12.0, 8.0, 16.0, 16.0).unwrap());
+        let shifted = similarity(&transparent, &moved);
+        assert!(shifted.pixel < 1.0);
+        assert!(shifted.structural < 0.9);
+        assert_eq!(shifted.differing, 0.125);
+    }
+
+    #[test]
+    fn references_must_be_renderable_pngs() {
+        let png = Pixmap::new(4, 4).unwrap().encode_png().unwrap();
+        assert_eq!(decode_reference(&png).unwrap().width(), 4);
+        assert!(decode_reference(b"GIF89a").is_err());
+
+        // A huge size in the header is refused before anything is allocated
+        let mut huge = png.clone();
+        huge[16..20].copy_from_slice(&100_000u32.to_be_bytes());
+        assert!(decode_reference(&huge).unwrap_err().contains("at most"));
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::Write;
+
+use actix_web::{
+    HttpRequest, HttpResponse,
+    http::header::{self, AcceptEncoding, Encoding, Header},
+};
+use flate2::{Compression, write::GzEncoder};
+
+use crate::conditional;
+
+/// Bodies smaller than this are not worth compressing
+const MIN_COMPRESS_SIZE: usize = 1024;
+
+/// Build an `image/svg+xml` response, compressed for clients that accept it.
+///
+/// actix's `Compress` middleware leaves every `image/*` response untouched, which
+/// includes SVG, so raw SVG bodies are encoded here instead. Clients revalidating
+/// with a matching `If-None-Match` get a `304 Not Modified`.
+pub fn svg_response(req: &HttpRequest, body: Vec<u8>) -> HttpResponse {
+    let etag = conditional::etag(&body);
+    if let Some(not_modified) = conditional::not_modified(req, &etag) {
+        return not_modified;
+    }
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type("image/svg+xml")
+        .insert_header(header::ETag(etag))
+        .insert_header((header::VARY, "accept-encoding"));
+
+    if body.len() < MIN_COMPRESS_SIZE {
+        return response.body(body);
+    }
+
+    let supported = [Encoding::brotli(), Encoding::gzip(), Encoding::identity()];
+    let encoding = AcceptEncoding::parse(req)
+        .ok()
+        .and_then(|accept| accept.negotiate(supported.iter()));
+
+    let encoded = match encoding {
+        Some(encoding) if encoding == Encoding::brotli() => brotli_encode(&body).map(|b| ("br", b)),
+        Some(encoding) if encoding == Encoding::gzip() => gzip_encode(&body).map(|b| ("gzip", b)),
+        _ => return response.body(body),
+    };
+
+    match encoded {
+        Ok((name, encoded)) => response
+            .insert_header((header::CONTENT_ENCODING, name))
+            .body(encoded),
+        Err(e) => {
+            eprintln!("Failed to compress SVG: {e}");
+            response.body(body)
+        }
+    }
+}
+
+fn brotli_encode(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    // Same quality/window as actix's own brotli encoder
+    let mut encoder = brotli::CompressorWriter::new(Vec::new(), 32 * 1024, 3, 22);
+    encoder.write_all(body)?;
+    encoder.flush()?;
+    Ok(encoder.into_inner())
+}
+
+fn gzip_encode(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
@@ -0,0 +1,28 @@
+use actix_web::{
+    HttpMessage, HttpRequest, HttpResponse,
+    http::header::{self, EntityTag, IfNoneMatch},
+};
+use sha2::{Digest, Sha256};
+
+/// Weak validator for a response body.
+///
+/// Weak because the same body may go out gzip-, brotli- or un-encoded.
+pub(crate) fn etag(body: &[u8]) -> EntityTag {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    EntityTag::new_weak(hex)
+}
+
+/// A `304 Not Modified` response if the client already holds `etag`
+pub(crate) fn not_modified(req: &HttpRequest, etag: &EntityTag) -> Option<HttpResponse> {
+    let matches = match req.get_header::<IfNoneMatch>()? {
+        IfNoneMatch::Any => true,
+        IfNoneMatch::Items(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+    };
+
+    matches.then(|| {
+        HttpResponse::NotModified()
+            .insert_header(header::ETag(etag.clone()))
+            .finish()
+    })
+}
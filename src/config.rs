@@ -0,0 +1,680 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, de::Error};
+
+use crate::{
+    auth::AuthConfig,
+    contrast::{Contrast, ContrastConfig},
+    digest::DigestConfig,
+    pages::percent_encode,
+    review::ReviewConfig,
+    rewrite::RewriteRule,
+    simulate::Simulation,
+    smtp::SmtpConfig,
+};
+
+/// Server settings loaded from the `--config` TOML file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Credentials the built-in authenticator accepts
+    pub auth: AuthConfig,
+    /// High-contrast transform settings
+    pub contrast: ContrastConfig,
+    /// Email digest of added, changed and approved diagrams
+    pub digest: DigestConfig,
+    /// Where each page's source can be edited
+    pub edit: EditConfig,
+    /// Fonts rasterized exports are drawn with and the attribution stamped into them
+    pub raster: RasterConfig,
+    /// License and attribution of the served diagrams
+    pub license: LicenseConfig,
+    /// Names of the built-in route prefixes
+    #[serde(deserialize_with = "routes")]
+    pub routes: RoutesConfig,
+    /// Who may move pages between review statuses
+    pub review: ReviewConfig,
+    /// Rules mapping request paths onto others before routing, tried in order
+    #[serde(deserialize_with = "crate::rewrite::rules")]
+    pub rewrite: Vec<RewriteRule>,
+    /// Signed cookie settings
+    pub session: SessionConfig,
+    /// Where the server stores what visitors write, such as comments and reviews
+    pub sidecar: SidecarConfig,
+    /// Mail server the digest is sent through
+    pub smtp: SmtpConfig,
+    /// When pages count as out of date
+    pub stale: StaleConfig,
+    /// How pages render unless visitors ask otherwise
+    pub view: ViewConfig,
+    /// Push webhooks that pull the served Git checkout
+    pub webhook: WebhookConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    /// Secret GitHub signs deliveries with, or GitLab sends as its token, at least 16
+    /// bytes.
+    ///
+    /// Without one `/api/webhook` is not served.
+    #[serde(deserialize_with = "webhook_secret")]
+    secret: Option<String>,
+}
+
+impl WebhookConfig {
+    /// Webhooks verified with `secret`, which has to be at least 16 bytes long
+    pub fn new(secret: Option<String>) -> Result<Self, String> {
+        if let Some(secret) = &secret {
+            check_webhook_secret(secret)?;
+        }
+        Ok(WebhookConfig { secret })
+    }
+
+    /// Secret deliveries are verified with, if any
+    pub fn secret(&self) -> Option<&str> {
+        self.secret.as_deref()
+    }
+}
+
+/// How exports are rasterized, and the metadata written into them, which otherwise
+/// carry none
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RasterConfig {
+    /// Directory of font files to draw text with, besides the fonts installed on the
+    /// host, e.g. the brand fonts the diagrams are designed with
+    pub fonts: Option<PathBuf>,
+    /// Copyright notice, e.g. "© 2026 Acme Corp"
+    pub copyright: Option<String>,
+    /// Author or attribution, e.g. "Acme Platform Team"
+    pub author: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SidecarConfig {
+    /// Directory holding per-page comments and review statuses, best kept outside the
+    /// served checkout.
+    ///
+    /// Without one the server never writes to disk, and comments and reviews are off.
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    /// Secret that preference cookies are signed with, at least 32 bytes.
+    ///
+    /// Without one a random key is used, and pinned preferences are forgotten
+    /// whenever the server restarts.
+    #[serde(deserialize_with = "session_secret")]
+    secret: Option<String>,
+}
+
+impl SessionConfig {
+    /// Sessions signed with `secret`, which has to be at least 32 bytes long
+    pub fn new(secret: Option<String>) -> Result<Self, String> {
+        if let Some(secret) = &secret {
+            check_session_secret(secret)?;
+        }
+        Ok(SessionConfig { secret })
+    }
+
+    /// Secret that preference cookies are signed with, if any
+    pub fn secret(&self) -> Option<&str> {
+        self.secret.as_deref()
+    }
+}
+
+/// "Edit this diagram" URL templates keyed by directory prefix, the longest matching
+/// prefix winning (`""` matches every page).
+///
+/// In a template `{path}` is replaced by the SVG file's path under the served
+/// directory and `{page}` by the page name, both percent-encoded.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct EditConfig {
+    #[serde(deserialize_with = "edit_links")]
+    pub links: BTreeMap<String, String>,
+}
+
+impl EditConfig {
+    /// Edit URL of `page` (`/` separated), or `None` if no prefix covers it
+    pub fn url(&self, page: &str) -> Option<String> {
+        let template = longest_prefix(&self.links, page)?;
+        let page = percent_encode(page);
+        Some(
+            template
+                .replace("{path}", &format!("{page}.svg"))
+                .replace("{page}", &page),
+        )
+    }
+}
+
+/// License of the served diagrams, shown in every page footer and stamped as a comment
+/// into downloaded SVGs
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LicenseConfig {
+    /// License name, e.g. "CC BY 4.0"
+    pub name: Option<String>,
+    /// Where the license terms are published
+    #[serde(deserialize_with = "link_url")]
+    pub url: Option<String>,
+    /// Who to credit, in order
+    pub attribution: Vec<Attribution>,
+}
+
+/// Someone credited for the diagrams
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Attribution {
+    pub name: String,
+    #[serde(deserialize_with = "link_url")]
+    pub url: Option<String>,
+}
+
+impl LicenseConfig {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.attribution.is_empty()
+    }
+
+    /// Text of the comment stamped into SVGs, `None` when nothing is configured
+    pub fn svg_comment(&self) -> Option<String> {
+        let linked = |name: &str, url: &Option<String>| match url {
+            Some(url) => format!("{name} ({url})"),
+            None => name.to_owned(),
+        };
+        let mut lines = Vec::new();
+        if let Some(name) = &self.name {
+            lines.push(format!("License: {}", linked(name, &self.url)));
+        }
+        if !self.attribution.is_empty() {
+            let credits: Vec<_> = self
+                .attribution
+                .iter()
+                .map(|credit| linked(&credit.name, &credit.url))
+                .collect();
+            lines.push(format!("Attribution: {}", credits.join(", ")));
+        }
+        if lines.is_empty() {
+            return None;
+        }
+
+        // Comments can't contain "--"
+        let mut text = lines.join("\n     ");
+        while text.contains("--") {
+            text = text.replace("--", "- -");
+        }
+        Some(format!("<!-- {text} -->"))
+    }
+
+    /// `svg` with the license comment ahead of its root element, after any XML
+    /// declaration
+    pub fn stamp(&self, svg: Vec<u8>) -> Vec<u8> {
+        let Some(comment) = self.svg_comment() else {
+            return svg;
+        };
+        let bom = if svg.starts_with(b"\xef\xbb\xbf") {
+            3
+        } else {
+            0
+        };
+        let at = if svg[bom..].starts_with(b"<?xml") {
+            svg.windows(2)
+                .position(|pair| pair == b"?>")
+                .map_or(bom, |end| end + 2)
+        } else {
+            bom
+        };
+
+        let mut stamped = Vec::with_capacity(svg.len() + comment.len() + 1);
+        stamped.extend_from_slice(&svg[..at]);
+        if at > bom {
+            stamped.push(b'\n');
+        }
+        stamped.extend_from_slice(comment.as_bytes());
+        if at == bom {
+            stamped.push(b'\n');
+        }
+        stamped.extend_from_slice(&svg[at..]);
+        stamped
+    }
+}
+
+/// Days without changes after which pages get an "out of date" badge, keyed by
+/// directory prefix like [`EditConfig`]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct StaleConfig {
+    #[serde(deserialize_with = "stale_days")]
+    pub days: BTreeMap<String, u64>,
+}
+
+impl StaleConfig {
+    /// Days after which `page` (`/` separated) is stale, or `None` if no prefix covers it
+    pub fn threshold(&self, page: &str) -> Option<u64> {
+        longest_prefix(&self.days, page).copied()
+    }
+
+    /// Whether `page`, last changed `age_days` ago, is past its threshold
+    pub fn is_stale(&self, page: &str, age_days: u64) -> bool {
+        self.threshold(page).is_some_and(|days| age_days >= days)
+    }
+}
+
+/// Rendering options pages get unless the query string or pinned preferences set them,
+/// keyed by directory prefix like [`EditConfig`]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ViewConfig {
+    #[serde(deserialize_with = "view_defaults")]
+    pub defaults: BTreeMap<String, ViewDefaults>,
+}
+
+impl ViewConfig {
+    /// Defaults for `page` (`/` separated), none if no prefix covers it
+    pub fn for_page(&self, page: &str) -> Option<&ViewDefaults> {
+        longest_prefix(&self.defaults, page)
+    }
+}
+
+/// Default of each query parameter of a page, see [`ViewConfig`]
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ViewDefaults {
+    pub simulate: Option<Simulation>,
+    pub contrast: Option<Contrast>,
+    pub background: Option<Background>,
+    pub fit: Option<Fit>,
+}
+
+/// What shows through the transparent parts of a diagram
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    White,
+    /// A checkerboard, telling transparent areas from white ones, e.g. for icons
+    Checkerboard,
+}
+
+/// How large a diagram is drawn on its page
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fit {
+    /// Scaled to the width of the window
+    FullWidth,
+    /// At the size the SVG gives itself, e.g. for icons
+    Fixed,
+}
+
+/// Value of the longest directory prefix in `entries` covering `page`
+fn longest_prefix<'a, T>(entries: &'a BTreeMap<String, T>, page: &str) -> Option<&'a T> {
+    entries
+        .iter()
+        .filter(|(prefix, _)| {
+            prefix.is_empty()
+                || page
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, value)| value)
+}
+
+/// Directory prefix as written in the TOML file, normalized like page names
+fn normalize_prefix(prefix: &str) -> String {
+    prefix
+        .trim_matches(['/', ':'])
+        .replace(':', "/")
+        .to_lowercase()
+}
+
+/// Top-level path segments of the built-in routes, `None` when a route is disabled.
+///
+/// In the TOML file each one is either a new name or `false` to turn the route off.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoutesConfig {
+    /// Prefix of `/raw/{page}`
+    #[serde(deserialize_with = "route_name")]
+    pub raw: Option<String>,
+    /// Prefix of `/copy/{page}`, behind the "Copy SVG" button
+    #[serde(deserialize_with = "route_name")]
+    pub copy: Option<String>,
+    /// Prefix of `/standalone/{page}`
+    #[serde(deserialize_with = "route_name")]
+    pub standalone: Option<String>,
+    /// Prefix of `/text/{page}`
+    #[serde(deserialize_with = "route_name")]
+    pub text: Option<String>,
+    /// Prefix of `/notebook/{page}`
+    #[serde(deserialize_with = "route_name")]
+    pub notebook: Option<String>,
+    /// Prefix of `/api/...`
+    #[serde(deserialize_with = "route_name")]
+    pub api: Option<String>,
+    /// Name of `/sprite.svg`
+    #[serde(deserialize_with = "route_name")]
+    pub sprite: Option<String>,
+    /// Prefix of `/archive/{dir}.tar.gz`
+    #[serde(deserialize_with = "route_name")]
+    pub archive: Option<String>,
+    /// Name of `/preferences`
+    #[serde(deserialize_with = "route_name")]
+    pub preferences: Option<String>,
+    /// Name of `/admin`
+    #[serde(deserialize_with = "route_name")]
+    pub admin: Option<String>,
+    /// Name of `/guest`, where guest links minted on the admin page land
+    #[serde(deserialize_with = "route_name")]
+    pub guest: Option<String>,
+    /// Prefix of `/page/{page}`, which always renders a file even when a route shadows `/{page}`
+    #[serde(deserialize_with = "route_name")]
+    pub page: Option<String>,
+}
+
+impl RoutesConfig {
+    /// Single-segment routes, which take precedence over pages of the same name
+    pub fn reserved_names(&self) -> Vec<&str> {
+        [&self.sprite, &self.preferences, &self.admin, &self.guest]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Every route's config key with its name, `None` for disabled ones
+    fn names(&self) -> [(&'static str, &Option<String>); 12] {
+        [
+            ("raw", &self.raw),
+            ("copy", &self.copy),
+            ("standalone", &self.standalone),
+            ("text", &self.text),
+            ("notebook", &self.notebook),
+            ("api", &self.api),
+            ("sprite", &self.sprite),
+            ("archive", &self.archive),
+            ("preferences", &self.preferences),
+            ("admin", &self.admin),
+            ("guest", &self.guest),
+            ("page", &self.page),
+        ]
+    }
+}
+
+impl Default for RoutesConfig {
+    fn default() -> Self {
+        RoutesConfig {
+            raw: Some("raw".to_owned()),
+            copy: Some("copy".to_owned()),
+            standalone: Some("standalone".to_owned()),
+            text: Some("text".to_owned()),
+            notebook: Some("notebook".to_owned()),
+            api: Some("api".to_owned()),
+            sprite: Some("sprite.svg".to_owned()),
+            archive: Some("archive".to_owned()),
+            preferences: Some("preferences".to_owned()),
+            admin: Some("admin".to_owned()),
+            guest: Some("guest".to_owned()),
+            page: Some("page".to_owned()),
+        }
+    }
+}
+
+/// Route names, checked not to take each other's place
+fn routes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<RoutesConfig, D::Error> {
+    let routes = RoutesConfig::deserialize(deserializer)?;
+    let names = routes.names();
+    for (i, (key, name)) in names.iter().enumerate() {
+        let Some(name) = name else {
+            continue;
+        };
+        if let Some((other, _)) = names[..i]
+            .iter()
+            .find(|(_, other)| other.as_ref() == Some(name))
+        {
+            return Err(D::Error::custom(format!(
+                "routes {other} and {key} are both named '{name}'"
+            )));
+        }
+    }
+    Ok(routes)
+}
+
+fn route_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RouteName {
+        Name(String),
+        Enabled(bool),
+    }
+
+    match RouteName::deserialize(deserializer)? {
+        RouteName::Enabled(false) => Ok(None),
+        RouteName::Enabled(true) => Err(D::Error::custom(
+            "expected a route name, or false to disable the route",
+        )),
+        RouteName::Name(name) => {
+            let valid = !name.is_empty()
+                && !name.contains(':')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.~".contains(c));
+            if valid {
+                Ok(Some(name))
+            } else {
+                Err(D::Error::custom(format!(
+                    "invalid route name '{name}', expected a single path segment"
+                )))
+            }
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config '{}': {e}", path.display()))?;
+        Config::from_toml(&content).map_err(|e| format!("Invalid config '{}': {e}", path.display()))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+}
+
+fn edit_links<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    let links = BTreeMap::<String, String>::deserialize(deserializer)?;
+    links
+        .into_iter()
+        .map(|(prefix, template)| {
+            if !template.starts_with("https://") && !template.starts_with("http://") {
+                return Err(D::Error::custom(format!(
+                    "invalid edit URL '{template}', expected an http(s) URL"
+                )));
+            }
+            Ok((normalize_prefix(&prefix), template))
+        })
+        .collect()
+}
+
+fn link_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let url = String::deserialize(deserializer)?;
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err(D::Error::custom(format!(
+            "invalid link '{url}', expected an http(s) URL"
+        )));
+    }
+    Ok(Some(url))
+}
+
+fn stale_days<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, u64>, D::Error> {
+    let days = BTreeMap::<String, u64>::deserialize(deserializer)?;
+    Ok(days
+        .into_iter()
+        .map(|(prefix, days)| (normalize_prefix(&prefix), days))
+        .collect())
+}
+
+fn view_defaults<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, ViewDefaults>, D::Error> {
+    let defaults = BTreeMap::<String, ViewDefaults>::deserialize(deserializer)?;
+    Ok(defaults
+        .into_iter()
+        .map(|(prefix, view)| (normalize_prefix(&prefix), view))
+        .collect())
+}
+
+fn session_secret<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let secret = String::deserialize(deserializer)?;
+    check_session_secret(&secret).map_err(D::Error::custom)?;
+    Ok(Some(secret))
+}
+
+/// Cookie keys are derived from at least 32 bytes
+fn check_session_secret(secret: &str) -> Result<(), String> {
+    if secret.len() < 32 {
+        return Err("session secret must be at least 32 bytes long".to_owned());
+    }
+    Ok(())
+}
+
+fn webhook_secret<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let secret = String::deserialize(deserializer)?;
+    check_webhook_secret(&secret).map_err(D::Error::custom)?;
+    Ok(Some(secret))
+}
+
+/// Short secrets are guessed, or their HMACs forged, too easily
+fn check_webhook_secret(secret: &str) -> Result<(), String> {
+    if secret.len() < 16 {
+        return Err("webhook secret must be at least 16 bytes long".to_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_longest_edit_prefix_wins() {
+        let config = Config::from_toml(
+            r#"
+            [edit]
+            "" = "https://github.com/acme/diagrams/blob/main/{path}"
+            "Network:" = "https://app.diagrams.net/#U{page}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.edit.url("home").unwrap(),
+            "https://github.com/acme/diagrams/blob/main/home.svg"
+        );
+        assert_eq!(
+            config.edit.url("network/core links").unwrap(),
+            "https://app.diagrams.net/#Unetwork/core%20links"
+        );
+        // Prefixes match whole directories only
+        assert_eq!(
+            config.edit.url("networking").unwrap(),
+            "https://github.com/acme/diagrams/blob/main/networking.svg"
+        );
+        assert!(EditConfig::default().url("home").is_none());
+
+        assert!(Config::from_toml("[edit]\n\"\" = \"javascript:alert(1)\"\n").is_err());
+    }
+
+    #[test]
+    fn stale_thresholds_follow_directories() {
+        let config = Config::from_toml("[stale]\n\"\" = 365\n\"runbooks:\" = 30\n").unwrap();
+        assert_eq!(config.stale.threshold("home"), Some(365));
+        assert_eq!(config.stale.threshold("runbooks/restore"), Some(30));
+        assert!(config.stale.is_stale("runbooks/restore", 30));
+        assert!(!config.stale.is_stale("home", 30));
+        assert_eq!(StaleConfig::default().threshold("home"), None);
+    }
+
+    #[test]
+    fn routes_are_named_apart() {
+        let config = Config::from_toml("[routes]\nraw = \"svg\"\napi = false\n").unwrap();
+        assert_eq!(config.routes.raw.as_deref(), Some("svg"));
+        for routes in [
+            "raw = \"api\"",
+            "admin = \"guest\"",
+            "page = \"x\"\ntext = \"x\"",
+        ] {
+            let error = Config::from_toml(&format!("[routes]\n{routes}\n")).unwrap_err();
+            assert!(error.contains("are both named"), "{routes}: {error}");
+        }
+    }
+
+    #[test]
+    fn session_secrets_are_long_enough_to_derive_keys() {
+        // Built in code as well as read from TOML, so embedders can't get a short one in
+        assert!(SessionConfig::new(Some("short".to_owned())).is_err());
+        assert!(Config::from_toml("[session]\nsecret = \"short\"\n").is_err());
+        let secret = "a secret long enough to derive a cookie key".to_owned();
+        let session = SessionConfig::new(Some(secret.clone())).unwrap();
+        assert_eq!(session.secret(), Some(secret.as_str()));
+        assert!(SessionConfig::new(None).unwrap().secret().is_none());
+    }
+
+    #[test]
+    fn webhook_secrets_are_hard_to_guess() {
+        assert!(WebhookConfig::new(Some("x".to_owned())).is_err());
+        assert!(WebhookConfig::new(Some(String::new())).is_err());
+        let webhook = WebhookConfig::new(Some("webhook-secret-of-the-diagrams".to_owned()));
+        assert_eq!(
+            webhook.unwrap().secret(),
+            Some("webhook-secret-of-the-diagrams")
+        );
+        assert!(WebhookConfig::new(None).unwrap().secret().is_none());
+    }
+
+    #[test]
+    fn licenses_are_stamped_into_svgs() {
+        let config = Config::from_toml(
+            r#"
+            [license]
+            name = "CC BY 4.0"
+            url = "https://creativecommons.org/licenses/by/4.0/"
+            attribution = [
+                { name = "Acme Design", url = "https://design.acme.example" },
+                { name = "Jane Roe--Doe" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let comment = concat!(
+            "<!-- License: CC BY 4.0 (https://creativecommons.org/licenses/by/4.0/)\n",
+            "     Attribution: Acme Design (https://design.acme.example), Jane Roe- -Doe -->",
+        );
+        assert_eq!(config.license.svg_comment().unwrap(), comment);
+
+        let stamped = config.license.stamp(b"<svg/>".to_vec());
+        assert_eq!(stamped, format!("{comment}\n<svg/>").as_bytes());
+        let declared = br#"<?xml version="1.0"?><svg/>"#.to_vec();
+        let stamped = config.license.stamp(declared);
+        assert_eq!(
+            stamped,
+            format!("<?xml version=\"1.0\"?>\n{comment}<svg/>").as_bytes()
+        );
+
+        assert_eq!(
+            LicenseConfig::default().stamp(b"<svg/>".to_vec()),
+            b"<svg/>"
+        );
+        assert!(Config::from_toml("[license]\nurl = \"javascript:alert(1)\"\n").is_err());
+    }
+}
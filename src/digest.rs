@@ -0,0 +1,413 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    pages::{normalize_page, percent_encode, svg_files},
+    review::{ReviewStatus, ReviewStore},
+    smtp::{self, SmtpConfig},
+    stats::{civil_from_days, rfc3339},
+};
+
+/// How often digests go out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestPeriod {
+    Daily,
+    #[default]
+    Weekly,
+}
+
+impl fmt::Display for DigestPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DigestPeriod::Daily => "daily",
+            DigestPeriod::Weekly => "weekly",
+        })
+    }
+}
+
+impl DigestPeriod {
+    fn duration(self) -> Duration {
+        match self {
+            DigestPeriod::Daily => Duration::from_secs(86_400),
+            DigestPeriod::Weekly => Duration::from_secs(7 * 86_400),
+        }
+    }
+}
+
+/// Who gets the email digest of diagram changes, and how often
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DigestConfig {
+    /// Recipients; with none, no digest is sent
+    pub to: Vec<String>,
+    /// Sender address
+    pub from: String,
+    pub every: DigestPeriod,
+    /// Public URL of the server, to link the pages from the email
+    pub url: Option<String>,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        DigestConfig {
+            to: Vec::new(),
+            from: "svg-server@localhost".to_owned(),
+            every: DigestPeriod::default(),
+            url: None,
+        }
+    }
+}
+
+/// Pages as the last digest saw them, kept in the sidecar directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Snapshot {
+    /// Seconds since the Unix epoch when it was taken
+    taken: u64,
+    /// Modification time of every page's file, in seconds since the Unix epoch
+    pages: BTreeMap<String, u64>,
+}
+
+/// What happened to the diagrams since the previous digest
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Digest {
+    /// When the previous digest was taken, as an RFC 3339 UTC timestamp
+    pub since: String,
+    /// New pages, `:` separated
+    pub added: Vec<String>,
+    /// Pages whose file was modified
+    pub changed: Vec<String>,
+    /// Pages approved, with who approved them
+    pub approved: Vec<(String, String)>,
+}
+
+impl Digest {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.approved.is_empty()
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "Diagram digest: {} added, {} changed, {} approved",
+            self.added.len(),
+            self.changed.len(),
+            self.approved.len()
+        )
+    }
+
+    /// Plain-text body, linking pages under `url` when given
+    pub fn to_text(&self, url: Option<&str>) -> String {
+        let link = |page: &str| match url {
+            Some(url) => format!(
+                "{page} <{}/{}>",
+                url.trim_end_matches('/'),
+                percent_encode(&page.replace(':', "/")).replace('/', ":")
+            ),
+            None => page.to_owned(),
+        };
+        let mut text = format!("Changes to the diagrams since {}:\n", self.since);
+        for (heading, pages) in [("Added", &self.added), ("Changed", &self.changed)] {
+            if !pages.is_empty() {
+                text.push_str(&format!("\n{heading}:\n"));
+                for page in pages {
+                    text.push_str(&format!("- {}\n", link(page)));
+                }
+            }
+        }
+        if !self.approved.is_empty() {
+            text.push_str("\nApproved:\n");
+            for (page, by) in &self.approved {
+                text.push_str(&format!("- {} (by {by})\n", link(page)));
+            }
+        }
+        text
+    }
+}
+
+/// Puts together and sends the email digest of added, changed and approved diagrams,
+/// remembering what the last one covered in the `[sidecar]` directory
+#[derive(Debug, Clone)]
+pub struct Digester {
+    root: PathBuf,
+    snapshot: PathBuf,
+    reviews: ReviewStore,
+    config: DigestConfig,
+    smtp: SmtpConfig,
+}
+
+impl Digester {
+    /// Digester for the pages under `root`, or an error when `config` lacks what it needs
+    pub fn new(root: &Path, config: &Config) -> Result<Self, String> {
+        if config.digest.to.is_empty() {
+            return Err("No digest recipients configured, set [digest] to".to_owned());
+        }
+        let dir = config
+            .sidecar
+            .dir
+            .as_ref()
+            .ok_or("Digests remember what they covered in the [sidecar] dir, set one")?;
+        if config.smtp.host.is_none() {
+            return Err("No mail server configured, set [smtp] host".to_owned());
+        }
+        Ok(Digester {
+            root: root.to_path_buf(),
+            snapshot: dir.join("digest.json"),
+            reviews: ReviewStore::new(dir),
+            config: config.digest.clone(),
+            smtp: config.smtp.clone(),
+        })
+    }
+
+    fn load(&self) -> Result<Option<Snapshot>, String> {
+        match std::fs::read_to_string(&self.snapshot) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|e| format!("Invalid '{}': {e}", self.snapshot.display())),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read '{}': {e}", self.snapshot.display())),
+        }
+    }
+
+    fn save(&self, snapshot: &Snapshot) -> Result<(), String> {
+        if let Some(parent) = self.snapshot.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create '{}': {e}", parent.display()))?;
+        }
+        let json = serde_json::to_string(snapshot).map_err(|e| e.to_string())?;
+        std::fs::write(&self.snapshot, json)
+            .map_err(|e| format!("Failed to write '{}': {e}", self.snapshot.display()))
+    }
+
+    /// Pages under the served directory as they are at `now`
+    fn snapshot(&self, now: SystemTime) -> Snapshot {
+        let pages = svg_files(&self.root)
+            .into_iter()
+            .filter_map(|(name, path)| {
+                let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+                Some((normalize_page(&name).ok()?, unix_secs(modified)))
+            })
+            .collect();
+        Snapshot {
+            taken: unix_secs(now),
+            pages,
+        }
+    }
+
+    /// Whether a digest should go out at `now`: once a period has passed since the
+    /// last one, or right away when there is none yet
+    pub fn due(&self, now: SystemTime) -> Result<bool, String> {
+        Ok(self.load()?.is_none_or(|last| {
+            let next = UNIX_EPOCH + Duration::from_secs(last.taken) + self.config.every.duration();
+            now >= next
+        }))
+    }
+
+    /// Changes since the last digest up to `now`, or `None` when there was none yet to
+    /// compare with
+    pub fn collect(&self, now: SystemTime) -> Result<Option<Digest>, String> {
+        Ok(self
+            .load()?
+            .map(|last| self.compare(&last, &self.snapshot(now))))
+    }
+
+    fn compare(&self, last: &Snapshot, current: &Snapshot) -> Digest {
+        let since = rfc3339(UNIX_EPOCH + Duration::from_secs(last.taken));
+        let mut digest = Digest {
+            since: since.clone(),
+            ..Digest::default()
+        };
+        for (page, modified) in &current.pages {
+            let name = page.replace('/', ":");
+            match last.pages.get(page) {
+                None => digest.added.push(name.clone()),
+                Some(before) if before != modified => digest.changed.push(name.clone()),
+                Some(_) => {}
+            }
+            // Timestamps in the same format sort chronologically
+            match self.reviews.get(page) {
+                Ok(Some(review))
+                    if review.status == ReviewStatus::Approved && review.changed >= since =>
+                {
+                    digest.approved.push((name, review.changed_by));
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+        digest
+    }
+
+    /// Who the email carrying `digest` is from and to, its subject and date
+    fn headers(&self, digest: &Digest, now: SystemTime) -> String {
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\n",
+            self.config.from,
+            self.config.to.join(", "),
+            digest.subject(),
+            rfc2822(now),
+        )
+    }
+
+    /// The email carrying `digest`, sent at `now`
+    pub fn message(&self, digest: &Digest, now: SystemTime) -> String {
+        let body = STANDARD.encode(digest.to_text(self.config.url.as_deref()));
+        let mut message = format!(
+            "{}MIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n",
+            self.headers(digest, now),
+        );
+        for line in body.as_bytes().chunks(76) {
+            message.push_str(&String::from_utf8_lossy(line));
+            message.push_str("\r\n");
+        }
+        message
+    }
+
+    /// The email carrying `digest` as it reads, its body in plain text rather than
+    /// encoded for transfer, for `digest --dry-run`
+    pub fn preview(&self, digest: &Digest, now: SystemTime) -> String {
+        format!(
+            "{}\n{}",
+            self.headers(digest, now).replace("\r\n", "\n"),
+            digest.to_text(self.config.url.as_deref()),
+        )
+    }
+
+    /// Send the digest of changes since the last one and remember what it covered,
+    /// returning it. Nothing is sent on the first run, which only records the baseline,
+    /// nor when nothing changed.
+    pub fn run(&self, now: SystemTime) -> Result<Option<Digest>, String> {
+        let current = self.snapshot(now);
+        let Some(last) = self.load()? else {
+            self.save(&current)?;
+            return Ok(None);
+        };
+        let digest = self.compare(&last, &current);
+        if !digest.is_empty() {
+            smtp::send(
+                &self.smtp,
+                &self.config.from,
+                &self.config.to,
+                &self.message(&digest, now),
+            )?;
+        }
+        self.save(&current)?;
+        Ok(Some(digest))
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// `time` as an RFC 5322 date, e.g. `Thu, 01 Jan 1970 00:00:00 +0000`
+fn rfc2822(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let secs = unix_secs(time);
+    let days = secs / 86_400;
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} +0000",
+        WEEKDAYS[(days % 7) as usize],
+        MONTHS[(month - 1) as usize],
+        secs % 86_400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_modified(path: &Path, time: SystemTime) {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    }
+
+    #[test]
+    fn digests_cover_changes_since_the_last_one() {
+        let dir = std::env::temp_dir().join(format!("svg-server-digest-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let root = dir.join("diagrams");
+        std::fs::create_dir_all(root.join("network")).unwrap();
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 86_400);
+        for page in ["home.svg", "network/core.svg"] {
+            std::fs::write(root.join(page), "<svg/>").unwrap();
+            set_modified(&root.join(page), week_ago);
+        }
+        let mut config = Config::from_toml(concat!(
+            "[digest]\nto = [\"team@example.com\"]\nurl = \"https://diagrams.example.com/\"\n",
+            "[smtp]\nhost = \"127.0.0.1\"\n",
+        ))
+        .unwrap();
+        assert!(Digester::new(&root, &config).is_err());
+        config.sidecar.dir = Some(dir.join("sidecar"));
+        let digester = Digester::new(&root, &config).unwrap();
+
+        // The first run only records the baseline
+        let start = SystemTime::now() - Duration::from_secs(86_400);
+        assert!(digester.due(start).unwrap());
+        assert_eq!(digester.run(start).unwrap(), None);
+        assert!(!digester.due(start + Duration::from_secs(86_400)).unwrap());
+        assert!(
+            digester
+                .due(start + Duration::from_secs(7 * 86_400))
+                .unwrap()
+        );
+
+        std::fs::write(root.join("network/edge.svg"), "<svg/>").unwrap();
+        set_modified(&root.join("home.svg"), SystemTime::now());
+        ReviewStore::new(dir.join("sidecar"))
+            .set("network/core", "carol", ReviewStatus::Approved)
+            .unwrap();
+
+        let digest = digester.collect(SystemTime::now()).unwrap().unwrap();
+        assert_eq!(digest.added, ["network:edge"]);
+        assert_eq!(digest.changed, ["home"]);
+        assert_eq!(
+            digest.approved,
+            [("network:core".to_owned(), "carol".to_owned())]
+        );
+        assert_eq!(
+            digest.subject(),
+            "Diagram digest: 1 added, 1 changed, 1 approved"
+        );
+        let text = digest.to_text(config.digest.url.as_deref());
+        assert!(text.contains("- network:edge <https://diagrams.example.com/network:edge>\n"));
+        assert!(text.contains("(by carol)"));
+
+        let message = digester.message(&digest, UNIX_EPOCH);
+        assert!(message.starts_with("From: svg-server@localhost\r\nTo: team@example.com\r\n"));
+        assert!(message.contains("Date: Thu, 01 Jan 1970 00:00:00 +0000\r\n"));
+        assert!(message.contains("Content-Transfer-Encoding: base64\r\n\r\n"));
+        // Dry runs show the body as it reads
+        let preview = digester.preview(&digest, UNIX_EPOCH);
+        assert!(preview.starts_with("From: svg-server@localhost\nTo: team@example.com\n"));
+        assert!(preview.contains("+0000\n\n"));
+        assert!(preview.ends_with(&text));
+    }
+
+    #[test]
+    fn dates_follow_rfc_5322() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_791_970_200);
+        assert_eq!(rfc2822(time), "Wed, 14 Oct 2026 09:30:00 +0000");
+    }
+}
@@ -0,0 +1,550 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Mutex, PoisonError},
+};
+
+use handlebars::Handlebars;
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    Assets, Settings,
+    config::Config,
+    index::SiteIndex,
+    pages::{normalize_page, percent_encode, svg_files},
+    preferences::ViewOptions,
+    raster::{Raster, svg_to_png},
+    render::{PageInfo, render_page},
+    report::FileError,
+};
+
+/// Content hashes of the previous run, kept in the output directory
+pub const STATE_FILE: &str = ".svg-server-export.json";
+
+/// Changes made by the latest run, kept in the output directory
+pub const CHANGES_FILE: &str = ".svg-server-changes.json";
+
+/// Runs in one process take turns, e.g. the scheduled export and one started from the
+/// admin page, rather than overwriting each other's state
+static EXPORTING: Mutex<()> = Mutex::new(());
+
+/// Pixels per CSS pixel of the images attached to Confluence pages, sharp on HiDPI screens
+const CONFLUENCE_SCALE: f32 = 2.0;
+
+/// Writes every page as a static `{page}.html`, next to a copy of its `{page}.svg`,
+/// for a CDN or plain file server to serve, or in [`ExportFormat::Confluence`] or
+/// [`ExportFormat::Standalone`].
+///
+/// Content hashes of each run are saved in the output directory, so later runs (even
+/// from another process) only render and rewrite pages whose source changed, and
+/// remove the output of deleted pages.
+pub struct Exporter {
+    root: PathBuf,
+    out: PathBuf,
+    config: Config,
+    template_engine: Handlebars<'static>,
+    index: SiteIndex,
+    format: ExportFormat,
+    state: ExportState,
+}
+
+/// What the exported files are made for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// `{page}.html` and `{page}.svg` for a static site
+    #[default]
+    Html,
+    /// `{page}.xhtml` in Confluence storage format, showing its `{page}.png` attachment,
+    /// for mirroring into a Confluence space. Pages refer to each other by title, which
+    /// is the page name in `:` separated form.
+    Confluence,
+    /// `{page}.html` alone, holding everything it shows so it can be emailed or
+    /// attached on its own
+    Standalone,
+}
+
+impl ExportFormat {
+    /// Extensions of the page and of the file shown on it, if not inside the page
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            ExportFormat::Html => &["html", "svg"],
+            ExportFormat::Confluence => &["xhtml", "png"],
+            ExportFormat::Standalone => &["html"],
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(ExportFormat::Html),
+            "confluence" => Ok(ExportFormat::Confluence),
+            "standalone" => Ok(ExportFormat::Standalone),
+            _ => Err(format!("Unknown export format '{s}'")),
+        }
+    }
+}
+
+/// What was exported, by content hash
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExportState {
+    /// Changes whenever the same source could render differently
+    renderer: String,
+    pages: BTreeMap<String, PageHashes>,
+}
+
+impl ExportState {
+    /// State saved in `out` by a run with the same `renderer`, empty otherwise
+    fn load(out: &Path, renderer: String) -> Self {
+        std::fs::read(out.join(STATE_FILE))
+            .ok()
+            .and_then(|state| serde_json::from_slice::<ExportState>(&state).ok())
+            .filter(|state| state.renderer == renderer)
+            .unwrap_or(ExportState {
+                renderer,
+                pages: BTreeMap::new(),
+            })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct PageHashes {
+    /// Hash of the SVG and the pages referring to it
+    source: String,
+    /// Hash of the rendered page, whichever the format
+    html: String,
+}
+
+/// What one export run did, also written to [`CHANGES_FILE`]
+#[derive(Debug, Default, Serialize)]
+pub struct ExportSummary {
+    pub format: ExportFormat,
+    /// Pages exported for the first time
+    pub added: Vec<String>,
+    /// Pages whose output was rewritten
+    pub changed: Vec<String>,
+    /// Pages whose output was deleted along with their source
+    pub removed: Vec<String>,
+    /// Number of pages left as they were
+    pub unchanged: usize,
+    /// Pages that could not be exported
+    pub errors: Vec<FileError>,
+}
+
+impl ExportSummary {
+    /// Whether the run touched any output
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+
+    /// Output files, relative to the output directory, that were written or deleted
+    pub fn changed_paths(&self) -> Vec<String> {
+        let touched = self.added.iter().chain(&self.changed).chain(&self.removed);
+        let extensions = self.format.extensions();
+        let mut paths: Vec<_> = touched
+            .flat_map(|page| {
+                extensions
+                    .iter()
+                    .map(move |extension| format!("{page}.{extension}"))
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+}
+
+impl Exporter {
+    /// Export the pages `settings` serves into `out`, picking up where the previous
+    /// export into `out` left off
+    pub fn new(settings: &Settings, out: impl Into<PathBuf>) -> Self {
+        Self::with_format(settings, out, ExportFormat::Html)
+    }
+
+    /// Like [`Exporter::new`], writing files in `format`
+    pub fn with_format(settings: &Settings, out: impl Into<PathBuf>, format: ExportFormat) -> Self {
+        let out = out.into();
+        let state = ExportState::load(&out, renderer_fingerprint(&settings.config, format));
+        Exporter {
+            root: settings.root.clone(),
+            out,
+            config: settings.config.clone(),
+            template_engine: crate::template_engine(),
+            index: SiteIndex::default(),
+            format,
+            state,
+        }
+    }
+
+    /// Export pages changed since the previous run, which is every page the first time
+    pub fn run(&mut self) -> ExportSummary {
+        self.run_with(|_, _| ControlFlow::Continue(()))
+    }
+
+    /// Like [`Exporter::run`], telling `progress` how many pages are done out of how
+    /// many before each one. Breaking stops the run there: the pages exported so far
+    /// are saved, and the output of the others, deleted pages' included, is left alone.
+    pub fn run_with(
+        &mut self,
+        mut progress: impl FnMut(usize, usize) -> ControlFlow<()>,
+    ) -> ExportSummary {
+        let _exporting = EXPORTING.lock().unwrap_or_else(PoisonError::into_inner);
+        // Another exporter may have written to the same directory since the last run
+        let renderer = std::mem::take(&mut self.state.renderer);
+        self.state = ExportState::load(&self.out, renderer);
+
+        let mut summary = ExportSummary {
+            format: self.format,
+            ..Default::default()
+        };
+        let mut seen = BTreeSet::new();
+        self.index.refresh(&self.root);
+
+        let files = svg_files(&self.root);
+        let total = files.len();
+        for (done, (page, path)) in files.into_iter().enumerate() {
+            if progress(done, total).is_break() {
+                return self.finish(summary);
+            }
+            let page = match normalize_page(&page) {
+                Ok(page) => page,
+                Err(error) => {
+                    summary.errors.push(FileError { page, error });
+                    continue;
+                }
+            };
+            seen.insert(page.clone());
+
+            let previous = self.state.pages.get(&page).cloned();
+            match self.export_page(&page, &path, previous.as_ref()) {
+                Ok(hashes) if previous.as_ref() == Some(&hashes) => summary.unchanged += 1,
+                Ok(hashes) => {
+                    self.state.pages.insert(page.clone(), hashes);
+                    match previous {
+                        Some(_) => summary.changed.push(page),
+                        None => summary.added.push(page),
+                    }
+                }
+                Err(error) => summary.errors.push(FileError { page, error }),
+            }
+        }
+
+        let deleted: Vec<_> = self
+            .state
+            .pages
+            .keys()
+            .filter(|page| !seen.contains(*page))
+            .cloned()
+            .collect();
+        for page in deleted {
+            self.state.pages.remove(&page);
+            for extension in self.format.extensions() {
+                let _ = std::fs::remove_file(self.output(&page, extension));
+            }
+            summary.removed.push(page);
+        }
+        let _ = progress(total, total);
+        self.finish(summary)
+    }
+
+    /// Save the state a run left, adding a failure to its errors
+    fn finish(&self, mut summary: ExportSummary) -> ExportSummary {
+        if let Err(e) = self.save(&summary) {
+            summary.errors.push(FileError {
+                page: String::new(),
+                error: format!("Failed to save export state: {e}"),
+            });
+        }
+        summary
+    }
+
+    /// Export one page unless its source and outputs match `previous`
+    fn export_page(
+        &self,
+        page: &str,
+        path: &Path,
+        previous: Option<&PageHashes>,
+    ) -> Result<PageHashes, String> {
+        let svg_content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        // Exports are only rewritten when their source changes, so they can't show an age
+        let info = PageInfo {
+            referenced_by: self.index.backlinks(&self.root, &self.config.routes, page),
+            standalone: self.format == ExportFormat::Standalone,
+            ..PageInfo::default()
+        };
+        let source =
+            sha256_hex(format!("{svg_content}\0{}", info.referenced_by.join("\n")).as_bytes());
+        let outputs: Vec<_> = self
+            .format
+            .extensions()
+            .iter()
+            .map(|extension| self.output(page, extension))
+            .collect();
+        let outputs_exist = outputs.iter().all(|output| output.exists());
+
+        if let Some(previous) = previous.filter(|p| p.source == source && outputs_exist) {
+            return Ok(previous.clone());
+        }
+
+        let render = || {
+            render_page(
+                &self.template_engine,
+                &self.config,
+                page,
+                &svg_content,
+                &ViewOptions::default(),
+                &|target: &str| relative_link(page, target),
+                &info,
+            )
+        };
+        let (rendered, shown) = match self.format {
+            ExportFormat::Html => (
+                render()?,
+                Some(self.config.license.stamp(svg_content.clone().into_bytes())),
+            ),
+            ExportFormat::Confluence => {
+                let raster = svg_to_png(&svg_content, CONFLUENCE_SCALE, &self.config.raster)?;
+                let rendered = storage_page(&self.config, page, &raster, &info.referenced_by);
+                (rendered, Some(raster.png))
+            }
+            ExportFormat::Standalone => (render()?, None),
+        };
+        let hashes = PageHashes {
+            source,
+            html: sha256_hex(rendered.as_bytes()),
+        };
+
+        if let Some(dir) = outputs[0].parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&outputs[0], rendered).map_err(|e| e.to_string())?;
+        if let (Some(shown_path), Some(shown)) = (outputs.get(1), shown) {
+            std::fs::write(shown_path, shown).map_err(|e| e.to_string())?;
+        }
+        Ok(hashes)
+    }
+
+    /// Persist the hashes and the change manifest, replacing the files atomically
+    fn save(&self, summary: &ExportSummary) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.out)?;
+        for (name, content) in [
+            (STATE_FILE, serde_json::to_vec(&self.state)?),
+            (CHANGES_FILE, serde_json::to_vec_pretty(summary)?),
+        ] {
+            let tmp = self.out.join(format!("{name}.tmp"));
+            std::fs::write(&tmp, content)?;
+            std::fs::rename(&tmp, self.out.join(name))?;
+        }
+        Ok(())
+    }
+
+    fn output(&self, page: &str, extension: &str) -> PathBuf {
+        self.out.join(format!("{page}.{extension}"))
+    }
+}
+
+/// Identifies everything besides the source that goes into an exported page
+fn renderer_fingerprint(config: &Config, format: ExportFormat) -> String {
+    let layout = Assets::get("layout.hbs")
+        .map(|file| file.data)
+        .unwrap_or_default();
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(&layout);
+    hasher.update(format!(
+        "{:?}{:?}{:?}{:?}{:?}{format:?}",
+        config.contrast, config.edit, config.license, config.raster, config.view
+    ));
+    hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Path from the exported HTML of `page` to the one of `target`
+fn relative_link(page: &str, target: &str) -> String {
+    let from: Vec<_> = page.split('/').collect();
+    let to: Vec<_> = target.split('/').collect();
+    let (from_dir, to_dir) = (&from[..from.len() - 1], &to[..to.len() - 1]);
+    let common = from_dir
+        .iter()
+        .zip(to_dir)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut link = "../".repeat(from_dir.len() - common);
+    link.push_str(&to[common..].join("/"));
+    format!("{}.html", percent_encode(&link.to_lowercase()))
+}
+
+/// Body of the Confluence page of `page` in storage format, showing the PNG attached
+/// next to it at its CSS size and linking the pages referring to it by title
+fn storage_page(config: &Config, page: &str, raster: &Raster, referenced_by: &[String]) -> String {
+    let title = page.replace('/', ":");
+    let file_name = page.rsplit('/').next().unwrap_or(page);
+    let mut body = format!(
+        "<p><ac:image ac:alt=\"{}\" ac:width=\"{}\" ac:height=\"{}\"><ri:attachment ri:filename=\"{}.png\" /></ac:image></p>\n",
+        escape(&title),
+        raster.width,
+        raster.height,
+        escape(file_name)
+    );
+    if let Some(url) = config.edit.url(page) {
+        body.push_str(&format!(
+            "<p><a href=\"{}\">Edit this diagram</a></p>\n",
+            escape(&url)
+        ));
+    }
+    if !referenced_by.is_empty() {
+        body.push_str("<h2>Referenced by</h2>\n<ul>\n");
+        for page in referenced_by {
+            body.push_str(&format!(
+                "<li><ac:link><ri:page ri:content-title=\"{}\" /></ac:link></li>\n",
+                escape(page.replace('/', ":"))
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+    body
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_link_each_other_relatively() {
+        assert_eq!(relative_link("home", "network/db"), "network/db.html");
+        assert_eq!(relative_link("network/db", "network/cache"), "cache.html");
+        assert_eq!(relative_link("network/db", "home"), "../home.html");
+        assert_eq!(relative_link("a/b/c", "a/d/My Page"), "../d/my%20page.html");
+    }
+
+    #[test]
+    fn only_changed_pages_are_rewritten() {
+        let dir = std::env::temp_dir().join(format!("svg-server-export-{}", std::process::id()));
+        let (root, out) = (dir.join("root"), dir.join("out"));
+        std::fs::create_dir_all(root.join("icons")).unwrap();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+        std::fs::write(root.join("home.svg"), svg).unwrap();
+        std::fs::write(root.join("icons").join("Arrow.svg"), svg).unwrap();
+
+        let settings = Settings::new(&root);
+        let summary = Exporter::new(&settings, &out).run();
+        assert_eq!(summary.added, ["home", "icons/arrow"]);
+        assert!(out.join("icons/arrow.html").exists());
+        assert!(out.join("icons/arrow.svg").exists());
+
+        // A new exporter resumes from the saved hashes
+        let mut exporter = Exporter::new(&settings, &out);
+        let summary = exporter.run();
+        assert!(summary.is_empty());
+        assert_eq!(summary.unchanged, 2);
+
+        std::fs::write(
+            root.join("home.svg"),
+            svg.replace("width=\"1\"", "width=\"2\""),
+        )
+        .unwrap();
+        std::fs::remove_file(root.join("icons").join("Arrow.svg")).unwrap();
+
+        let summary = exporter.run();
+        assert_eq!(summary.changed, ["home"]);
+        assert_eq!(summary.removed, ["icons/arrow"]);
+        assert!(!out.join("icons/arrow.html").exists());
+        assert_eq!(
+            summary.changed_paths(),
+            [
+                "home.html",
+                "home.svg",
+                "icons/arrow.html",
+                "icons/arrow.svg"
+            ]
+        );
+
+        let changes = std::fs::read_to_string(out.join(CHANGES_FILE)).unwrap();
+        assert!(changes.contains("\"removed\": [\n    \"icons/arrow\""));
+    }
+
+    #[test]
+    fn confluence_pages_show_attached_images() {
+        let dir =
+            std::env::temp_dir().join(format!("svg-server-confluence-{}", std::process::id()));
+        let (root, out) = (dir.join("root"), dir.join("out"));
+        std::fs::create_dir_all(root.join("network")).unwrap();
+        std::fs::write(
+            root.join("network").join("db.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="30"><rect width="40" height="30"/></svg>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("home.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><a href="network/db.svg"><rect width="5" height="5"/></a></svg>"#,
+        )
+        .unwrap();
+
+        let settings = Settings::new(&root);
+        let summary = Exporter::with_format(&settings, &out, ExportFormat::Confluence).run();
+        assert!(summary.errors.is_empty(), "{:?}", summary.errors);
+        assert_eq!(
+            summary.changed_paths(),
+            [
+                "home.png",
+                "home.xhtml",
+                "network/db.png",
+                "network/db.xhtml"
+            ]
+        );
+        assert!(!out.join("home.html").exists());
+
+        let page = std::fs::read_to_string(out.join("network/db.xhtml")).unwrap();
+        assert_eq!(
+            page,
+            concat!(
+                "<p><ac:image ac:alt=\"network:db\" ac:width=\"40\" ac:height=\"30\"><ri:attachment ri:filename=\"db.png\" /></ac:image></p>\n",
+                "<h2>Referenced by</h2>\n<ul>\n",
+                "<li><ac:link><ri:page ri:content-title=\"home\" /></ac:link></li>\n",
+                "</ul>\n",
+            )
+        );
+        let png = std::fs::read(out.join("network/db.png")).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+
+        // Switching formats starts over rather than trusting the HTML export's hashes
+        let summary = Exporter::new(&settings, &out).run();
+        assert_eq!(summary.added, ["home", "network/db"]);
+    }
+    #[test]
+    fn standalone_pages_need_no_other_file() {
+        let dir =
+            std::env::temp_dir().join(format!("svg-server-standalone-{}", std::process::id()));
+        let (root, out) = (dir.join("root"), dir.join("out"));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("home.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><rect width="5" height="5"/></svg>"#,
+        )
+        .unwrap();
+
+        let settings = Settings::new(&root);
+        let summary = Exporter::with_format(&settings, &out, ExportFormat::Standalone).run();
+        assert_eq!(summary.changed_paths(), ["home.html"]);
+        assert!(!out.join("home.svg").exists());
+        let page = std::fs::read_to_string(out.join("home.html")).unwrap();
+        assert!(page.contains(r#"<rect width="5" height="5"/>"#));
+        assert!(!page.contains("fonts.googleapis.com"));
+    }
+}
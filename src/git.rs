@@ -0,0 +1,289 @@
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use actix_web::HttpRequest;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::auth::secure_eq;
+
+/// Git refuses to run two pulls in one checkout at once
+static PULLING: Mutex<()> = Mutex::new(());
+
+/// Commits checked out before and after a pull
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Pull {
+    pub before: String,
+    pub head: String,
+}
+
+impl Pull {
+    /// Whether the pull brought in new commits
+    pub fn updated(&self) -> bool {
+        self.before != self.head
+    }
+}
+
+/// What keeps a checkout from fast-forwarding
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Conflict {
+    /// Commits on the checked-out branch that its upstream doesn't have
+    pub local_commits: usize,
+    /// Uncommitted changes, as `git status --porcelain` lists them
+    pub changed_files: Vec<String>,
+}
+
+/// What started a pull
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncTrigger {
+    Webhook,
+    Schedule,
+    /// A sync job started from the admin page
+    Admin,
+}
+
+/// Outcome of one pull
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    pub trigger: SyncTrigger,
+    #[serde(skip)]
+    pub at: SystemTime,
+    pub result: Result<Pull, String>,
+    /// Local commits and changes found when the pull failed
+    pub conflict: Option<Conflict>,
+}
+
+/// The latest pull of the served checkout, shared by the webhook, the schedule and the
+/// admin page reporting on them
+#[derive(Debug, Default)]
+pub struct GitSync {
+    latest: Mutex<Option<SyncReport>>,
+}
+
+impl GitSync {
+    /// [`pull`] `root`, remembering the outcome and, when it fails, what conflicts
+    pub fn pull(&self, root: &Path, trigger: SyncTrigger) -> Result<Pull, String> {
+        let result = pull(root);
+        let conflict = match &result {
+            Ok(_) => None,
+            Err(_) => conflict(root).unwrap_or_default(),
+        };
+        self.record(SyncReport {
+            trigger,
+            at: SystemTime::now(),
+            result: result.clone(),
+            conflict,
+        });
+        result
+    }
+
+    /// Remember `report` as the latest pull, e.g. one the embedding application ran
+    pub fn record(&self, report: SyncReport) {
+        *self.latest.lock().unwrap_or_else(PoisonError::into_inner) = Some(report);
+    }
+
+    pub fn latest(&self) -> Option<SyncReport> {
+        self.latest
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// Fast-forward the Git checkout containing `root` to its upstream branch.
+///
+/// Local commits or changes that would have to be merged make the pull fail, rather
+/// than leaving conflict markers in the served files.
+pub fn pull(root: &Path) -> Result<Pull, String> {
+    let _pulling = PULLING.lock().unwrap_or_else(PoisonError::into_inner);
+    let before = git(root, &["rev-parse", "HEAD"])?;
+    git(root, &["pull", "--ff-only", "--quiet"])?;
+    let head = git(root, &["rev-parse", "HEAD"])?;
+    Ok(Pull { before, head })
+}
+
+/// Local commits and uncommitted changes in the checkout containing `root`, `None`
+/// when it has neither
+pub fn conflict(root: &Path) -> Result<Option<Conflict>, String> {
+    let local_commits = git(root, &["rev-list", "--count", "@{upstream}..HEAD"])?
+        .trim()
+        .parse()
+        .map_err(|e| format!("Unexpected commit count: {e}"))?;
+    let changed_files: Vec<_> = git(root, &["status", "--porcelain"])?
+        .lines()
+        .map(str::to_owned)
+        .collect();
+    if local_commits == 0 && changed_files.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Conflict {
+        local_commits,
+        changed_files,
+    }))
+}
+
+/// Run git in `root` without a terminal to prompt on, returning its output
+fn git(root: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run git: {e}"))?;
+    if output.status.success() {
+        // Leading spaces are part of `git status --porcelain` lines
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_owned())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Whether a webhook delivery comes from whoever knows `secret`: GitHub signs the body
+/// into `X-Hub-Signature-256`, GitLab sends the secret itself as `X-Gitlab-Token`
+pub(crate) fn webhook_authentic(secret: &str, req: &HttpRequest, body: &[u8]) -> bool {
+    if secret.is_empty() {
+        return false;
+    }
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+
+    if let Some(signature) = header("x-hub-signature-256") {
+        let Some(signature) = signature.strip_prefix("sha256=").and_then(hex_decode) else {
+            return false;
+        };
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+        mac.update(body);
+        return mac.verify_slice(&signature).is_ok();
+    }
+    header("x-gitlab-token").is_some_and(|token| secure_eq(secret, token))
+}
+
+/// Whether a webhook delivery announces pushed commits, as opposed to a ping or an
+/// event about issues, tags and the like
+pub(crate) fn webhook_is_push(req: &HttpRequest) -> bool {
+    let event = ["x-github-event", "x-gitlab-event"]
+        .iter()
+        .find_map(|name| req.headers().get(*name)?.to_str().ok());
+    matches!(event, Some("push" | "Push Hook"))
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn diverged_checkouts_conflict() {
+        let git = |dir: &Path, args: &[&str]| {
+            let identity = ["-c", "user.name=Test", "-c", "user.email=test@example.com"];
+            git(dir, &[&identity[..], args].concat()).unwrap();
+        };
+        let dir = std::env::temp_dir().join(format!("svg-server-git-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (origin, served, author) = (dir.join("origin"), dir.join("served"), dir.join("author"));
+        std::fs::create_dir_all(&origin).unwrap();
+        git(
+            &origin,
+            &["init", "--quiet", "--bare", "--initial-branch=main"],
+        );
+        git(&dir, &["clone", "--quiet", "origin", "author"]);
+        std::fs::write(author.join("home.svg"), "<svg/>").unwrap();
+        git(&author, &["add", "."]);
+        git(&author, &["commit", "--quiet", "-m", "Add home"]);
+        git(&author, &["push", "--quiet", "origin", "HEAD:main"]);
+        git(&dir, &["clone", "--quiet", "origin", "served"]);
+        assert_eq!(conflict(&served), Ok(None));
+
+        // Someone fixed a diagram on the server while another change was pushed
+        std::fs::write(served.join("home.svg"), "<svg></svg>").unwrap();
+        git(&served, &["commit", "--quiet", "-am", "Hotfix"]);
+        std::fs::write(served.join("home.svg"), "<svg><g/></svg>").unwrap();
+        std::fs::write(author.join("network.svg"), "<svg/>").unwrap();
+        git(&author, &["add", "."]);
+        git(&author, &["commit", "--quiet", "-m", "Add network"]);
+        git(&author, &["push", "--quiet", "origin", "HEAD:main"]);
+
+        let sync = GitSync::default();
+        assert!(sync.pull(&served, SyncTrigger::Schedule).is_err());
+        let report = sync.latest().unwrap();
+        assert_eq!(report.trigger, SyncTrigger::Schedule);
+        assert_eq!(
+            report.conflict,
+            Some(Conflict {
+                local_commits: 1,
+                changed_files: vec![" M home.svg".to_owned()],
+            })
+        );
+        assert!(!served.join("network.svg").exists());
+    }
+
+    #[test]
+    fn deliveries_are_verified() {
+        // Example from GitHub's documentation on validating webhook deliveries
+        let (secret, body) = ("It's a Secret to Everybody", b"Hello, World!");
+        let github = |signature: &str| {
+            TestRequest::default()
+                .insert_header(("X-Hub-Signature-256", signature))
+                .to_http_request()
+        };
+        let signed =
+            github("sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17");
+        assert!(webhook_authentic(secret, &signed, body));
+        assert!(!webhook_authentic("another secret", &signed, body));
+        assert!(!webhook_authentic(secret, &signed, b"Hello"));
+        assert!(!webhook_authentic(secret, &github("sha256=zz"), body));
+
+        let gitlab = TestRequest::default()
+            .insert_header(("X-Gitlab-Token", "s3cret"))
+            .to_http_request();
+        assert!(webhook_authentic("s3cret", &gitlab, body));
+        assert!(!webhook_authentic("s3cret!", &gitlab, body));
+        let anonymous = TestRequest::default().to_http_request();
+        assert!(!webhook_authentic("s3cret", &anonymous, body));
+
+        // Anyone can sign with an empty secret, or send it as an empty token
+        let empty = TestRequest::default()
+            .insert_header(("X-Gitlab-Token", ""))
+            .to_http_request();
+        assert!(!webhook_authentic("", &empty, body));
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"").unwrap();
+        mac.update(body);
+        let signature: String = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        assert!(!webhook_authentic(
+            "",
+            &github(&format!("sha256={signature}")),
+            body
+        ));
+        for secret in ["", "short"] {
+            let toml = format!("[webhook]\nsecret = \"{secret}\"\n");
+            assert!(crate::Config::from_toml(&toml).is_err(), "{secret:?}");
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Time-limited read-only access for people without an account, through links minted
+//! on the admin page.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::{
+    HttpRequest,
+    cookie::{Cookie, SameSite, time},
+    http::Method,
+};
+
+use crate::{
+    config::{Config, RoutesConfig},
+    pages::{normalize_page, percent_encode},
+    preferences::SessionKey,
+};
+
+const COOKIE_NAME: &str = "svg_server_guest";
+
+/// Longest a guest link can stay valid, in hours
+pub(crate) const MAX_HOURS: u64 = 30 * 24;
+
+/// Read-only access until a point in time, to the whole site or a directory
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GuestPass {
+    /// Seconds since the Unix epoch from which the pass is refused
+    expires: u64,
+    /// Normalized directory the pass is limited to, the whole site if `None`
+    pub(crate) dir: Option<String>,
+}
+
+impl GuestPass {
+    /// Pass valid for `hours` from now
+    pub(crate) fn new(hours: u64, dir: Option<String>) -> Self {
+        GuestPass {
+            expires: unix_time(SystemTime::now()) + hours * 3600,
+            dir,
+        }
+    }
+
+    /// Signed token carried by the guest link, and the cookie the link sets
+    pub(crate) fn token(&self, key: &SessionKey) -> String {
+        let dir = self.dir.as_deref().map(percent_encode).unwrap_or_default();
+        key.sign(COOKIE_NAME, format!("{}:{dir}", self.expires))
+    }
+
+    /// Pass in `token`, unless it was forged or has expired
+    pub(crate) fn from_token(token: &str, key: &SessionKey) -> Option<Self> {
+        let value = key.verify(COOKIE_NAME, token)?;
+        let (expires, dir) = value.split_once(':')?;
+        let pass = GuestPass {
+            expires: expires.parse().ok()?,
+            dir: (!dir.is_empty()).then(|| percent_decode(dir)),
+        };
+        (pass.remaining() > Duration::ZERO).then_some(pass)
+    }
+
+    /// Pass carried by `req`, if any
+    pub(crate) fn of(req: &HttpRequest, key: &SessionKey) -> Option<Self> {
+        GuestPass::from_token(req.cookie(COOKIE_NAME)?.value(), key)
+    }
+
+    fn remaining(&self) -> Duration {
+        Duration::from_secs(self.expires.saturating_sub(unix_time(SystemTime::now())))
+    }
+
+    /// Cookie holding the pass, expiring with it
+    pub(crate) fn cookie(&self, key: &SessionKey) -> Cookie<'static> {
+        Cookie::build(COOKIE_NAME, self.token(key))
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .max_age(time::Duration::seconds(self.remaining().as_secs() as i64))
+            .finish()
+    }
+
+    /// Whether the pass lets `req` through: reading anything but the admin page for the
+    /// whole site, or only the pages inside its directory
+    pub(crate) fn admits(&self, req: &HttpRequest, routes: &RoutesConfig) -> bool {
+        if !matches!(*req.method(), Method::GET | Method::HEAD) {
+            return false;
+        }
+        // Relative to where the service is mounted, still percent-encoded
+        let path = req.match_info().unprocessed().trim_start_matches('/');
+        let Some(dir) = &self.dir else {
+            let first = path.split('/').next().unwrap_or_default();
+            return routes.admin.as_deref() != Some(first);
+        };
+
+        let api = routes.api.as_ref();
+        let prefixes = [
+            &routes.page,
+            &routes.raw,
+            &routes.copy,
+            &routes.standalone,
+            &routes.text,
+            &routes.notebook,
+        ]
+        .into_iter()
+        .flatten()
+        .map(|prefix| format!("{prefix}/"))
+        .chain(
+            ["statistics", "comments", "review"]
+                .into_iter()
+                .filter_map(|route| Some(format!("{}/{route}/", api?))),
+        );
+        let page = prefixes
+            .filter_map(|prefix| path.strip_prefix(&prefix))
+            .next()
+            .unwrap_or(path);
+        let page = percent_decode(page);
+        // Any other path, such as listings, could tell of pages outside the directory
+        !page.contains('/')
+            && normalize_page(&page).is_ok_and(|page| page.starts_with(&format!("{dir}/")))
+    }
+}
+
+/// What the authentication middleware needs to let guests in
+#[derive(Clone)]
+pub(crate) struct GuestAccess {
+    key: SessionKey,
+    routes: RoutesConfig,
+}
+
+impl GuestAccess {
+    /// Guest access under `config`, unless its guest route is turned off
+    pub(crate) fn new(config: &Config) -> Option<Self> {
+        config.routes.guest.as_ref()?;
+        Some(GuestAccess {
+            key: SessionKey::new(&config.session),
+            routes: config.routes.clone(),
+        })
+    }
+
+    /// Whether `req` carries a guest pass admitting it
+    pub(crate) fn admits(&self, req: &HttpRequest) -> bool {
+        GuestPass::of(req, &self.key).is_some_and(|pass| pass.admits(req, &self.routes))
+    }
+}
+
+fn unix_time(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Undo [`percent_encode`], or the encoding of a request path
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| encoded.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+    use crate::config::SessionConfig;
+
+    fn session_key(secret: &str) -> SessionKey {
+        SessionKey::new(&SessionConfig::new(Some(secret.to_owned())).unwrap())
+    }
+
+    #[test]
+    fn tokens_are_signed_and_expire() {
+        let key = session_key("a secret long enough to derive a cookie key");
+        let pass = GuestPass::new(2, Some("audits/2026 q3".to_owned()));
+        let token = pass.token(&key);
+        assert_eq!(GuestPass::from_token(&token, &key), Some(pass.clone()));
+
+        let other = session_key("another secret long enough for a cookie key");
+        assert_eq!(GuestPass::from_token(&token, &other), None);
+        let tampered = token.replace("audits", "public");
+        assert_eq!(GuestPass::from_token(&tampered, &key), None);
+
+        let expired = GuestPass {
+            expires: unix_time(SystemTime::now()) - 1,
+            dir: None,
+        };
+        assert_eq!(GuestPass::from_token(&expired.token(&key), &key), None);
+    }
+
+    #[test]
+    fn directory_passes_only_read_its_pages() {
+        let routes = RoutesConfig::default();
+        let pass = GuestPass::new(1, Some("network".to_owned()));
+        let admits = |method: Method, path: &str| {
+            let req = TestRequest::default()
+                .method(method)
+                .uri(path)
+                .to_http_request();
+            pass.admits(&req, &routes)
+        };
+        assert!(admits(Method::GET, "/network:db"));
+        assert!(admits(Method::GET, "/Network:Edge:Router"));
+        assert!(admits(Method::GET, "/raw/network:db"));
+        assert!(admits(Method::GET, "/copy/network:db"));
+        assert!(admits(Method::GET, "/text/network:db"));
+        assert!(admits(Method::GET, "/api/statistics/network:db"));
+        assert!(admits(Method::HEAD, "/page/network:db"));
+        assert!(!admits(Method::POST, "/network:db"));
+        assert!(!admits(Method::GET, "/network"));
+        assert!(!admits(Method::GET, "/network:..:secrets"));
+        assert!(!admits(Method::GET, "/home"));
+        assert!(!admits(Method::GET, "/api/search"));
+        assert!(!admits(Method::GET, "/api/backlinks/network:db"));
+        assert!(!admits(Method::GET, "/admin"));
+
+        let site = GuestPass::new(1, None);
+        let req = |path: &str| TestRequest::default().uri(path).to_http_request();
+        assert!(site.admits(&req("/api/search"), &routes));
+        assert!(!site.admits(&req("/admin"), &routes));
+    }
+}
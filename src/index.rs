@@ -0,0 +1,468 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::RoutesConfig,
+    links::{LinkTarget, SvgLinks, svg_link_page},
+    pages::{normalize_page, svg_files},
+    search::{SearchQuery, SvgMetadata},
+};
+
+/// How long the index is trusted before files are checked for changes again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Which pages refer to which, through links or by naming them in their text, and
+/// what each is drawn with for search.
+///
+/// The index is brought up to date on use, at most every few seconds, re-reading only
+/// files whose size or modification time changed. Only the first lookup waits for that;
+/// later ones answer from the last refresh while a background thread checks the files.
+#[derive(Debug, Default)]
+pub struct SiteIndex {
+    shared: Arc<Shared>,
+}
+
+/// What lookups and background refreshes share
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<IndexState>,
+    /// Held while files are checked, so refreshes never run side by side
+    refreshing: Mutex<()>,
+    /// Whether a background refresh has been started and not finished yet
+    pending: AtomicBool,
+    /// File the index is kept in across restarts
+    cache: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct IndexState {
+    refreshed: Option<Instant>,
+    /// Whether files need checking before the refresh interval is up
+    invalidated: bool,
+    /// Indexed files keyed by normalized page name
+    files: BTreeMap<String, IndexedFile>,
+    /// Directories and pages of `files`, built when first asked for after a change
+    tree: Option<Arc<SiteTree>>,
+}
+
+/// A directory of the site, with the pages and directories directly inside it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SiteTree {
+    /// Last segment of its path, empty for the root
+    pub name: String,
+    /// Its path as written in page names, `:` separated, empty for the root
+    pub path: String,
+    /// Pages directly inside, sorted by name
+    pub pages: Vec<TreePage>,
+    /// Directories directly inside, sorted by name
+    pub dirs: Vec<SiteTree>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TreePage {
+    /// Last segment of the page name
+    pub name: String,
+    /// Full page name, `:` separated, as it appears in `/{page}` URLs
+    pub page: String,
+}
+
+impl SiteTree {
+    /// Tree of normalized, sorted page names
+    fn build<'a>(pages: impl IntoIterator<Item = &'a String>) -> Self {
+        let mut tree = SiteTree::default();
+        for page in pages {
+            let mut dir = &mut tree;
+            let (dirs, name) = page.rsplit_once('/').unwrap_or(("", page));
+            for segment in dirs.split('/').filter(|segment| !segment.is_empty()) {
+                // Pages come sorted, so a directory is always the last one added
+                if dir.dirs.last().is_none_or(|last| last.name != segment) {
+                    let path = match dir.path.as_str() {
+                        "" => segment.to_owned(),
+                        parent => format!("{parent}:{segment}"),
+                    };
+                    dir.dirs.push(SiteTree {
+                        name: segment.to_owned(),
+                        path,
+                        ..SiteTree::default()
+                    });
+                }
+                dir = dir.dirs.last_mut().unwrap();
+            }
+            dir.pages.push(TreePage {
+                name: name.to_owned(),
+                page: page.replace('/', ":"),
+            });
+        }
+        tree
+    }
+
+    /// The directory at `path` (`:` or `/` separated) inside this one
+    pub fn find(&self, path: &str) -> Option<&SiteTree> {
+        path.split([':', '/'])
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |dir, segment| {
+                let segment = segment.to_lowercase();
+                dir.dirs.iter().find(|inner| inner.name == segment)
+            })
+    }
+}
+
+/// Index as kept on disk, only trusted by the same version for the same directory
+#[derive(Debug, Deserialize)]
+struct CachedIndex {
+    version: String,
+    root: PathBuf,
+    files: BTreeMap<String, IndexedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedFile {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// Normalized names the page links to or mentions, route prefixes included
+    references: BTreeSet<String>,
+    /// Colors, elements and size searched by `/api/search`
+    metadata: SvgMetadata,
+}
+
+impl SiteIndex {
+    /// Index of `root` kept in the `cache` file across restarts.
+    ///
+    /// It starts from what the file holds, so after a restart only the files whose size
+    /// or modification time changed are read again; a missing, unreadable or outdated
+    /// file just means indexing from scratch.
+    pub fn cached(cache: impl Into<PathBuf>, root: &Path) -> Self {
+        let cache = cache.into();
+        let files = std::fs::read_to_string(&cache)
+            .ok()
+            .and_then(|content| serde_json::from_str::<CachedIndex>(&content).ok())
+            .filter(|cached| cached.version == env!("CARGO_PKG_VERSION") && cached.root == root)
+            .map(|cached| cached.files)
+            .unwrap_or_default();
+        SiteIndex {
+            shared: Arc::new(Shared {
+                state: Mutex::new(IndexState {
+                    files,
+                    ..IndexState::default()
+                }),
+                cache: Some(cache),
+                ..Shared::default()
+            }),
+        }
+    }
+
+    /// Pages under `root` referring to `page`, normalized (`/` separated) and sorted
+    pub fn backlinks(&self, root: &Path, routes: &RoutesConfig, page: &str) -> Vec<String> {
+        let Ok(page) = normalize_page(page) else {
+            return Vec::new();
+        };
+        let state = self.fresh_state(root);
+
+        // Links may go through the `/page/` or `/raw/` prefixes as well
+        let names: Vec<_> = [&routes.page, &routes.raw]
+            .into_iter()
+            .flatten()
+            .map(|prefix| format!("{}/{page}", prefix.to_lowercase()))
+            .chain([page.clone()])
+            .collect();
+        state
+            .files
+            .iter()
+            .filter(|(name, file)| {
+                **name != page && names.iter().any(|name| file.references.contains(name))
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Pages under `root` matching `query`, sorted by name, with their metadata
+    pub fn search(&self, root: &Path, query: &SearchQuery) -> Vec<(String, SvgMetadata)> {
+        let state = self.fresh_state(root);
+        state
+            .files
+            .iter()
+            .filter(|(name, file)| query.matches(name, &file.metadata))
+            .map(|(name, file)| (name.clone(), file.metadata.clone()))
+            .collect()
+    }
+
+    /// Pages under `root` by how long ago their file was modified, oldest first, with
+    /// their age in whole days
+    pub fn ages(&self, root: &Path) -> Vec<(String, u64)> {
+        let state = self.fresh_state(root);
+        let mut ages: Vec<_> = state
+            .files
+            .iter()
+            .filter_map(|(name, file)| Some((name.clone(), age_days(file.modified?))))
+            .collect();
+        ages.sort_by(|(a_name, a_days), (b_name, b_days)| {
+            b_days.cmp(a_days).then_with(|| a_name.cmp(b_name))
+        });
+        ages
+    }
+
+    /// Directories and pages under `root`, only rebuilt after files changed
+    pub fn tree(&self, root: &Path) -> Arc<SiteTree> {
+        let mut state = self.fresh_state(root);
+        let state = &mut *state;
+        let tree = state
+            .tree
+            .get_or_insert_with(|| Arc::new(SiteTree::build(state.files.keys())));
+        Arc::clone(tree)
+    }
+
+    /// Have the next lookup re-check every file, e.g. after the directory was swapped
+    pub fn invalidate(&self) {
+        self.shared.state.lock().unwrap().invalidated = true;
+    }
+
+    /// Bring the index up to date with `root` now
+    pub fn refresh(&self, root: &Path) {
+        self.shared.refresh(root);
+    }
+
+    /// The index as last refreshed, first starting a background refresh when it is due
+    fn fresh_state(&self, root: &Path) -> MutexGuard<'_, IndexState> {
+        let state = self.shared.state.lock().unwrap();
+        let Some(refreshed) = state.refreshed else {
+            // Nothing to answer from yet
+            drop(state);
+            self.shared.refresh(root);
+            return self.shared.state.lock().unwrap();
+        };
+        let due = state.invalidated || refreshed.elapsed() >= REFRESH_INTERVAL;
+        if due && !self.shared.pending.swap(true, Ordering::AcqRel) {
+            let (shared, root) = (Arc::clone(&self.shared), root.to_owned());
+            std::thread::spawn(move || {
+                shared.refresh(&root);
+                shared.pending.store(false, Ordering::Release);
+            });
+        }
+        state
+    }
+}
+
+impl Shared {
+    /// Re-read the files under `root` that changed, without holding up lookups
+    /// meanwhile, and save the index if any did
+    fn refresh(&self, root: &Path) {
+        let _refreshing = self
+            .refreshing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let known: BTreeMap<_, _> = {
+            let mut state = self.state.lock().unwrap();
+            state.invalidated = false;
+            state
+                .files
+                .iter()
+                .map(|(page, file)| (page.clone(), (file.modified, file.len)))
+                .collect()
+        };
+
+        // Changed files, or `None` for those still as indexed
+        let mut read = BTreeMap::new();
+        for (page, path) in svg_files(root) {
+            let Ok(page) = normalize_page(&page) else {
+                continue;
+            };
+            let metadata = std::fs::metadata(&path).ok();
+            let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+            let len = metadata.as_ref().map_or(0, |m| m.len());
+
+            let file = match known.get(&page) {
+                Some(&known) if known == (modified, len) => None,
+                _ => {
+                    let content = std::fs::read_to_string(&path).unwrap_or_default();
+                    Some(IndexedFile {
+                        modified,
+                        len,
+                        references: references(&page, &content),
+                        metadata: SvgMetadata::collect(&content).unwrap_or_default(),
+                    })
+                }
+            };
+            read.insert(page, file);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // Whatever isn't read again was deleted
+        let changed = read.values().any(Option::is_some) || read.len() != state.files.len();
+        let mut indexed = std::mem::take(&mut state.files);
+        state.files = read
+            .into_iter()
+            .filter_map(|(page, file)| {
+                let file = file.or_else(|| indexed.remove(&page))?;
+                Some((page, file))
+            })
+            .collect();
+        state.refreshed = Some(Instant::now());
+        if !changed {
+            return;
+        }
+        state.tree = None;
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        let cached = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "root": root,
+            "files": state.files,
+        })
+        .to_string();
+        drop(state);
+
+        // Written aside first, so a crash never leaves half an index behind
+        let partial = cache.with_extension("json.tmp");
+        let saved = cache
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&partial, cached))
+            .and_then(|()| std::fs::rename(&partial, cache));
+        if let Err(e) = saved {
+            eprintln!("Failed to save the index to '{}': {e}", cache.display());
+        }
+    }
+}
+
+/// Whole days since `modified`, 0 for times in the future
+pub fn age_days(modified: SystemTime) -> u64 {
+    modified
+        .elapsed()
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400)
+}
+
+/// Normalized names of everything `page` links to or names in its text
+fn references(page: &str, svg_content: &str) -> BTreeSet<String> {
+    let Ok(document) = SvgLinks::collect(svg_content) else {
+        return BTreeSet::new();
+    };
+
+    let links = document.links.iter().filter_map(|link| {
+        svg_link_page(page, &link.href).or_else(|| match link.target() {
+            LinkTarget::Page(name) => Some(name.to_owned()),
+            _ => None,
+        })
+    });
+    let mentions = document
+        .mentions
+        .iter()
+        .map(|mention| mention.trim_start_matches('/').to_owned());
+    links
+        .chain(mentions)
+        .filter_map(|name| normalize_page(&name).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_and_mentions_are_backlinks() {
+        let root = std::env::temp_dir().join(format!("svg-server-index-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("network")).unwrap();
+        let write = |name: &str, content: &str| {
+            std::fs::write(
+                root.join(name),
+                format!(r#"<svg xmlns="http://www.w3.org/2000/svg">{content}</svg>"#),
+            )
+            .unwrap();
+        };
+        write("network/db.svg", "<text>Primary database</text>");
+        write("network/overview.svg", r#"<a href="db.svg"><rect/></a>"#);
+        write("home.svg", r#"<a href="/page/network:overview"/>"#);
+        write(
+            "runbook.svg",
+            "<text>Restore network:db from backups</text>",
+        );
+        write(
+            "unrelated.svg",
+            r#"<a href="https://example.com/network:db"/>"#,
+        );
+
+        let index = SiteIndex::default();
+        let routes = RoutesConfig::default();
+        assert_eq!(
+            index.backlinks(&root, &routes, "Network:DB"),
+            ["network/overview", "runbook"]
+        );
+        assert_eq!(
+            index.backlinks(&root, &routes, "network:overview"),
+            ["home"]
+        );
+        assert!(index.backlinks(&root, &routes, "home").is_empty());
+
+        let year_ago = SystemTime::now() - Duration::from_secs(365 * 86_400);
+        std::fs::File::options()
+            .write(true)
+            .open(root.join("runbook.svg"))
+            .unwrap()
+            .set_modified(year_ago)
+            .unwrap();
+        // Lookups answer from the last refresh while the files are checked behind them
+        index.invalidate();
+        assert_eq!(index.ages(&root)[0].1, 0);
+        index.refresh(&root);
+        let ages = index.ages(&root);
+        assert_eq!(ages[0], ("runbook".to_owned(), 365));
+        assert_eq!(ages.len(), 5);
+
+        let tree = index.tree(&root);
+        let names = |dir: &SiteTree| -> Vec<String> {
+            dir.pages.iter().map(|page| page.page.clone()).collect()
+        };
+        assert_eq!(names(&tree), ["home", "runbook", "unrelated"]);
+        assert_eq!(tree.dirs.len(), 1);
+        assert_eq!(names(&tree.dirs[0]), ["network:db", "network:overview"]);
+        assert_eq!(tree.find("Network").unwrap().path, "network");
+        assert!(tree.find("network:db").is_none());
+        // Cached until a file is added or removed
+        assert!(Arc::ptr_eq(&tree, &index.tree(&root)));
+        std::fs::remove_file(root.join("unrelated.svg")).unwrap();
+        index.refresh(&root);
+        assert_eq!(names(&index.tree(&root)), ["home", "runbook"]);
+    }
+
+    #[test]
+    fn cached_index_survives_restarts() {
+        let dir = std::env::temp_dir().join(format!("svg-server-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (root, cache) = (dir.join("diagrams"), dir.join("sidecar/index.json"));
+        std::fs::create_dir_all(&root).unwrap();
+        let home = root.join("home.svg");
+        std::fs::write(root.join("db.svg"), "<svg/>").unwrap();
+        std::fs::write(&home, r#"<svg><a href="db.svg"/></svg>"#).unwrap();
+        let routes = RoutesConfig::default();
+
+        let index = SiteIndex::cached(&cache, &root);
+        assert_eq!(index.backlinks(&root, &routes, "db"), ["home"]);
+        assert!(cache.exists());
+
+        // Same size and modification time, so a restart trusts what was cached
+        let modified = std::fs::metadata(&home).unwrap().modified().unwrap();
+        std::fs::write(&home, r#"<svg><a href="no.svg"/></svg>"#).unwrap();
+        let file = std::fs::File::options().write(true).open(&home).unwrap();
+        file.set_modified(modified).unwrap();
+        let restarted = SiteIndex::cached(&cache, &root);
+        assert_eq!(restarted.backlinks(&root, &routes, "db"), ["home"]);
+
+        // Anything else is read again, and a cache for another directory ignored
+        file.set_modified(modified + Duration::from_secs(1))
+            .unwrap();
+        let restarted = SiteIndex::cached(&cache, &root);
+        assert!(restarted.backlinks(&root, &routes, "db").is_empty());
+        assert_eq!(restarted.backlinks(&root, &routes, "no"), ["home"]);
+        let elsewhere = SiteIndex::cached(&cache, &dir);
+        assert!(elsewhere.shared.state.lock().unwrap().files.is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    ops::ControlFlow,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Settings,
+    config::Config,
+    export::Exporter,
+    git::{GitSync, SyncTrigger},
+    index::SiteIndex,
+};
+
+/// Finished jobs remembered for `/admin/api/jobs`, older ones being forgotten
+const KEPT_JOBS: usize = 20;
+
+/// What a job does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobKind {
+    /// Export changed pages to `--export-out`, cancellable between pages
+    Export,
+    /// Pull the served Git checkout, which can't be stopped halfway
+    Sync,
+}
+
+impl JobKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            JobKind::Export => "export",
+            JobKind::Sync => "sync",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+/// Where a job stands, as `/admin/api/jobs` reports it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub kind: JobKind,
+    pub state: JobState,
+    /// Steps done out of `total`, pages for an export
+    pub done: usize,
+    pub total: usize,
+    /// What the job did, or why it failed, once it is over
+    pub message: Option<String>,
+}
+
+/// A long-running operation, updated by the thread running it
+#[derive(Debug)]
+pub struct Job {
+    status: Mutex<JobStatus>,
+    cancelled: AtomicBool,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Record that `done` of `total` steps are over
+    pub fn progress(&self, done: usize, total: usize) {
+        let mut status = self.status.lock().unwrap();
+        (status.done, status.total) = (done, total);
+    }
+
+    /// Ask the job to stop at its next chance, returning whether it was still running
+    pub fn cancel(&self) -> bool {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.status.lock().unwrap().state == JobState::Running
+    }
+
+    /// Whether the job was asked to stop
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Record how the job ended, with what it did or why it failed
+    pub fn finish(&self, state: JobState, message: impl Into<String>) {
+        let mut status = self.status.lock().unwrap();
+        status.state = state;
+        status.message = Some(message.into());
+    }
+}
+
+/// Jobs started from the admin page, shared by every worker so any of them can report
+/// on or cancel a job another one started
+#[derive(Debug, Default)]
+pub struct Jobs {
+    next_id: AtomicU64,
+    jobs: Mutex<Vec<Arc<Job>>>,
+}
+
+impl Jobs {
+    /// Track a new job of `kind`, or return the one of that kind still running: two
+    /// exports or pulls at once would only get in each other's way
+    pub fn start(&self, kind: JobKind) -> Result<Arc<Job>, Arc<Job>> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let running = jobs.iter().find(|job| {
+            let status = job.status();
+            status.kind == kind && status.state == JobState::Running
+        });
+        if let Some(running) = running {
+            return Err(Arc::clone(running));
+        }
+
+        let job = Arc::new(Job {
+            status: Mutex::new(JobStatus {
+                id: self.next_id.fetch_add(1, Ordering::Relaxed) + 1,
+                kind,
+                state: JobState::Running,
+                done: 0,
+                total: 0,
+                message: None,
+            }),
+            cancelled: AtomicBool::new(false),
+        });
+        jobs.push(Arc::clone(&job));
+
+        let finished = jobs
+            .iter()
+            .filter(|job| job.status().state != JobState::Running)
+            .count();
+        let mut forgotten = finished.saturating_sub(KEPT_JOBS);
+        jobs.retain(|job| {
+            let forget = forgotten > 0 && job.status().state != JobState::Running;
+            forgotten -= usize::from(forget);
+            !forget
+        });
+        Ok(job)
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Job>> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().find(|job| job.status().id == id).cloned()
+    }
+
+    /// Every job remembered, the latest first
+    pub fn list(&self) -> Vec<JobStatus> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().rev().map(|job| job.status()).collect()
+    }
+}
+
+/// What the jobs of one service run against
+#[derive(Debug)]
+pub(crate) struct JobRunner {
+    pub(crate) root: PathBuf,
+    pub(crate) config: Config,
+    /// Where exports are written, none meaning export jobs can't be started
+    pub(crate) export_out: Option<PathBuf>,
+    pub(crate) site_index: Arc<SiteIndex>,
+    pub(crate) git_sync: Arc<GitSync>,
+}
+
+impl JobRunner {
+    /// Run `job` on a thread of its own, exports and pulls both blocking on I/O
+    pub(crate) fn spawn(&self, job: Arc<Job>) {
+        let status = job.status();
+        match status.kind {
+            JobKind::Export => {
+                let Some(out) = &self.export_out else {
+                    job.finish(JobState::Failed, "No --export-out directory to export to");
+                    return;
+                };
+                let settings = Settings {
+                    config: self.config.clone(),
+                    ..Settings::new(&self.root)
+                };
+                let mut exporter = Exporter::new(&settings, out);
+                std::thread::spawn(move || {
+                    let mut stopped = false;
+                    let summary = exporter.run_with(|done, total| {
+                        job.progress(done, total);
+                        stopped = job.is_cancelled() && done < total;
+                        if stopped {
+                            ControlFlow::Break(())
+                        } else {
+                            ControlFlow::Continue(())
+                        }
+                    });
+                    let counts = format!(
+                        "{} added, {} changed, {} removed, {} unchanged, {} failed",
+                        summary.added.len(),
+                        summary.changed.len(),
+                        summary.removed.len(),
+                        summary.unchanged,
+                        summary.errors.len()
+                    );
+                    for error in &summary.errors {
+                        eprintln!("Failed to export '{}': {}", error.page, error.error);
+                    }
+                    let (state, message) = if stopped {
+                        let JobStatus { done, total, .. } = job.status();
+                        let cancelled = format!("Cancelled after {done} of {total} pages");
+                        (JobState::Cancelled, format!("{cancelled}: {counts}"))
+                    } else if summary.errors.is_empty() {
+                        (JobState::Succeeded, counts)
+                    } else {
+                        (JobState::Failed, counts)
+                    };
+                    println!("Export job {}: {message}", status.id);
+                    job.finish(state, message);
+                });
+            }
+            JobKind::Sync => {
+                let (root, sync, index) = (
+                    self.root.clone(),
+                    Arc::clone(&self.git_sync),
+                    Arc::clone(&self.site_index),
+                );
+                std::thread::spawn(move || {
+                    job.progress(0, 1);
+                    let result = sync.pull(&root, SyncTrigger::Admin);
+                    job.progress(1, 1);
+                    match result {
+                        Ok(pull) if pull.updated() => {
+                            let message = format!("Pulled {} to {}", pull.before, pull.head);
+                            println!("Sync job {}: {message}", status.id);
+                            index.invalidate();
+                            job.finish(JobState::Succeeded, message);
+                        }
+                        Ok(pull) => {
+                            job.finish(JobState::Succeeded, format!("Already at {}", pull.head))
+                        }
+                        Err(e) => {
+                            eprintln!("Sync job {} failed: {e}", status.id);
+                            job.finish(JobState::Failed, e);
+                        }
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_job_of_a_kind_runs_at_a_time() {
+        let jobs = Jobs::default();
+        let export = jobs.start(JobKind::Export).unwrap();
+        assert_eq!(jobs.start(JobKind::Export).unwrap_err().status().id, 1);
+        let sync = jobs.start(JobKind::Sync).unwrap();
+        assert_eq!(sync.status().id, 2);
+
+        export.progress(3, 10);
+        assert!(export.cancel());
+        assert!(export.is_cancelled());
+        export.finish(JobState::Cancelled, "Cancelled after 3 of 10 pages");
+        assert!(!export.cancel());
+        assert_eq!(
+            jobs.get(1).unwrap().status(),
+            JobStatus {
+                id: 1,
+                kind: JobKind::Export,
+                state: JobState::Cancelled,
+                done: 3,
+                total: 10,
+                message: Some("Cancelled after 3 of 10 pages".to_owned()),
+            }
+        );
+        assert_eq!(jobs.start(JobKind::Export).unwrap().status().id, 3);
+    }
+
+    #[test]
+    fn only_recent_finished_jobs_are_kept() {
+        let jobs = Jobs::default();
+        let running = jobs.start(JobKind::Sync).unwrap();
+        for _ in 0..KEPT_JOBS + 5 {
+            let job = jobs.start(JobKind::Export).unwrap();
+            job.finish(JobState::Succeeded, "Done");
+        }
+        // Jobs are forgotten when the next one starts
+        let latest = jobs.start(JobKind::Export).unwrap();
+        let listed = jobs.list();
+        assert_eq!(listed.len(), KEPT_JOBS + 2);
+        assert_eq!(listed[0].id, latest.status().id);
+        assert_eq!(listed.last().unwrap().id, running.status().id);
+        assert!(jobs.get(2).is_none());
+    }
+}
@@ -0,0 +1,444 @@
+//! Serve a directory of SVG files as HTML pages.
+//!
+//! Besides running the `svg-server` binary, the viewer can be mounted inside an
+//! existing actix-web application:
+//!
+//! ```no_run
+//! use actix_web::{App, HttpServer, web};
+//! use svg_server::Settings;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! HttpServer::new(|| {
+//!     App::new().service(
+//!         web::scope("/diagrams").service(svg_server::service(Settings::new("./diagrams"))),
+//!     )
+//! })
+//! .bind("127.0.0.1:8080")?
+//! .run()
+//! .await
+//! # }
+//! ```
+//!
+//! Applications that guard their routes with their own middleware and auth can mount
+//! the routes directly with [`scope`]:
+//!
+//! ```no_run
+//! use actix_web::{App, HttpServer, middleware::DefaultHeaders};
+//! use svg_server::Settings;
+//!
+//! # async fn run() -> std::io::Result<()> {
+//! HttpServer::new(|| {
+//!     App::new()
+//!         .wrap(DefaultHeaders::new().add(("X-Frame-Options", "DENY")))
+//!         .service(svg_server::scope("/diagrams", Settings::new("./diagrams")))
+//! })
+//! .bind("127.0.0.1:8080")?
+//! .run()
+//! .await
+//! # }
+//! ```
+//!
+//! The SVG transforms behind the viewer's query parameters are exposed as plain
+//! functions in their own modules, re-exported from the I/O-free `svg-server-core`
+//! crate.
+
+mod api;
+mod archive;
+pub mod auth;
+pub mod comments;
+mod compare;
+mod compress;
+mod conditional;
+pub mod config;
+pub mod digest;
+pub mod export;
+pub mod git;
+mod guest;
+pub mod index;
+pub mod jobs;
+pub mod mirror;
+mod pages;
+mod preferences;
+#[cfg(feature = "s3")]
+pub mod publish;
+mod raster;
+pub mod recolor;
+mod render;
+pub mod report;
+pub mod review;
+pub mod rewrite;
+mod routes;
+pub mod smtp;
+pub mod stats;
+mod trace;
+pub mod validate;
+
+use std::{path::PathBuf, sync::Arc};
+
+use actix_web::{
+    Error, HttpRequest, Scope,
+    body::MessageBody,
+    dev::{HttpServiceFactory, ServiceFactory, ServiceRequest, ServiceResponse},
+    middleware, web,
+};
+use auth::Authenticator;
+use comments::CommentStore;
+use git::GitSync;
+use guest::GuestAccess;
+use handlebars::Handlebars;
+use index::SiteIndex;
+use jobs::{JobRunner, Jobs};
+use preferences::SessionKey;
+use render::SiteTreeHelper;
+use review::ReviewStore;
+use rust_embed::RustEmbed;
+use stats::ViewStats;
+
+pub use config::Config;
+pub use svg_server_core::{
+    contrast, links, outline, resize, search, simulate, sprite, standalone, statistics, svg,
+};
+
+/// Everything needed to serve a directory of SVG files
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Directory containing the SVG files to be served
+    pub root: PathBuf,
+    /// Route to redirect the service root to, relative to where the service is mounted
+    pub index: String,
+    /// Whether pages can be enumerated (e.g. whole-directory sprites), rather than only
+    /// reached by exact name
+    pub listing: bool,
+    /// Settings loaded from the configuration file
+    pub config: Config,
+    /// Decides who may view pages, in place of the one `config.auth` describes
+    pub authenticator: Option<Arc<dyn Authenticator>>,
+    /// Page view counts served by `/api/stats`, shared by every clone of the settings
+    pub view_stats: Arc<ViewStats>,
+    /// Cross-references between pages behind "Referenced by" and `/api/backlinks`,
+    /// shared by every clone of the settings
+    pub site_index: Arc<SiteIndex>,
+    /// Latest pull of the served Git checkout, from the webhook or `--git-pull-interval`,
+    /// shown on the admin page
+    pub git_sync: Arc<GitSync>,
+    /// Exports and pulls started from the admin page, shared by every clone of the
+    /// settings
+    pub jobs: Arc<Jobs>,
+    /// Directory the admin page's export jobs write to, without which there are none
+    pub export_out: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Serve `root` with the default index route and configuration
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Settings {
+            root: root.into(),
+            index: "/home".to_owned(),
+            listing: true,
+            config: Config::default(),
+            authenticator: None,
+            view_stats: Arc::default(),
+            site_index: Arc::default(),
+            git_sync: Arc::default(),
+            jobs: Arc::default(),
+            export_out: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SvgPath(PathBuf);
+
+#[derive(Debug, Clone)]
+struct RedirectIndexTo(String);
+
+/// Whether pages may be enumerated, as opposed to only reached by exact name.
+///
+/// Listings, search results, backlinks, view stats, sprites and archives of whole
+/// directories all name pages a visitor didn't ask for, so servers started with
+/// `--no-listing` never serve them. It counts as enabled where it isn't registered, as
+/// it is by default.
+#[derive(Debug, Clone)]
+struct ListingEnabled(bool);
+
+impl ListingEnabled {
+    /// Whether listing is enabled for the service `req` reached
+    fn of(req: &HttpRequest) -> bool {
+        req.app_data::<web::Data<ListingEnabled>>()
+            .is_none_or(|listing| listing.0)
+    }
+}
+
+#[derive(RustEmbed)]
+#[folder = "templates"]
+struct Assets;
+
+/// Handlebars registry with every embedded template
+fn template_engine() -> Handlebars<'static> {
+    // Initialize Handlebars
+    let mut hb = Handlebars::new();
+
+    // Register templates from files
+    hb.register_embed_templates_with_extension::<Assets>(".hbs")
+        .unwrap();
+
+    hb
+}
+
+/// Build the SVG viewer service, to be registered on an `App` or inside a `web::scope`.
+///
+/// Requests are checked by `settings.authenticator`, or the `[auth]` credentials.
+pub fn service(mut settings: Settings) -> impl HttpServiceFactory {
+    let authenticator = settings
+        .authenticator
+        .take()
+        .unwrap_or_else(|| settings.config.auth.authenticator());
+
+    // Webhook deliveries prove themselves with their signature instead, and guest links
+    // with their token
+    let guest = settings.config.routes.guest.as_ref();
+    let public: Arc<[String]> = webhook_path(&settings.config)
+        .into_iter()
+        .chain(guest.map(|guest| format!("/{guest}")))
+        .collect();
+    let guests = GuestAccess::new(&settings.config);
+    // Rewritten before authentication, so guest passes are checked against the page
+    // actually served
+    let rules: Arc<[_]> = settings.config.rewrite.clone().into();
+    routes("", settings)
+        .wrap(middleware::from_fn(move |req, next| {
+            auth::require(
+                Arc::clone(&authenticator),
+                Arc::clone(&public),
+                guests.clone(),
+                req,
+                next,
+            )
+        }))
+        .wrap(middleware::from_fn(move |req, next| {
+            rewrite::apply(Arc::clone(&rules), req, next)
+        }))
+}
+
+/// Path of the push webhook inside the service, when one is configured
+fn webhook_path(config: &Config) -> Option<String> {
+    let api = config.routes.api.as_ref()?;
+    // Never empty, WebhookConfig only holds secrets long enough to be safe
+    config.webhook.secret()?;
+    Some(format!("/{api}/webhook"))
+}
+
+/// Every viewer route mounted under `path`, for applications that bring their own
+/// middleware.
+///
+/// Unlike [`service`], no authentication is applied: neither `settings.authenticator`
+/// nor the `[auth]` credentials are checked, so wrap the scope in the application's
+/// own auth middleware where pages are not public. `[[rewrite]]` rules apply inside it.
+pub fn scope(
+    path: &str,
+    settings: Settings,
+) -> Scope<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = Error,
+        InitError = (),
+    >,
+> {
+    let rules: Arc<[_]> = settings.config.rewrite.clone().into();
+    routes(path, settings).wrap(middleware::from_fn(move |req, next| {
+        rewrite::apply(Arc::clone(&rules), req, next)
+    }))
+}
+
+/// Every viewer route mounted under `path`, before any middleware
+fn routes(path: &str, settings: Settings) -> Scope {
+    let mut hb = template_engine();
+    let tree = SiteTreeHelper {
+        root: settings.root.clone(),
+        index: Arc::clone(&settings.site_index),
+        listing: settings.listing,
+    };
+    hb.register_helper("site_tree", Box::new(tree));
+    let names = settings.config.routes.clone();
+    let webhook = webhook_path(&settings.config);
+    let sidecar = settings.config.sidecar.dir.clone();
+    let runner = JobRunner {
+        root: settings.root.clone(),
+        config: settings.config.clone(),
+        export_out: settings.export_out,
+        site_index: Arc::clone(&settings.site_index),
+        git_sync: Arc::clone(&settings.git_sync),
+    };
+
+    let mut scope = web::scope(path)
+        .app_data(web::Data::new(hb))
+        .app_data(web::Data::new(SvgPath(settings.root)))
+        .app_data(web::Data::new(RedirectIndexTo(settings.index)))
+        .app_data(web::Data::new(ListingEnabled(settings.listing)))
+        .app_data(web::Data::from(settings.view_stats))
+        .app_data(web::Data::from(settings.site_index))
+        .app_data(web::Data::from(settings.git_sync))
+        .app_data(web::Data::from(settings.jobs))
+        .app_data(web::Data::new(runner))
+        .app_data(web::Data::new(SessionKey::new(&settings.config.session)))
+        .app_data(web::Data::new(settings.config))
+        .service(routes::home_redirect);
+    if let Some(dir) = &sidecar {
+        scope = scope
+            .app_data(web::Data::new(CommentStore::new(dir)))
+            .app_data(web::Data::new(ReviewStore::new(dir)));
+    }
+
+    if let Some(raw) = &names.raw {
+        scope = scope.route(&format!("/{raw}/{{page}}"), web::get().to(routes::raw_svg));
+    }
+    if let Some(copy) = &names.copy {
+        scope = scope.route(
+            &format!("/{copy}/{{page}}"),
+            web::get().to(routes::copy_svg),
+        );
+    }
+    if let Some(standalone) = &names.standalone {
+        scope = scope.route(
+            &format!("/{standalone}/{{page}}"),
+            web::get().to(routes::standalone_page),
+        );
+    }
+    if let Some(text) = &names.text {
+        scope = scope.route(
+            &format!("/{text}/{{page}}"),
+            web::get().to(routes::text_page),
+        );
+    }
+    if let Some(notebook) = &names.notebook {
+        scope = scope.route(
+            &format!("/{notebook}/{{page}}"),
+            web::get().to(routes::notebook_svg),
+        );
+    }
+    if let Some(sprite) = &names.sprite {
+        scope = scope.route(&format!("/{sprite}"), web::get().to(routes::sprite_svg));
+    }
+    if let Some(archive) = &names.archive {
+        scope = scope.route(
+            &format!("/{archive}/{{dir}}.tar.gz"),
+            web::get().to(routes::archive_dir),
+        );
+    }
+    if let Some(api) = &names.api {
+        scope = scope.route(
+            &format!("/{api}/statistics/{{page}}"),
+            web::get().to(routes::page_statistics),
+        );
+        scope = scope.service(
+            web::resource(format!("/{api}/compare/{{page}}"))
+                // Full-size exports of large diagrams run to megabytes
+                .app_data(web::PayloadConfig::new(20 << 20))
+                .route(web::post().to(routes::compare_page)),
+        );
+        scope = scope.route(&format!("/{api}/stats"), web::get().to(routes::view_stats));
+        scope = scope.route(
+            &format!("/{api}/backlinks/{{page}}"),
+            web::get().to(routes::backlinks),
+        );
+        scope = scope.route(&format!("/{api}/stale"), web::get().to(routes::stale_pages));
+        scope = scope.route(&format!("/{api}/search"), web::get().to(routes::search));
+        if sidecar.is_some() {
+            scope = scope.service(
+                web::resource(format!("/{api}/comments/{{page}}"))
+                    .route(web::get().to(routes::page_comments))
+                    .route(web::post().to(routes::add_comment)),
+            );
+            scope = scope.service(
+                web::resource(format!("/{api}/review/{{page}}"))
+                    .route(web::get().to(routes::page_review))
+                    .route(web::post().to(routes::set_review)),
+            );
+            scope = scope.route(&format!("/{api}/reviews"), web::get().to(routes::reviews));
+        }
+        if let Some(path) = webhook {
+            scope = scope.service(
+                web::resource(path)
+                    // GitHub push payloads reach 25 MB
+                    .app_data(web::PayloadConfig::new(25 << 20))
+                    .route(web::post().to(routes::git_webhook)),
+            );
+        }
+        scope = scope.route(
+            &format!("/{api}/{{tail:.*}}"),
+            web::route().to(api::not_found),
+        );
+    }
+
+    if let Some(admin) = &names.admin {
+        scope = scope.route(&format!("/{admin}"), web::get().to(routes::admin_page));
+        scope = scope.service(
+            web::resource(format!("/{admin}/api/jobs"))
+                .route(web::get().to(routes::list_jobs))
+                .route(web::post().to(routes::start_job)),
+        );
+        scope = scope.service(
+            web::resource(format!("/{admin}/api/jobs/{{id}}"))
+                .route(web::get().to(routes::job_status))
+                .route(web::delete().to(routes::cancel_job)),
+        );
+        scope = scope.route(
+            &format!("/{admin}/api/jobs/{{id}}/events"),
+            web::get().to(routes::job_events),
+        );
+        if names.guest.is_some() {
+            scope = scope.route(
+                &format!("/{admin}/guest"),
+                web::post().to(routes::mint_guest_link),
+            );
+        }
+    }
+    if let Some(guest) = &names.guest {
+        scope = scope.route(&format!("/{guest}"), web::get().to(routes::guest_landing));
+    }
+    if let Some(preferences) = &names.preferences {
+        scope = scope.route(
+            &format!("/{preferences}"),
+            web::get().to(routes::set_preferences),
+        );
+    }
+    if let Some(page) = &names.page {
+        scope = scope.route(
+            &format!("/{page}/{{page}}"),
+            web::get().to(routes::render_svg),
+        );
+        if sidecar.is_some() {
+            scope = scope.route(
+                &format!("/{page}/{{page}}"),
+                web::post().to(routes::comment_form),
+            );
+        }
+    }
+
+    if sidecar.is_some() {
+        scope = scope.route("/{page}", web::post().to(routes::comment_form));
+    }
+    scope.route("/{page}", web::get().to(routes::render_svg))
+}
+
+/// A page whose `/{page}` URL is taken by a built-in route
+#[derive(Debug, Clone)]
+pub struct Collision {
+    /// Name of the shadowed page
+    pub page: String,
+    /// File the page is served from
+    pub path: PathBuf,
+}
+
+/// Find pages that built-in routes shadow, so they can be reported at startup
+pub fn reserved_collisions(settings: &Settings) -> Vec<Collision> {
+    let reserved = settings.config.routes.reserved_names();
+
+    pages::svg_files(&settings.root)
+        .into_iter()
+        .filter(|(page, _)| reserved.contains(&page.to_lowercase().as_str()))
+        .map(|(page, path)| Collision { page, path })
+        .collect()
+}
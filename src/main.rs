@@ -1,14 +1,37 @@
 mod usage_guide;
 
-use std::{net::SocketAddr, path::PathBuf, sync::LazyLock};
+use std::{
+    net::{SocketAddr, TcpListener},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
-use actix_web::{App, HttpResponse, HttpServer, Responder, get, web};
-use handlebars::Handlebars;
-use regex::Regex;
-use rust_embed::RustEmbed;
+use actix_web::{
+    App, HttpServer,
+    http::KeepAlive,
+    middleware::{Compress, Condition},
+};
+use socket2::{Domain, Protocol, Socket, Type};
 use structopt::StructOpt;
+use svg_server::{
+    Config, Settings,
+    digest::{Digest, Digester},
+    export::{ExportFormat, ExportSummary, Exporter},
+    git::SyncTrigger,
+    index::SiteIndex,
+    mirror::Mirror,
+    recolor::{ColorMapping, Recoloring},
+    report::{FontReport, Report},
+    validate::LinkReport,
+};
 use usage_guide::USAGE_GUIDE;
 
+/// How often the server checks whether a digest is due
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 #[derive(Debug, StructOpt)]
 struct Opt {
     /// Bind address to listen on
@@ -23,141 +46,742 @@ struct Opt {
     #[structopt(short = "i", long = "index", default_value = "/home")]
     index: String,
 
+    /// Disable every way of enumerating pages, so they are only reachable by exact name
+    #[structopt(long = "no-listing")]
+    no_listing: bool,
+
+    /// Number of worker threads, at least 1 [default: number of physical CPU cores]
+    #[structopt(short = "w", long = "workers")]
+    workers: Option<NonZeroUsize>,
+
+    /// Keep-alive timeout for idle connections in seconds, 0 disables keep-alive
+    #[structopt(short = "k", long = "keep-alive", default_value = "5")]
+    keep_alive: u64,
+
+    /// Let another server bind the same address and port, to upgrade without downtime
+    /// (Unix only)
+    #[structopt(long = "reuse-port")]
+    reuse_port: bool,
+
+    /// Secondary server to mirror a sample of GET requests to, e.g. http://127.0.0.1:5001
+    #[structopt(long = "mirror-to")]
+    mirror_to: Option<String>,
+
+    /// Fraction of GET requests to mirror, between 0 and 1
+    #[structopt(long = "mirror-sample", default_value = "0.1")]
+    mirror_sample: f64,
+
+    /// Re-export changed pages to --export-out this often, e.g. 30m or 1h
+    #[structopt(long = "export-every", requires = "export-out")]
+    export_every: Option<Interval>,
+
+    /// Directory the scheduled export and the admin page's export jobs write to
+    #[structopt(long = "export-out", parse(from_os_str))]
+    export_out: Option<PathBuf>,
+
+    /// Fast-forward the Git checkout being served this often, e.g. 5m
+    #[structopt(long = "git-pull-interval")]
+    git_pull_interval: Option<Interval>,
+
+    /// Path to a TOML configuration file
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
     /// Path to a directory containing the SVG files to be served
     #[structopt(parse(from_os_str))]
     path: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Report sizes, missing titles, unused fonts and duplicates across a directory tree
+    Report(ReportOpt),
+    /// Write every page as static HTML, next to a copy of its SVG
+    Export(ExportOpt),
+    /// Check the SVGs across a directory tree, exiting with status 1 on problems
+    Validate(ValidateOpt),
+    /// Email the [digest] recipients what changed since the last digest, right away
+    Digest(DigestOpt),
+    /// Replace colors across a directory tree, printing the changes unless --write is given
+    Recolor(RecolorOpt),
+    /// List the font families used across a directory tree and the pages that would fall
+    /// back for lack of one, exiting with status 1 if any would
+    Fonts(FontsOpt),
+}
+
+#[derive(Debug, StructOpt)]
+struct FontsOpt {
+    /// Output format
+    #[structopt(short = "f", long = "format", default_value = "text", possible_values = &["text", "json"])]
+    format: ValidateFormat,
+
+    /// File to write the report to [default: stdout]
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Path to a TOML configuration file, for the [raster] font directory
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Path to a directory containing the SVG files to check
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct RecolorOpt {
+    /// Color to replace and its replacement, e.g. old=#112233:new=#445566; repeat for more
+    #[structopt(short = "m", long = "map", required = true, number_of_values = 1)]
+    map: Vec<ColorMapping>,
+
+    /// Write the recolored files instead of printing a diff
+    #[structopt(long = "write")]
+    write: bool,
+
+    /// Path to a directory containing the SVG files to recolor
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct DigestOpt {
+    /// Print the email instead of sending it, leaving the next digest to cover the same changes
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Path to a TOML configuration file
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Path to a directory containing the SVG files being served
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+struct ValidateOpt {
+    /// Check that every href inside the SVGs resolves: ids, pages and http(s) URLs
+    #[structopt(long = "links")]
+    links: bool,
+
+    /// Output format
+    #[structopt(short = "f", long = "format", default_value = "text", possible_values = &["text", "json"])]
+    format: ValidateFormat,
+
+    /// File to write the report to [default: stdout]
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// Path to a TOML configuration file
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Path to a directory containing the SVG files to validate
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ValidateFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ValidateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ValidateFormat::Text),
+            "json" => Ok(ValidateFormat::Json),
+            _ => Err(format!("Unknown validate format '{s}'")),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+struct ExportOpt {
+    /// Directory to write the exported pages to
+    #[structopt(
+        short = "o",
+        long = "output",
+        default_value = "public",
+        parse(from_os_str)
+    )]
+    output: PathBuf,
+
+    /// Output format: html pages with their SVGs, confluence storage-format pages with
+    /// PNG attachments, or standalone html pages holding their SVGs
+    #[structopt(short = "f", long = "format", default_value = "html", possible_values = &["html", "confluence", "standalone"])]
+    format: ExportFormat,
+
+    /// Path to a TOML configuration file
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Upload the pages this run wrote, and delete the ones it removed, at s3://bucket/prefix
+    #[cfg(feature = "s3")]
+    #[structopt(long = "publish")]
+    publish: Option<svg_server::publish::S3Target>,
+
+    /// Upload every exported page, not just the ones this run changed
+    #[cfg(feature = "s3")]
+    #[structopt(long = "publish-all", requires = "publish")]
+    publish_all: bool,
+
+    /// Cache-Control header of uploaded files
+    #[cfg(feature = "s3")]
+    #[structopt(long = "cache-control", requires = "publish", default_value = svg_server::publish::DEFAULT_CACHE_CONTROL)]
+    cache_control: String,
+
+    /// CloudFront distribution to invalidate the published paths on
+    #[cfg(feature = "s3")]
+    #[structopt(long = "invalidate", requires = "publish")]
+    invalidate: Option<String>,
+
+    /// Path to a directory containing the SVG files to export
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone)]
-struct SvgPath(PathBuf);
+/// Time between scheduled runs, a number followed by s, m, h or d
+#[derive(Debug, Clone, Copy)]
+struct Interval(Duration);
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid interval '{s}', expected e.g. 90s, 30m, 1h or 1d");
+        let unit = s.chars().last().ok_or_else(invalid)?;
+        let count: u64 = s[..s.len() - unit.len_utf8()]
+            .parse()
+            .map_err(|_| invalid())?;
+        let unit_secs = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        let secs = count.checked_mul(unit_secs).ok_or_else(invalid)?;
+        if secs == 0 {
+            return Err(invalid());
+        }
+        Ok(Interval(Duration::from_secs(secs)))
+    }
+}
 
-#[derive(Debug, Clone)]
-struct RedirectIndexTo(String);
+#[derive(Debug, StructOpt)]
+struct ReportOpt {
+    /// Output format
+    #[structopt(short = "f", long = "format", default_value = "html", possible_values = &["html", "json"])]
+    format: ReportFormat,
 
-#[derive(RustEmbed)]
-#[folder = "templates"]
-struct Assets;
+    /// File to write the report to [default: stdout]
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
 
-static HEIGHT_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r#"height\s*=\s*"[^"]*""#).unwrap());
+    /// Number of largest files to list
+    #[structopt(short = "n", long = "largest", default_value = "10")]
+    largest: usize,
 
-static WIDTH_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"width\s*=\s*"[^"]*""#).unwrap());
+    /// Path to a directory containing the SVG files to report on
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+}
 
-fn svg_size_full_width(svg_content: &str) -> Result<String, String> {
-    let svg_start = svg_content
-        .find("<svg")
-        .ok_or("No SVG start found".to_owned())?;
-    let svg_end = svg_content[svg_start..]
-        .find('>')
-        .ok_or("No SVG end found".to_owned())?;
+#[derive(Debug, Clone, Copy)]
+enum ReportFormat {
+    Html,
+    Json,
+}
 
-    // Get the full SVG tag line
-    let svg_tag_line = &svg_content[svg_start..svg_start + svg_end + 1];
-    // Remove height attribute
-    let new_svg_tag_line = HEIGHT_RE.replace_all(svg_tag_line, "").to_string();
+impl FromStr for ReportFormat {
+    type Err = String;
 
-    // Replace width attribute with 100%
-    let new_svg_tag_line = WIDTH_RE
-        .replace_all(&new_svg_tag_line, "width=\"100%\"")
-        .to_string();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(ReportFormat::Html),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("Unknown report format '{s}'")),
+        }
+    }
+}
 
-    Ok(svg_content.replace(svg_tag_line, &new_svg_tag_line))
+fn load_config(path: Option<&Path>) -> Result<Config, String> {
+    match path {
+        Some(path) => Config::load(path),
+        None => Ok(Config::default()),
+    }
 }
 
-#[get("/")]
-async fn home_redirect(redirect_to: web::Data<RedirectIndexTo>) -> impl Responder {
-    // Permanent redirect to /home
-    println!("Redirecting / to {}", redirect_to.0);
-    web::redirect("/", redirect_to.0.to_owned()).temporary()
+fn print_export_summary(summary: &ExportSummary) {
+    println!(
+        "Exported {} new and {} changed pages ({} unchanged, {} removed)",
+        summary.added.len(),
+        summary.changed.len(),
+        summary.unchanged,
+        summary.removed.len()
+    );
+    for error in &summary.errors {
+        eprintln!("Failed to export '{}': {}", error.page, error.error);
+    }
 }
 
-#[get("/{page}")]
-async fn render_svg(
-    page: web::Path<String>,
-    template_engine: web::Data<Handlebars<'_>>,
-    opt: web::Data<SvgPath>,
-) -> impl Responder {
-    let page = page.into_inner().to_lowercase().replace(':', "/");
-    let svg_path = format!("{}.svg", page.as_str());
-    let full_svg_path = opt.0.join(svg_path);
-    println!("Loading SVG at: {}", full_svg_path.display());
+async fn export(opt: ExportOpt) -> std::io::Result<()> {
+    let root = opt.path.unwrap_or_else(|| PathBuf::from("."));
+    if !root.exists() {
+        eprintln!("Error: SVG folder '{}' does not exist", root.display());
+        return Ok(());
+    }
+    let mut settings = Settings::new(root);
+    settings.config = match load_config(opt.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return Ok(());
+        }
+    };
 
-    // Read SVG file contents
-    let svg_content = match std::fs::read_to_string(&full_svg_path) {
-        Ok(content) => match svg_size_full_width(&content) {
-            Ok(content) => content,
+    let summary = Exporter::with_format(&settings, &opt.output, opt.format).run();
+    print_export_summary(&summary);
+
+    #[cfg(feature = "s3")]
+    if let Some(target) = opt.publish {
+        let mut publisher = match svg_server::publish::Publisher::from_env(target.clone()) {
+            Ok(publisher) => publisher.cache_control(opt.cache_control),
             Err(e) => {
-                eprintln!("{e}");
-                return HttpResponse::InternalServerError().body(e);
+                eprintln!("Error: {e}");
+                return Ok(());
             }
-        },
+        };
+        if let Some(distribution) = opt.invalidate {
+            publisher = publisher.invalidate(distribution);
+        }
+
+        let published = if opt.publish_all {
+            publisher.publish_all(&opt.output).await
+        } else {
+            publisher.publish_changes(&opt.output, &summary).await
+        };
+        println!(
+            "Published to {target}: {} uploaded, {} deleted",
+            published.uploaded, published.deleted
+        );
+        if let Some(id) = &published.invalidation {
+            println!("Created CloudFront invalidation {id}");
+        }
+        for error in &published.errors {
+            eprintln!("Failed to publish '{}': {}", error.page, error.error);
+        }
+    }
+    Ok(())
+}
+
+async fn validate(opt: ValidateOpt) -> std::io::Result<()> {
+    let root = opt.path.unwrap_or_else(|| PathBuf::from("."));
+    if !root.exists() {
+        eprintln!("Error: SVG folder '{}' does not exist", root.display());
+        return Ok(());
+    }
+    if !opt.links {
+        eprintln!("Error: nothing to validate, pass --links");
+        return Ok(());
+    }
+    let config = match load_config(opt.config.as_deref()) {
+        Ok(config) => config,
         Err(e) => {
-            eprintln!("{e}");
-            return HttpResponse::InternalServerError().body("Failed to load SVG");
+            eprintln!("Error: {e}");
+            return Ok(());
         }
     };
 
-    // Prepare template data
-    let data = serde_json::json!({
-        "title": page,
-        "svg_content": svg_content
-    });
-
-    // Render template
-    match template_engine.render("layout", &data) {
-        Ok(rendered) => HttpResponse::Ok()
-            .content_type("text/html; charset=utf-8")
-            .body(rendered),
+    let report = LinkReport::collect(&root, &config.routes).await;
+    let rendered = match opt.format {
+        ValidateFormat::Text => report.to_text(),
+        ValidateFormat::Json => serde_json::to_string_pretty(&report)?,
+    };
+    match opt.output {
+        Some(path) => std::fs::write(path, rendered)?,
+        None => println!("{rendered}"),
+    }
+
+    if !report.is_clean() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn print_digest_outcome(outcome: &Result<Option<Digest>, String>) {
+    match outcome {
+        Ok(Some(digest)) if digest.is_empty() => println!("Nothing changed, no digest sent"),
+        Ok(Some(digest)) => println!("Sent digest: {}", digest.subject()),
+        Ok(None) => println!("Recorded the pages the first digest will be compared with"),
+        Err(e) => eprintln!("Digest failed: {e}"),
+    }
+}
+
+fn digest(opt: DigestOpt) -> std::io::Result<()> {
+    let root = opt.path.unwrap_or_else(|| PathBuf::from("."));
+    if !root.exists() {
+        eprintln!("Error: SVG folder '{}' does not exist", root.display());
+        return Ok(());
+    }
+    let digester =
+        match load_config(opt.config.as_deref()).and_then(|config| Digester::new(&root, &config)) {
+            Ok(digester) => digester,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return Ok(());
+            }
+        };
+
+    let now = SystemTime::now();
+    if !opt.dry_run {
+        print_digest_outcome(&digester.run(now));
+        return Ok(());
+    }
+    match digester.collect(now) {
+        Ok(Some(digest)) => println!("{}", digester.preview(&digest, now)),
+        Ok(None) => println!("No digest yet to compare with, the first run records the pages"),
+        Err(e) => eprintln!("Error: {e}"),
+    }
+    Ok(())
+}
+
+fn report(opt: ReportOpt) -> std::io::Result<()> {
+    let root = opt.path.unwrap_or_else(|| PathBuf::from("."));
+    if !root.exists() {
+        eprintln!("Error: SVG folder '{}' does not exist", root.display());
+        return Ok(());
+    }
+
+    let report = Report::collect(&root, opt.largest);
+    let rendered = match opt.format {
+        ReportFormat::Html => report.to_html().map_err(std::io::Error::other)?,
+        ReportFormat::Json => serde_json::to_string_pretty(&report)?,
+    };
+
+    match opt.output {
+        Some(path) => std::fs::write(path, rendered),
+        None => {
+            println!("{rendered}");
+            Ok(())
+        }
+    }
+}
+
+fn fonts(opt: FontsOpt) -> std::io::Result<()> {
+    let root = opt.path.unwrap_or_else(|| PathBuf::from("."));
+    if !root.exists() {
+        eprintln!("Error: SVG folder '{}' does not exist", root.display());
+        return Ok(());
+    }
+    let config = match load_config(opt.config.as_deref()) {
+        Ok(config) => config,
         Err(e) => {
-            eprintln!("{e}");
-            HttpResponse::InternalServerError().body("Template rendering error")
+            eprintln!("Error: {e}");
+            return Ok(());
         }
+    };
+    if let Some(dir) = config.raster.fonts.as_ref().filter(|dir| !dir.is_dir()) {
+        eprintln!("Error: font directory '{}' does not exist", dir.display());
+        return Ok(());
     }
+
+    let report = FontReport::collect(&root, &config.raster);
+    let rendered = match opt.format {
+        ValidateFormat::Text => report.to_text(),
+        ValidateFormat::Json => serde_json::to_string_pretty(&report)?,
+    };
+    match opt.output {
+        Some(path) => std::fs::write(path, rendered)?,
+        None => println!("{rendered}"),
+    }
+
+    if !report.is_clean() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn recolor(opt: RecolorOpt) -> std::io::Result<()> {
+    let root = opt.path.unwrap_or_else(|| PathBuf::from("."));
+    if !root.exists() {
+        eprintln!("Error: SVG folder '{}' does not exist", root.display());
+        return Ok(());
+    }
+
+    let recoloring = Recoloring::collect(&root, &opt.map);
+    for error in &recoloring.errors {
+        eprintln!("Failed to read '{}': {}", error.page, error.error);
+    }
+    let summary = format!(
+        "{} colors replaced in {} of {} files",
+        recoloring.replaced(),
+        recoloring.changed.len(),
+        recoloring.files
+    );
+    if !opt.write {
+        print!("{}", recoloring.to_diff());
+        println!("{summary} (dry run, pass --write to apply)");
+        return Ok(());
+    }
+
+    let errors = recoloring.write();
+    for error in &errors {
+        eprintln!("Failed to write '{}': {}", error.page, error.error);
+    }
+    println!("{summary}");
+    if !errors.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    println!("{USAGE_GUIDE}\n\n");
-
     // Parse command line arguments
     let opt = Opt::from_args();
 
+    match opt.cmd {
+        Some(Command::Report(report_opt)) => return report(report_opt),
+        Some(Command::Export(export_opt)) => return export(export_opt).await,
+        Some(Command::Validate(validate_opt)) => return validate(validate_opt).await,
+        Some(Command::Digest(digest_opt)) => return digest(digest_opt),
+        Some(Command::Recolor(recolor_opt)) => return recolor(recolor_opt),
+        Some(Command::Fonts(fonts_opt)) => return fonts(fonts_opt),
+        None => {}
+    }
+
+    println!("{USAGE_GUIDE}\n\n");
+
     // Create socket address from bind address and port
     let addr = format!("{}:{}", opt.bind, opt.port);
     let socket_addr = addr.parse::<SocketAddr>().expect("Invalid address");
 
     // Get SVG folder path (use current directory if none provided)
-    let svg_folder = match opt.path {
-        Some(path) => SvgPath(path),
-        None => SvgPath(PathBuf::from(".")),
-    };
+    let svg_folder = opt.path.unwrap_or_else(|| PathBuf::from("."));
 
     // Verify SVG folder exists
-    if !svg_folder.0.exists() {
+    if !svg_folder.exists() {
         eprintln!(
             "Error: SVG folder '{}' does not exist",
-            svg_folder.0.display()
+            svg_folder.display()
         );
         return Ok(());
     }
 
-    // Initialize Handlebars
-    let mut hb = Handlebars::new();
+    // Load configuration (use defaults if none provided)
+    let config = match load_config(opt.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return Ok(());
+        }
+    };
 
-    // Register templates from files
-    hb.register_embed_templates_with_extension::<Assets>(".hbs")
-        .unwrap();
+    // With somewhere to keep it, the index survives restarts and only changes are re-read
+    let site_index = match &config.sidecar.dir {
+        Some(dir) => Arc::new(SiteIndex::cached(dir.join("index.json"), &svg_folder)),
+        None => Default::default(),
+    };
+    let settings = Settings {
+        root: svg_folder,
+        index: opt.index,
+        listing: !opt.no_listing,
+        config,
+        authenticator: None,
+        view_stats: Default::default(),
+        site_index,
+        git_sync: Default::default(),
+        jobs: Default::default(),
+        export_out: opt.export_out,
+    };
+
+    // Warn about pages that built-in routes make unreachable at /{page}
+    for collision in svg_server::reserved_collisions(&settings) {
+        let escape_hatch = match &settings.config.routes.page {
+            Some(prefix) => format!("use /{prefix}/{} instead", collision.page),
+            None => "enable the page route to reach it".to_owned(),
+        };
+        eprintln!(
+            "Warning: '{}' is shadowed by the /{} route, {escape_hatch}",
+            collision.path.display(),
+            collision.page.to_lowercase(),
+        );
+    }
+
+    if let Some(target) = &opt.mirror_to {
+        if !target.starts_with("http://") {
+            eprintln!("Error: mirror target '{target}' must be an http:// URL");
+            return Ok(());
+        }
+        if !(0.0..=1.0).contains(&opt.mirror_sample) {
+            eprintln!("Error: mirror sample must be between 0 and 1");
+            return Ok(());
+        }
+        println!(
+            "Mirroring {}% of GET requests to {target}",
+            opt.mirror_sample * 100.0
+        );
+    }
+    let mirror_to = opt.mirror_to;
+    let mirror_sample = opt.mirror_sample;
+
+    // Keep the static export in sync from a thread of its own, file I/O blocks
+    if let (Some(Interval(every)), Some(out)) = (opt.export_every, &settings.export_out) {
+        let mut exporter = Exporter::new(&settings, out);
+        println!(
+            "Exporting changed pages to '{}' every {every:?}",
+            out.display()
+        );
+        std::thread::spawn(move || {
+            loop {
+                let summary = exporter.run();
+                if !summary.is_empty() || !summary.errors.is_empty() {
+                    print_export_summary(&summary);
+                }
+                std::thread::sleep(every);
+            }
+        });
+    }
+
+    // Index ahead of the first page view rather than during it
+    if settings.listing {
+        let (root, index) = (settings.root.clone(), Arc::clone(&settings.site_index));
+        std::thread::spawn(move || index.refresh(&root));
+    }
+
+    // Pulls block on the network and on git, so they get a thread of their own too
+    if let Some(Interval(every)) = opt.git_pull_interval {
+        let (root, sync, index) = (
+            settings.root.clone(),
+            Arc::clone(&settings.git_sync),
+            Arc::clone(&settings.site_index),
+        );
+        println!("Pulling '{}' every {every:?}", root.display());
+        std::thread::spawn(move || {
+            loop {
+                match sync.pull(&root, SyncTrigger::Schedule) {
+                    Ok(pull) if pull.updated() => {
+                        println!(
+                            "Pulled '{}' from {} to {}",
+                            root.display(),
+                            pull.before,
+                            pull.head
+                        );
+                        index.invalidate();
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Scheduled pull failed: {e}"),
+                }
+                std::thread::sleep(every);
+            }
+        });
+    }
+
+    // Digests read every file and talk to the mail server, on a thread of their own
+    if !settings.config.digest.to.is_empty() {
+        let digester = match Digester::new(&settings.root, &settings.config) {
+            Ok(digester) => digester,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return Ok(());
+            }
+        };
+        println!(
+            "Emailing a {} digest to {}",
+            settings.config.digest.every,
+            settings.config.digest.to.join(", ")
+        );
+        std::thread::spawn(move || {
+            loop {
+                match digester.due(SystemTime::now()) {
+                    Ok(true) => print_digest_outcome(&digester.run(SystemTime::now())),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Digest failed: {e}"),
+                }
+                std::thread::sleep(DIGEST_CHECK_INTERVAL);
+            }
+        });
+    }
+
+    let keep_alive = match opt.keep_alive {
+        0 => KeepAlive::Disabled,
+        secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+    };
 
     println!("Server started at http://{socket_addr}");
-    HttpServer::new(move || {
+    let mut server = HttpServer::new(move || {
+        let mirror = mirror_to
+            .as_deref()
+            .map(|target| Mirror::new(target, mirror_sample));
+
         App::new()
-            .app_data(web::Data::new(hb.clone()))
-            .app_data(web::Data::new(svg_folder.clone()))
-            .app_data(web::Data::new(RedirectIndexTo(opt.index.to_owned())))
-            .service(home_redirect)
-            .service(render_svg)
+            .wrap(Compress::default())
+            // Outermost, so the mirror is compared on the bytes clients receive
+            .wrap(Condition::new(mirror.is_some(), mirror.unwrap_or_default()))
+            .service(svg_server::service(settings.clone()))
     })
-    .bind(socket_addr)?
-    .run()
-    .await
+    .keep_alive(keep_alive);
+
+    if let Some(workers) = opt.workers {
+        server = server.workers(workers.get());
+    }
+
+    server
+        .listen(listener(socket_addr, opt.reuse_port)?)?
+        .run()
+        .await
+}
+
+/// Socket to serve on, shared with other servers passing `reuse_port` on Unix: the
+/// kernel spreads new connections between them, so a new binary can start before the
+/// old one is stopped
+fn listener(addr: SocketAddr, reuse_port: bool) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    // Like actix-web's own binding, so restarts don't wait out TIME_WAIT
+    socket.set_reuse_address(true)?;
+    if reuse_port {
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        #[cfg(not(unix))]
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "--reuse-port is only supported on Unix",
+        ));
+    }
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intervals_take_a_unit() {
+        let secs = |s: &str| s.parse::<Interval>().map(|Interval(every)| every.as_secs());
+        assert_eq!(secs("90s"), Ok(90));
+        assert_eq!(secs("30m"), Ok(1800));
+        assert_eq!(secs("1d"), Ok(86_400));
+        for invalid in ["", "0h", "5", "1w", "999999999999999d"] {
+            assert!(secs(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn a_server_needs_a_worker() {
+        let workers = |count: &str| {
+            Opt::from_iter_safe(["svg-server", "--workers", count]).map(|opt| opt.workers)
+        };
+        assert_eq!(workers("2").unwrap(), NonZeroUsize::new(2));
+        assert!(workers("0").is_err());
+    }
 }
@@ -0,0 +1,209 @@
+//! Mirror a sample of GET requests to a secondary instance and log where it answers
+//! differently, to validate an upgrade or template change before switching over.
+
+use std::{
+    future::{Future, Ready, ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{self, EitherBody, MessageBody},
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    error::ErrorInternalServerError,
+    http::{
+        Method, StatusCode,
+        header::{self, HeaderMap},
+    },
+    web::Bytes,
+};
+use sha2::{Digest, Sha256};
+
+use crate::trace::{TRACEPARENT, TRACESTATE, TraceContext};
+
+/// Largest secondary response body read for comparison
+const MAX_MIRROR_BODY: usize = 64 * 1024 * 1024;
+
+/// Request headers that describe the connection rather than the request
+const HOP_BY_HOP: [header::HeaderName; 4] = [
+    header::HOST,
+    header::CONNECTION,
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+];
+
+/// Middleware replaying a sample of GET requests against a secondary instance.
+///
+/// Sampled responses are buffered so their bodies can be hashed; the secondary is
+/// queried in the background once the primary response is ready, and never delays it.
+/// Streamed responses, such as job events and archives, pass through unmirrored.
+/// Register it outermost so both instances are compared on the bytes they send.
+#[derive(Debug, Clone, Default)]
+pub struct Mirror {
+    target: Rc<str>,
+    sample: f64,
+}
+
+impl Mirror {
+    /// Mirror a `sample` fraction (0 to 1) of GET requests to the server at `target`,
+    /// e.g. `http://127.0.0.1:5001`
+    pub fn new(target: &str, sample: f64) -> Self {
+        Mirror {
+            target: target.trim_end_matches('/').into(),
+            sample,
+        }
+    }
+
+    fn sampled(&self, req: &ServiceRequest) -> bool {
+        req.method() == Method::GET && self.sample > 0.0 && rand::random::<f64>() < self.sample
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Mirror
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, Bytes>>;
+    type Error = Error;
+    type Transform = MirrorMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MirrorMiddleware {
+            service: Rc::new(service),
+            mirror: self.clone(),
+        }))
+    }
+}
+
+pub struct MirrorMiddleware<S> {
+    service: Rc<S>,
+    mirror: Mirror,
+}
+
+impl<S, B> Service<ServiceRequest> for MirrorMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B, Bytes>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        if !self.mirror.sampled(&req) {
+            return Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) });
+        }
+
+        let path = req
+            .uri()
+            .path_and_query()
+            .map_or_else(|| "/".to_owned(), |pq| pq.to_string());
+        let url = format!("{}{path}", self.mirror.target);
+        let mut headers = req.headers().clone();
+        for name in HOP_BY_HOP.into_iter().chain([TRACEPARENT, TRACESTATE]) {
+            headers.remove(name);
+        }
+        // The replay is a call this server makes, a child of its own span
+        let trace = TraceContext::of(req.request());
+        for (name, value) in trace.outbound() {
+            headers.insert(name, value);
+        }
+
+        Box::pin(async move {
+            let res = service.call(req).await?;
+            if streaming(res.response()) {
+                return Ok(res.map_into_left_body());
+            }
+            let status = res.status();
+
+            let (req, res) = res.into_parts();
+            let (res, body) = res.into_parts();
+            let body = body::to_bytes(body)
+                .await
+                .map_err(|e| ErrorInternalServerError(e.into().to_string()))?;
+
+            let primary = Observed::new(status, &body);
+            actix_web::rt::spawn(compare(path, url, headers, primary, trace.trace_id));
+
+            Ok(ServiceResponse::new(req, res.set_body(body)).map_into_right_body())
+        })
+    }
+}
+
+/// Whether `res` is sent as it is produced, which buffering it would hold back.
+///
+/// Told by its content type rather than its body size: compressed bodies have no size
+/// known up front either, yet are mirrored like any other.
+fn streaming<B>(res: &HttpResponse<B>) -> bool {
+    let content_type = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    content_type.starts_with("text/event-stream") || content_type.starts_with("application/gzip")
+}
+
+/// What an instance answered, reduced to what is compared
+#[derive(Debug, PartialEq)]
+struct Observed {
+    status: StatusCode,
+    hash: String,
+}
+
+impl Observed {
+    fn new(status: StatusCode, body: &Bytes) -> Self {
+        let digest = Sha256::digest(body);
+        let hash = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+        Observed { status, hash }
+    }
+}
+
+/// Replay a request against the secondary and log any difference from the primary
+async fn compare(
+    path: String,
+    url: String,
+    headers: HeaderMap,
+    primary: Observed,
+    trace_id: String,
+) {
+    let mut request = awc::Client::default().get(&url);
+    for (name, value) in &headers {
+        request = request.append_header((name.clone(), value.clone()));
+    }
+
+    let mut response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("Mirror: failed to reach {url}: {e}");
+            return;
+        }
+    };
+    let body = match response.body().limit(MAX_MIRROR_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("Mirror: failed to read response from {url}: {e}");
+            return;
+        }
+    };
+
+    let secondary = Observed::new(response.status(), &body);
+    if secondary.status != primary.status {
+        println!(
+            "Mirror: GET {path} returned {} here but {} on the mirror (trace {trace_id})",
+            primary.status, secondary.status
+        );
+    } else if secondary.hash != primary.hash {
+        println!(
+            "Mirror: GET {path} content differs, {} here but {} on the mirror (trace {trace_id})",
+            primary.hash, secondary.hash
+        );
+    }
+}
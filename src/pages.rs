@@ -0,0 +1,149 @@
+use std::path::{Path, PathBuf};
+
+/// Normalize a page name (`:` separates directories), rejecting names that would
+/// escape the served directory
+pub(crate) fn normalize_page(page: &str) -> Result<String, String> {
+    let page = page.to_lowercase().replace(':', "/");
+    let valid = page.split('/').all(|segment| {
+        !segment.is_empty() && segment != "." && segment != ".." && !segment.contains(['\\', '\0'])
+    });
+
+    if valid {
+        Ok(page)
+    } else {
+        Err(format!("Invalid page name '{page}'"))
+    }
+}
+
+/// Resolve a page name to the SVG file it is served from
+pub(crate) fn page_svg_path(root: &Path, page: &str) -> Result<(String, PathBuf), String> {
+    let page = normalize_page(page)?;
+    let svg_path = root.join(format!("{page}.svg"));
+    Ok((page, svg_path))
+}
+
+/// Page name a file under `root` is served as, the inverse of [`page_svg_path`]
+pub(crate) fn page_name(root: &Path, svg_path: &Path) -> Option<String> {
+    let relative = svg_path.strip_prefix(root).ok()?.with_extension("");
+    let segments = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(segments.join(":"))
+}
+
+/// Percent-encode everything in a page name but unreserved characters and `/`
+pub(crate) fn percent_encode(page: &str) -> String {
+    page.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Every SVG file under `root`, paired with its page name and sorted by it
+pub(crate) fn svg_files(root: &Path) -> Vec<(String, PathBuf)> {
+    let mut files: Vec<_> = walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "svg"))
+        .filter_map(|entry| Some((page_name(root, entry.path())?, entry.into_path())))
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Component;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn colons_separate_directories() {
+        assert_eq!(normalize_page("Icons:Arrow").unwrap(), "icons/arrow");
+        let (page, path) = page_svg_path(Path::new("/srv"), "a:b").unwrap();
+        assert_eq!(page, "a/b");
+        assert_eq!(path, Path::new("/srv/a/b.svg"));
+    }
+
+    #[test]
+    fn page_names_use_colons() {
+        let name = page_name(Path::new("/srv"), Path::new("/srv/icons/arrow.svg"));
+        assert_eq!(name.as_deref(), Some("icons:arrow"));
+        assert_eq!(
+            page_name(Path::new("/srv"), Path::new("/other/a.svg")),
+            None
+        );
+    }
+
+    #[test]
+    fn traversal_is_rejected() {
+        for page in [
+            "..",
+            "..:etc:passwd",
+            "a:..:..:b",
+            ":etc",
+            "a::b",
+            "a\\..\\b",
+            ".",
+        ] {
+            assert!(normalize_page(page).is_err(), "{page} should be rejected");
+        }
+    }
+
+    /// Fragments that have been used to escape a served directory somewhere
+    fn path_fragment() -> impl Strategy<Value = String> {
+        prop_oneof![
+            // Separators and dot segments, including Windows and fullwidth lookalikes
+            prop::sample::select(vec![
+                "/", ":", "\\", ".", "..", "...", "\0", "．", "／", "＼", "‥", "︰",
+            ])
+            .prop_map(str::to_owned),
+            // Percent-encoded forms, in case something decodes the name once more
+            prop::sample::select(vec![
+                "%2e", "%2E", "%2f", "%2F", "%5c", "%3a", "%00", "%252e", "%c0%ae",
+            ])
+            .prop_map(str::to_owned),
+            // Letters whose case mapping or normalization changes their length
+            prop::sample::select(vec!["a", "Z", "İ", "ß", "ﬀ", "e\u{301}", "Å", "K"])
+                .prop_map(str::to_owned),
+            "\\PC{1,3}",
+        ]
+    }
+
+    /// Page names assembled from [`path_fragment`]s
+    fn page_name_strategy() -> impl Strategy<Value = String> {
+        prop::collection::vec(path_fragment(), 0..10).prop_map(|fragments| fragments.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn resolved_pages_stay_inside_the_root(page in page_name_strategy()) {
+            let root = Path::new("/srv/svg");
+            if let Ok((_, path)) = page_svg_path(root, &page) {
+                let relative = path.strip_prefix(root).unwrap();
+                prop_assert!(
+                    relative.components().all(|c| matches!(c, Component::Normal(_))),
+                    "{page:?} resolved to {}",
+                    path.display()
+                );
+            }
+        }
+
+        #[test]
+        fn resolved_pages_round_trip_to_their_name(page in page_name_strategy()) {
+            let root = Path::new("/srv/svg");
+            if let Ok((normalized, path)) = page_svg_path(root, &page) {
+                let name = page_name(root, &path).unwrap();
+                prop_assert_eq!(name.replace(':', "/"), normalized);
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::LazyLock;
+
+use actix_web::{
+    HttpRequest,
+    cookie::{Cookie, CookieJar, Key, SameSite, time::Duration},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{Background, Fit, SessionConfig, ViewDefaults},
+    contrast::Contrast,
+    simulate::Simulation,
+};
+
+const COOKIE_NAME: &str = "svg_server_preferences";
+
+/// Signs cookies when no secret is configured, shared by every worker of the process
+static EPHEMERAL_KEY: LazyLock<Key> = LazyLock::new(Key::generate);
+
+/// Key that preference cookies are signed with
+#[derive(Clone)]
+pub(crate) struct SessionKey(Key);
+
+impl SessionKey {
+    pub(crate) fn new(config: &SessionConfig) -> Self {
+        // SessionConfig only holds secrets long enough to derive a key from
+        match config.secret() {
+            Some(secret) => SessionKey(Key::derive_from(secret.as_bytes())),
+            None => SessionKey(EPHEMERAL_KEY.clone()),
+        }
+    }
+
+    /// `value` signed as the cookie `name`, readable by anyone but not forgeable
+    pub(crate) fn sign(&self, name: &'static str, value: String) -> String {
+        let mut jar = CookieJar::new();
+        jar.signed_mut(&self.0).add(Cookie::new(name, value));
+        jar.get(name).unwrap().value().to_owned()
+    }
+
+    /// The value `signed` was made from by [`SessionKey::sign`], unless tampered with
+    pub(crate) fn verify(&self, name: &'static str, signed: &str) -> Option<String> {
+        let mut jar = CookieJar::new();
+        jar.add_original(Cookie::new(name, signed.to_owned()));
+        jar.signed(&self.0)
+            .get(name)
+            .map(|cookie| cookie.value().to_owned())
+    }
+}
+
+/// Options applied when rendering a page, set per request through the query string or
+/// pinned per visitor in a signed cookie
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub(crate) struct ViewOptions {
+    /// Color-vision deficiency to simulate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) simulate: Option<Simulation>,
+    /// Contrast adjustment to apply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) contrast: Option<Contrast>,
+    /// What shows behind the diagram, white if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) background: Option<Background>,
+    /// How large the diagram is drawn, full width if unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) fit: Option<Fit>,
+}
+
+impl ViewOptions {
+    /// Fill options missing from `self` with `defaults`
+    pub(crate) fn or(self, defaults: ViewOptions) -> ViewOptions {
+        ViewOptions {
+            simulate: self.simulate.or(defaults.simulate),
+            contrast: self.contrast.or(defaults.contrast),
+            background: self.background.or(defaults.background),
+            fit: self.fit.or(defaults.fit),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.simulate.is_none()
+            && self.contrast.is_none()
+            && self.background.is_none()
+            && self.fit.is_none()
+    }
+
+    /// Preferences pinned by the visitor, ignoring missing or tampered cookies
+    pub(crate) fn pinned(req: &HttpRequest, key: &SessionKey) -> ViewOptions {
+        let Some(cookie) = req.cookie(COOKIE_NAME) else {
+            return ViewOptions::default();
+        };
+
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+        jar.signed(&key.0)
+            .get(COOKIE_NAME)
+            .and_then(|cookie| serde_urlencoded::from_str(cookie.value()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Signed cookie pinning these preferences, or removing them when there are none
+    pub(crate) fn cookie(&self, key: &SessionKey) -> Cookie<'static> {
+        let mut cookie = Cookie::build(COOKIE_NAME, "")
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .finish();
+
+        if self.is_empty() {
+            cookie.make_removal();
+            return cookie;
+        }
+
+        // Stored like a query string, which only uses characters valid in cookie values
+        cookie.set_value(serde_urlencoded::to_string(self).unwrap_or_default());
+        cookie.set_max_age(Duration::days(365));
+
+        let mut jar = CookieJar::new();
+        jar.signed_mut(&key.0).add(cookie);
+        jar.get(COOKIE_NAME).cloned().unwrap()
+    }
+}
+
+impl From<&ViewDefaults> for ViewOptions {
+    fn from(defaults: &ViewDefaults) -> Self {
+        ViewOptions {
+            simulate: defaults.simulate,
+            contrast: defaults.contrast,
+            background: defaults.background,
+            fit: defaults.fit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn pinned_preferences_round_trip() {
+        let key = SessionKey(Key::generate());
+        let prefs = ViewOptions {
+            simulate: Some(Simulation::Protanopia),
+            fit: Some(Fit::Fixed),
+            ..ViewOptions::default()
+        };
+        let cookie = prefs.cookie(&key);
+
+        let req = TestRequest::default()
+            .cookie(cookie.clone())
+            .to_http_request();
+        let pinned = ViewOptions::pinned(&req, &key);
+        assert!(matches!(pinned.simulate, Some(Simulation::Protanopia)));
+        assert!(pinned.contrast.is_none());
+        assert_eq!(pinned.fit, Some(Fit::Fixed));
+
+        // A cookie signed with another key is ignored
+        let req = TestRequest::default().cookie(cookie).to_http_request();
+        let other = SessionKey(Key::generate());
+        assert!(ViewOptions::pinned(&req, &other).is_empty());
+    }
+
+    #[test]
+    fn query_options_override_pinned_ones() {
+        let query = ViewOptions {
+            contrast: Some(Contrast::High),
+            ..ViewOptions::default()
+        };
+        let pinned = ViewOptions {
+            simulate: Some(Simulation::Tritanopia),
+            ..ViewOptions::default()
+        };
+        let view = query.or(pinned);
+        assert!(matches!(view.simulate, Some(Simulation::Tritanopia)));
+        assert!(matches!(view.contrast, Some(Contrast::High)));
+    }
+}
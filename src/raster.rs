@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use resvg::{tiny_skia, usvg};
+
+use crate::config::RasterConfig;
+
+/// Largest width or height of a rasterized image in pixels
+pub(crate) const MAX_DIMENSION: f32 = 8192.0;
+
+pub(crate) const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Chunks needed to draw an image, everything else being metadata
+const KEPT_CHUNKS: [&[u8]; 5] = [b"IHDR", b"PLTE", b"tRNS", b"IDAT", b"IEND"];
+
+/// Fonts installed on the host along with those of a font directory, loaded once per
+/// directory for every rasterization
+static FONTS: LazyLock<Mutex<HashMap<Option<PathBuf>, Arc<usvg::fontdb::Database>>>> =
+    LazyLock::new(Mutex::default);
+
+/// A rasterized SVG
+pub(crate) struct Raster {
+    pub(crate) png: Vec<u8>,
+    /// Size of the SVG in CSS pixels, before scaling
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+/// Fonts text is drawn with: the host's, plus those in `dir`
+fn fonts(dir: Option<&Path>) -> Arc<usvg::fontdb::Database> {
+    let mut loaded = FONTS.lock().unwrap();
+    let fonts = loaded.entry(dir.map(Path::to_owned)).or_insert_with(|| {
+        let mut fonts = usvg::fontdb::Database::new();
+        fonts.load_system_fonts();
+        if let Some(dir) = dir {
+            fonts.load_fonts_dir(dir);
+        }
+        Arc::new(fonts)
+    });
+    Arc::clone(fonts)
+}
+
+/// Lowercased names of the families text can be drawn with, see [`fonts`]
+pub(crate) fn font_families(dir: Option<&Path>) -> BTreeSet<String> {
+    fonts(dir)
+        .faces()
+        .flat_map(|face| face.families.iter().map(|(name, _)| name.to_lowercase()))
+        .collect()
+}
+
+/// Parse an SVG for rendering with the fonts of `config`.
+///
+/// Only images embedded as data URLs are drawn; references to other files are
+/// skipped, so a document can't pull arbitrary files from the host into the output.
+fn svg_tree(svg_content: &str, config: &RasterConfig) -> Result<usvg::Tree, String> {
+    let options = usvg::Options {
+        fontdb: fonts(config.fonts.as_deref()),
+        image_href_resolver: usvg::ImageHrefResolver {
+            resolve_data: usvg::ImageHrefResolver::default_data_resolver(),
+            resolve_string: Box::new(|_, _| None),
+        },
+        ..usvg::Options::default()
+    };
+    usvg::Tree::from_str(svg_content, &options).map_err(|e| format!("Invalid SVG: {e}"))
+}
+
+/// Render an SVG to PNG at `scale` times its own size with the fonts of `config`,
+/// carrying no metadata besides what it sets
+pub(crate) fn svg_to_png(
+    svg_content: &str,
+    scale: f32,
+    config: &RasterConfig,
+) -> Result<Raster, String> {
+    let tree = svg_tree(svg_content, config)?;
+    let size = tree.size();
+    let scale = scale.min(MAX_DIMENSION / size.width().max(size.height()));
+    let mut pixmap = tiny_skia::Pixmap::new(
+        (size.width() * scale).ceil() as u32,
+        (size.height() * scale).ceil() as u32,
+    )
+    .ok_or("SVG has an empty size".to_owned())?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    let png = pixmap.encode_png().map_err(|e| e.to_string())?;
+    Ok(Raster {
+        png: stamped_png(&png, config)?,
+        width: size.width().ceil() as u32,
+        height: size.height().ceil() as u32,
+    })
+}
+
+/// Render an SVG stretched to exactly `width` by `height` pixels, to compare it with an
+/// image of that size
+pub(crate) fn svg_to_pixmap(
+    svg_content: &str,
+    width: u32,
+    height: u32,
+    config: &RasterConfig,
+) -> Result<tiny_skia::Pixmap, String> {
+    let tree = svg_tree(svg_content, config)?;
+    let size = tree.size();
+    let mut pixmap =
+        tiny_skia::Pixmap::new(width, height).ok_or("Image has an empty size".to_owned())?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(
+            width as f32 / size.width(),
+            height as f32 / size.height(),
+        ),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
+
+/// `png` with only the chunks needed to draw it, plus `metadata` as text chunks
+fn stamped_png(png: &[u8], metadata: &RasterConfig) -> Result<Vec<u8>, String> {
+    let mut rest = png
+        .strip_prefix(PNG_SIGNATURE)
+        .ok_or("Not a PNG".to_owned())?;
+    let mut stamped = PNG_SIGNATURE.to_vec();
+    // Each chunk is its length, type, data and CRC
+    while let [a, b, c, d, after_len @ ..] = rest {
+        let len = u32::from_be_bytes([*a, *b, *c, *d]) as usize;
+        if after_len.len() < len + 8 {
+            return Err("Truncated PNG chunk".to_owned());
+        }
+        let kind = &after_len[..4];
+        if KEPT_CHUNKS.contains(&kind) {
+            stamped.extend_from_slice(&rest[..len + 12]);
+        }
+        // Text goes right after the header, ahead of the image data
+        if kind == b"IHDR" {
+            for (keyword, text) in [
+                ("Copyright", &metadata.copyright),
+                ("Author", &metadata.author),
+            ] {
+                if let Some(text) = text {
+                    push_text_chunk(&mut stamped, keyword, text);
+                }
+            }
+        }
+        rest = &after_len[len + 8..];
+    }
+    Ok(stamped)
+}
+
+/// Append an uncompressed international text (`iTXt`) chunk, which holds UTF-8 unlike
+/// `tEXt`
+fn push_text_chunk(png: &mut Vec<u8>, keyword: &str, text: &str) {
+    let mut chunk = b"iTXt".to_vec();
+    chunk.extend_from_slice(keyword.as_bytes());
+    // Keyword terminator, no compression, then empty language and translated keyword
+    chunk.extend_from_slice(&[0, 0, 0, 0, 0]);
+    chunk.extend_from_slice(text.as_bytes());
+
+    let mut crc = flate2::Crc::new();
+    crc.update(&chunk);
+    png.extend_from_slice(&(chunk.len() as u32 - 4).to_be_bytes());
+    png.extend_from_slice(&chunk);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svgs_rasterize_at_scale() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" width="30" height="20"><rect width="30" height="20" fill="#336699"/><image href="/etc/hostname" width="5" height="5"/></svg>"##;
+        let raster = svg_to_png(svg, 2.0, &RasterConfig::default()).unwrap();
+        assert_eq!((raster.width, raster.height), (30, 20));
+
+        let pixmap = tiny_skia::Pixmap::decode_png(&raster.png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (60, 40));
+        let pixel = pixmap.pixel(1, 1).unwrap();
+        assert_eq!(
+            (pixel.red(), pixel.green(), pixel.blue()),
+            (0x33, 0x66, 0x99)
+        );
+
+        assert!(svg_to_png("<html/>", 1.0, &RasterConfig::default()).is_err());
+        assert_eq!(chunk_kinds(&raster.png), ["IHDR", "IDAT", "IEND"]);
+    }
+
+    fn chunk_kinds(png: &[u8]) -> Vec<String> {
+        let mut kinds = Vec::new();
+        let mut rest = &png[PNG_SIGNATURE.len()..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            kinds.push(String::from_utf8_lossy(&rest[4..8]).into_owned());
+            rest = &rest[len + 12..];
+        }
+        kinds
+    }
+
+    #[test]
+    fn metadata_is_scrubbed_unless_configured() {
+        // An encoder's own text and timestamp chunks never reach the export
+        let mut png = PNG_SIGNATURE.to_vec();
+        let pixmap = tiny_skia::Pixmap::new(1, 1).unwrap().encode_png().unwrap();
+        let mut encoded = &pixmap[PNG_SIGNATURE.len()..];
+        while !encoded.is_empty() {
+            let len = u32::from_be_bytes(encoded[..4].try_into().unwrap()) as usize;
+            png.extend_from_slice(&encoded[..len + 12]);
+            if &encoded[4..8] == b"IHDR" {
+                push_text_chunk(&mut png, "Software", "Secret Editor 1.0");
+            }
+            encoded = &encoded[len + 12..];
+        }
+        let scrubbed = stamped_png(&png, &RasterConfig::default()).unwrap();
+        assert_eq!(chunk_kinds(&scrubbed), ["IHDR", "IDAT", "IEND"]);
+
+        let metadata = RasterConfig {
+            copyright: Some("© 2026 Acme Corp".to_owned()),
+            author: Some("Platform Team".to_owned()),
+            ..RasterConfig::default()
+        };
+        let stamped = stamped_png(&png, &metadata).unwrap();
+        assert_eq!(
+            chunk_kinds(&stamped),
+            ["IHDR", "iTXt", "iTXt", "IDAT", "IEND"]
+        );
+        let text = String::from_utf8_lossy(&stamped);
+        assert!(text.contains("Copyright\0\0\0\0\0© 2026 Acme Corp"));
+        assert!(!text.contains("Secret Editor"));
+        // Decoders check every chunk's CRC
+        assert!(tiny_skia::Pixmap::decode_png(&stamped).is_ok());
+    }
+}
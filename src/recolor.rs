@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+
+pub use svg_server_core::recolor::{ColorMapping, svg_recolor};
+
+use crate::{pages::svg_files, report::FileError};
+
+/// A color mapping applied across every SVG under a directory
+#[derive(Debug, Default)]
+pub struct Recoloring {
+    /// Number of SVG files read
+    pub files: usize,
+    /// Pages with at least one color replaced
+    pub changed: Vec<RecoloredPage>,
+    /// Pages that could not be read
+    pub errors: Vec<FileError>,
+}
+
+#[derive(Debug)]
+pub struct RecoloredPage {
+    pub page: String,
+    pub path: PathBuf,
+    /// Number of color values replaced
+    pub replaced: usize,
+    pub before: String,
+    pub after: String,
+}
+
+impl Recoloring {
+    /// Recolor every SVG under `root` in memory, leaving the files alone
+    pub fn collect(root: &Path, mappings: &[ColorMapping]) -> Self {
+        let mut recoloring = Recoloring::default();
+        for (page, path) in svg_files(root) {
+            let before = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    recoloring.errors.push(FileError {
+                        page,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            recoloring.files += 1;
+
+            let (after, replaced) = svg_recolor(&before, mappings);
+            if after != before {
+                recoloring.changed.push(RecoloredPage {
+                    page,
+                    path,
+                    replaced,
+                    before,
+                    after,
+                });
+            }
+        }
+        recoloring
+    }
+
+    /// Number of color values replaced across every page
+    pub fn replaced(&self) -> usize {
+        self.changed.iter().map(|page| page.replaced).sum()
+    }
+
+    /// Write the recolored pages back, each through a temporary file renamed over the
+    /// original so a failure never leaves half a drawing behind
+    pub fn write(&self) -> Vec<FileError> {
+        let mut errors = Vec::new();
+        for page in &self.changed {
+            let partial = page.path.with_extension("svg.tmp");
+            let written = std::fs::write(&partial, &page.after)
+                .and_then(|()| std::fs::rename(&partial, &page.path));
+            if let Err(e) = written {
+                let _ = std::fs::remove_file(&partial);
+                errors.push(FileError {
+                    page: page.page.clone(),
+                    error: e.to_string(),
+                });
+            }
+        }
+        errors
+    }
+
+    /// The changes as a unified diff, one file after the other
+    pub fn to_diff(&self) -> String {
+        let mut diff = String::new();
+        for page in &self.changed {
+            let path = page.path.display();
+            diff.push_str(&format!("--- {path}\n+++ {path}\n"));
+            diff.push_str(&line_diff(&page.before, &page.after));
+        }
+        diff
+    }
+}
+
+/// Hunks of the lines that differ, without context lines. Replacing colors never adds
+/// or removes lines, so line `n` of `before` is always line `n` of `after`.
+fn line_diff(before: &str, after: &str) -> String {
+    let lines: Vec<_> = before.lines().zip(after.lines()).collect();
+    let mut diff = String::new();
+    let mut start = 0;
+    while start < lines.len() {
+        if lines[start].0 == lines[start].1 {
+            start += 1;
+            continue;
+        }
+        let end = (start..lines.len())
+            .find(|&i| lines[i].0 == lines[i].1)
+            .unwrap_or(lines.len());
+        let count = end - start;
+        diff.push_str(&format!(
+            "@@ -{first},{count} +{first},{count} @@\n",
+            first = start + 1
+        ));
+        for (old, _) in &lines[start..end] {
+            diff.push_str(&format!("-{old}\n"));
+        }
+        for (_, new) in &lines[start..end] {
+            diff.push_str(&format!("+{new}\n"));
+        }
+        start = end;
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_are_replaced_across_the_tree() {
+        let root = std::env::temp_dir().join(format!("svg-server-recolor-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("brand")).unwrap();
+        let logo = root.join("brand/logo.svg");
+        std::fs::write(
+            &logo,
+            "<svg>\n<rect fill=\"#112233\"/>\n<rect fill=\"#112233\"/>\n<circle/>\n<path stroke=\"#112233\"/>\n</svg>\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("home.svg"), "<svg><rect fill=\"#ffffff\"/></svg>").unwrap();
+
+        let mappings = ["old=#112233:new=#445566".parse().unwrap()];
+        let recoloring = Recoloring::collect(&root, &mappings);
+        assert_eq!(recoloring.files, 2);
+        assert_eq!(recoloring.replaced(), 3);
+        assert_eq!(
+            recoloring.to_diff(),
+            format!(
+                "--- {path}\n+++ {path}\n\
+                 @@ -2,2 +2,2 @@\n\
+                 -<rect fill=\"#112233\"/>\n-<rect fill=\"#112233\"/>\n\
+                 +<rect fill=\"#445566\"/>\n+<rect fill=\"#445566\"/>\n\
+                 @@ -5,1 +5,1 @@\n\
+                 -<path stroke=\"#112233\"/>\n+<path stroke=\"#445566\"/>\n",
+                path = logo.display()
+            )
+        );
+        // Nothing is written until asked for
+        assert!(std::fs::read_to_string(&logo).unwrap().contains("#112233"));
+
+        assert!(recoloring.write().is_empty());
+        assert!(!std::fs::read_to_string(&logo).unwrap().contains("#112233"));
+        assert!(!root.join("brand/logo.svg.tmp").exists());
+        assert_eq!(Recoloring::collect(&root, &mappings).replaced(), 0);
+    }
+}
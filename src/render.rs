@@ -0,0 +1,259 @@
+use std::{path::PathBuf, sync::Arc};
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason,
+    ScopedJson,
+};
+
+use crate::{
+    comments::{Comment, MAX_COMMENT_CHARS},
+    config::{Background, Config, Fit},
+    contrast::{Contrast, svg_high_contrast},
+    index::SiteIndex,
+    links::{LinkTarget, link_target, svg_link_page, svg_rewrite_links},
+    outline::{OutlineItem, svg_outline},
+    pages::normalize_page,
+    preferences::ViewOptions,
+    resize::{svg_size_fixed, svg_size_full_width},
+    review::Review,
+    simulate::svg_simulate,
+};
+
+/// Web font stylesheet linked from every page
+pub(crate) const FONT_STYLESHEET: &str = "https://fonts.googleapis.com/css2?family=Open+Sans:ital,wght@0,300..800;1,300..800&display=swap";
+
+/// What is known about a page besides its SVG
+#[derive(Debug, Default)]
+pub(crate) struct PageInfo {
+    /// Pages referring to it, `/` separated
+    pub(crate) referenced_by: Vec<String>,
+    /// Days since its file was modified, for the out-of-date badge
+    pub(crate) age_days: Option<u64>,
+    /// Comments left on it, `None` when comments are turned off
+    pub(crate) comments: Option<Vec<Comment>>,
+    /// Its latest review status, for the banner above the diagram
+    pub(crate) review: Option<Review>,
+    /// Who is viewing it, if signed in and so allowed to comment
+    pub(crate) user: Option<String>,
+    /// Where the "Copy SVG" button fetches its standalone copy, `None` without a server
+    /// to ask
+    pub(crate) copy_url: Option<String>,
+    /// Whether the page has to show as a file of its own, without web fonts
+    pub(crate) standalone: bool,
+}
+
+/// Render a page's text as a plain HTML outline, with links to other pages pointed at
+/// `page_link` of their page name and one back to the drawing at `diagram_url`
+pub(crate) fn render_text(
+    template_engine: &Handlebars<'_>,
+    page: &str,
+    svg_content: &str,
+    page_link: &dyn Fn(&str) -> String,
+    diagram_url: &str,
+) -> Result<String, String> {
+    let mut outline = svg_outline(svg_content).inspect_err(|e| eprintln!("{e}"))?;
+    link_items(&mut outline.items, page, page_link);
+
+    let data = serde_json::json!({
+        "title": outline.title.unwrap_or_else(|| page.replace('/', ":")),
+        "description": outline.description,
+        "items": outline.items,
+        "diagram_url": diagram_url,
+    });
+    template_engine.render("text", &data).map_err(|e| {
+        eprintln!("{e}");
+        "Template rendering error".to_owned()
+    })
+}
+
+/// Point the links of `items` at the pages they lead to, dropping those that can't be
+/// followed from an outline, such as links into the drawing itself
+fn link_items(items: &mut [OutlineItem], page: &str, page_link: &dyn Fn(&str) -> String) {
+    for item in items {
+        item.href = item.href.take().and_then(|href| {
+            let target = svg_link_page(page, &href).or_else(|| match link_target(&href) {
+                LinkTarget::Page(name) => normalize_page(name).ok(),
+                _ => None,
+            });
+            match (target, link_target(&href)) {
+                (Some(target), _) => Some(page_link(&target)),
+                (None, LinkTarget::External(_)) => Some(href),
+                (None, _) => None,
+            }
+        });
+        link_items(&mut item.items, page, page_link);
+    }
+}
+
+/// `site_tree` helper, handing templates the site's directories and pages as a
+/// [`SiteTree`](crate::index::SiteTree) to build their own navigation from, without
+/// fetching anything: `{{#with (site_tree)}}`, or `(site_tree "network")` for one
+/// directory. The tree is only built when a template asks for it, then kept until files
+/// change; it is `null` when listing is disabled, or for a directory that doesn't exist.
+pub(crate) struct SiteTreeHelper {
+    pub(crate) root: PathBuf,
+    pub(crate) index: Arc<SiteIndex>,
+    pub(crate) listing: bool,
+}
+
+impl HelperDef for SiteTreeHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        if !self.listing {
+            return Ok(ScopedJson::Derived(serde_json::Value::Null));
+        }
+        let dir = match h.param(0) {
+            Some(param) => param
+                .value()
+                .as_str()
+                .ok_or(RenderErrorReason::InvalidParamType("directory path string"))?,
+            None => "",
+        };
+        let tree = self.index.tree(&self.root);
+        let value = tree
+            .find(dir)
+            .map_or(serde_json::Value::Null, |dir| serde_json::json!(dir));
+        Ok(ScopedJson::Derived(value))
+    }
+}
+
+/// Render a page's SVG source into the HTML layout, with `view` applied and relative
+/// links to other SVGs pointed at `page_link` of their page name, listing the pages
+/// referring to it below
+pub(crate) fn render_page(
+    template_engine: &Handlebars<'_>,
+    config: &Config,
+    page: &str,
+    svg_content: &str,
+    view: &ViewOptions,
+    page_link: &dyn Fn(&str) -> String,
+    info: &PageInfo,
+) -> Result<String, String> {
+    // The visitor's options win over the defaults of the page's directory
+    let defaults = config.view.for_page(page).map(ViewOptions::from);
+    let view = view.clone().or(defaults.unwrap_or_default());
+
+    let svg_content = svg_rewrite_links(svg_content, page, page_link);
+    let svg_content = match view.fit {
+        // Sized by the viewBox, if any, else left to the browser like an <img>
+        Some(Fit::Fixed) => svg_size_fixed(&svg_content, None).unwrap_or(svg_content),
+        Some(Fit::FullWidth) | None => {
+            svg_size_full_width(&svg_content).inspect_err(|e| eprintln!("{e}"))?
+        }
+    };
+
+    // Apply the requested contrast adjustment
+    let svg_content = match view.contrast {
+        Some(Contrast::High) => svg_high_contrast(&svg_content, &config.contrast),
+        None => svg_content,
+    };
+
+    // Apply the requested color-vision simulation
+    let svg_content = match view.simulate {
+        Some(simulation) => {
+            svg_simulate(&svg_content, simulation).inspect_err(|e| eprintln!("{e}"))?
+        }
+        None => svg_content,
+    };
+
+    let referenced_by: Vec<_> = info
+        .referenced_by
+        .iter()
+        .map(|page| serde_json::json!({ "name": page.replace('/', ":"), "href": page_link(page) }))
+        .collect();
+
+    let stale = info
+        .age_days
+        .filter(|days| config.stale.is_stale(page, *days))
+        .map(|days| serde_json::json!({ "days": days }));
+
+    let comments = info.comments.as_ref().map(|comments| {
+        serde_json::json!({
+            "items": comments,
+            "user": info.user,
+            "max_chars": MAX_COMMENT_CHARS,
+        })
+    });
+
+    let review = info.review.as_ref().map(|review| {
+        serde_json::json!({
+            "status": review.status,
+            "label": review.status.label(),
+            "changed_by": review.changed_by,
+            "changed": review.changed,
+            "date": review.changed.get(..10),
+        })
+    });
+
+    let license = (!config.license.is_empty()).then(|| {
+        serde_json::json!({
+            "name": config.license.name,
+            "url": config.license.url,
+            "attribution": config.license.attribution.iter().map(|credit| {
+                serde_json::json!({ "name": credit.name, "url": credit.url })
+            }).collect::<Vec<_>>(),
+        })
+    });
+
+    // Prepare template data
+    let data = serde_json::json!({
+        "title": page,
+        "font_stylesheet": FONT_STYLESHEET,
+        "edit_url": config.edit.url(page),
+        "copy_url": info.copy_url,
+        "standalone": info.standalone,
+        "stale": stale,
+        "review": review,
+        "referenced_by": referenced_by,
+        "comments": comments,
+        "license": license,
+        "checkerboard": view.background == Some(Background::Checkerboard),
+        "svg_content": svg_content
+    });
+
+    // Render template
+    template_engine.render("layout", &data).map_err(|e| {
+        eprintln!("{e}");
+        "Template rendering error".to_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_walk_the_site_tree() {
+        let root = std::env::temp_dir().join(format!("svg-server-tree-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("network/edge")).unwrap();
+        for page in ["home", "network/db", "network/edge/router"] {
+            std::fs::write(root.join(format!("{page}.svg")), "<svg/>").unwrap();
+        }
+        let mut hb = Handlebars::new();
+        let template = concat!(
+            "{{#with (site_tree)}}{{#each pages}}{{page}} {{/each}}{{/with}}",
+            "{{#with (site_tree \"Network\")}}{{path}}: {{#each pages}}{{page}} {{/each}}",
+            "{{#each dirs}}{{name}}/{{#each pages}}{{name}}{{/each}}{{/each}}{{/with}}",
+            "{{#unless (site_tree \"storage\")}} none{{/unless}}",
+        );
+        hb.register_template_string("nav", template).unwrap();
+        let helper = |listing| SiteTreeHelper {
+            root: root.clone(),
+            index: Arc::default(),
+            listing,
+        };
+
+        hb.register_helper("site_tree", Box::new(helper(true)));
+        assert_eq!(
+            hb.render("nav", &()).unwrap(),
+            "home network: network:db edge/router none"
+        );
+        hb.register_helper("site_tree", Box::new(helper(false)));
+        assert_eq!(hb.render("nav", &()).unwrap(), " none");
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::LazyLock,
+};
+
+use quick_xml::{Reader, events::Event};
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{config::RasterConfig, pages::svg_files, raster::font_families};
+
+static FONT_FACE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?is)@font-face\s*\{([^}]*)\}").unwrap());
+
+static FONT_FAMILY_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)font-family\s*:\s*([^;}]+)").unwrap());
+
+/// CSS generic families, which always resolve to some installed font
+const GENERIC_FAMILIES: [&str; 11] = [
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "ui-serif",
+    "ui-sans-serif",
+    "ui-monospace",
+    "ui-rounded",
+    "math",
+];
+
+/// Health check across every SVG under a directory
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Number of SVG files found
+    pub files: usize,
+    /// Combined size of every SVG file in bytes
+    pub total_size_bytes: u64,
+    /// The biggest files, largest first
+    pub largest: Vec<FileSize>,
+    /// Pages whose root has no non-empty `<title>`
+    pub missing_titles: Vec<String>,
+    /// Fonts declared in a page that none of its text uses
+    pub unused_fonts: Vec<UnusedFonts>,
+    /// Groups of pages with byte-identical content
+    pub duplicates: Vec<Vec<String>>,
+    /// Pages that could not be read or parsed
+    pub errors: Vec<FileError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileSize {
+    pub page: String,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnusedFonts {
+    pub page: String,
+    pub families: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileError {
+    pub page: String,
+    pub error: String,
+}
+
+impl Report {
+    /// Scan `root` recursively, keeping the `largest` biggest files
+    pub fn collect(root: &Path, largest: usize) -> Self {
+        let mut report = Report::default();
+        let mut sizes = Vec::new();
+        let mut by_hash: BTreeMap<[u8; 32], Vec<String>> = BTreeMap::new();
+
+        for (page, path) in svg_files(root) {
+            let content = match std::fs::read(&path) {
+                Ok(content) => content,
+                Err(e) => {
+                    report.errors.push(FileError {
+                        page,
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            report.files += 1;
+            report.total_size_bytes += content.len() as u64;
+            sizes.push(FileSize {
+                page: page.clone(),
+                size_bytes: content.len() as u64,
+            });
+            by_hash
+                .entry(Sha256::digest(&content).into())
+                .or_default()
+                .push(page.clone());
+
+            let document = match std::str::from_utf8(&content)
+                .map_err(|e| e.to_string())
+                .and_then(DocumentFacts::collect)
+            {
+                Ok(document) => document,
+                Err(error) => {
+                    report.errors.push(FileError { page, error });
+                    continue;
+                }
+            };
+
+            if !document.has_title {
+                report.missing_titles.push(page.clone());
+            }
+
+            let unused: Vec<_> = document
+                .declared_fonts
+                .difference(&document.used_fonts)
+                .cloned()
+                .collect();
+            if !unused.is_empty() {
+                report.unused_fonts.push(UnusedFonts {
+                    page,
+                    families: unused,
+                });
+            }
+        }
+
+        sizes.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then(a.page.cmp(&b.page)));
+        sizes.truncate(largest);
+        report.largest = sizes;
+
+        report.duplicates = by_hash
+            .into_values()
+            .filter(|pages| pages.len() > 1)
+            .collect();
+        report.duplicates.sort();
+
+        report
+    }
+
+    /// Render the report as a standalone HTML page
+    pub fn to_html(&self) -> Result<String, String> {
+        crate::template_engine()
+            .render("report", self)
+            .map_err(|e| format!("Template rendering error: {e}"))
+    }
+}
+
+/// Fonts referenced across every SVG under a directory, checked against the fonts
+/// rasterized exports are drawn with
+#[derive(Debug, Default, Serialize)]
+pub struct FontReport {
+    /// Number of SVG files checked
+    pub files: usize,
+    /// Every family referenced, by name
+    pub families: Vec<FontUsage>,
+    /// Pages asking first for a family that isn't available, so their text is drawn
+    /// with whatever comes next
+    pub fallbacks: Vec<FontFallback>,
+    /// Pages that could not be read or parsed
+    pub errors: Vec<FileError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FontUsage {
+    pub family: String,
+    /// Whether it is installed or in the `[raster] fonts` directory; generic families
+    /// such as `sans-serif` always are
+    pub available: bool,
+    pub pages: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FontFallback {
+    pub page: String,
+    pub families: Vec<String>,
+}
+
+impl FontReport {
+    /// Scan `root` recursively, checking families against the host's fonts and the
+    /// `fonts` directory of `config`
+    pub fn collect(root: &Path, config: &RasterConfig) -> Self {
+        Self::with_fonts(root, &font_families(config.fonts.as_deref()))
+    }
+
+    fn with_fonts(root: &Path, available: &BTreeSet<String>) -> Self {
+        let mut report = FontReport::default();
+        let mut families: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let is_available =
+            |family: &str| GENERIC_FAMILIES.contains(&family) || available.contains(family);
+
+        for (page, path) in svg_files(root) {
+            let document = match std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| DocumentFacts::collect(&content))
+            {
+                Ok(document) => document,
+                Err(error) => {
+                    report.errors.push(FileError { page, error });
+                    continue;
+                }
+            };
+            report.files += 1;
+
+            for family in &document.used_fonts {
+                families
+                    .entry(family.clone())
+                    .or_default()
+                    .push(page.clone());
+            }
+            let missing: Vec<_> = document
+                .primary_fonts
+                .into_iter()
+                .filter(|family| !is_available(family))
+                .collect();
+            if !missing.is_empty() {
+                report.fallbacks.push(FontFallback {
+                    page,
+                    families: missing,
+                });
+            }
+        }
+
+        report.families = families
+            .into_iter()
+            .map(|(family, pages)| FontUsage {
+                available: is_available(&family),
+                family,
+                pages,
+            })
+            .collect();
+        report
+    }
+
+    /// Whether every page gets the fonts it asks for first
+    pub fn is_clean(&self) -> bool {
+        self.fallbacks.is_empty() && self.errors.is_empty()
+    }
+
+    /// Families with their availability and page count, then the pages falling back
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Found {} font families in {} files, {} pages fall back\n\n",
+            self.families.len(),
+            self.files,
+            self.fallbacks.len()
+        );
+        for usage in &self.families {
+            text.push_str(&format!(
+                "  {:<9}  {}  ({} pages)\n",
+                if usage.available {
+                    "available"
+                } else {
+                    "missing"
+                },
+                usage.family,
+                usage.pages.len()
+            ));
+        }
+        for fallback in &self.fallbacks {
+            text.push_str(&format!(
+                "\n{}\n  falls back from {}\n",
+                fallback.page,
+                fallback.families.join(", ")
+            ));
+        }
+        for error in &self.errors {
+            text.push_str(&format!(
+                "\nFailed to check '{}': {}\n",
+                error.page, error.error
+            ));
+        }
+        text
+    }
+}
+
+/// What a single document declares and uses
+#[derive(Debug, Default)]
+struct DocumentFacts {
+    has_title: bool,
+    declared_fonts: BTreeSet<String>,
+    used_fonts: BTreeSet<String>,
+    /// The first family of each `font-family`, the one the author means to see
+    primary_fonts: BTreeSet<String>,
+}
+
+impl DocumentFacts {
+    fn collect(svg_content: &str) -> Result<Self, String> {
+        let mut facts = DocumentFacts::default();
+        let mut reader = Reader::from_str(svg_content);
+        let mut depth = 0usize;
+        let mut in_root_title = false;
+        let mut in_style = false;
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| format!("Invalid SVG: {e}"))?;
+
+            match &event {
+                Event::Start(element) | Event::Empty(element) => {
+                    let name = element.local_name();
+                    for attr in element.attributes().flatten() {
+                        let value = attr.unescape_value().unwrap_or_default();
+                        match attr.key.local_name().as_ref() {
+                            // SVG fonts declare their family on <font-face>
+                            b"font-family" if name.as_ref() == b"font-face" => {
+                                facts.declared_fonts.extend(font_family(&value));
+                            }
+                            b"font-family" => facts.use_fonts(&value),
+                            b"style" => facts.collect_css(&value),
+                            _ => {}
+                        }
+                    }
+
+                    if matches!(event, Event::Start(_)) {
+                        depth += 1;
+                        in_root_title = depth == 2 && name.as_ref() == b"title";
+                        in_style = name.as_ref() == b"style";
+                    }
+                }
+                Event::End(_) => {
+                    depth = depth.saturating_sub(1);
+                    in_root_title = false;
+                    in_style = false;
+                }
+                Event::Text(text) if in_root_title => {
+                    let text = text.unescape().unwrap_or_default();
+                    facts.has_title |= !text.trim().is_empty();
+                }
+                Event::Text(text) if in_style => {
+                    facts.collect_css(&text.unescape().unwrap_or_default());
+                }
+                Event::CData(css) if in_style => {
+                    facts.collect_css(&String::from_utf8_lossy(css));
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(facts)
+    }
+
+    /// Record `@font-face` declarations and `font-family` uses in a stylesheet or style attribute
+    fn collect_css(&mut self, css: &str) {
+        for face in FONT_FACE_RE.captures_iter(css) {
+            for family in FONT_FAMILY_RE.captures_iter(&face[1]) {
+                self.declared_fonts.extend(font_family(&family[1]));
+            }
+        }
+
+        let rules = FONT_FACE_RE.replace_all(css, "");
+        for family in FONT_FAMILY_RE.captures_iter(&rules) {
+            self.use_fonts(&family[1]);
+        }
+    }
+
+    fn use_fonts(&mut self, value: &str) {
+        self.primary_fonts.extend(font_family(value).next());
+        self.used_fonts.extend(font_family(value));
+    }
+}
+
+/// Normalized family names from a `font-family` value
+fn font_family(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .map(|family| {
+            family
+                .trim()
+                .trim_matches(['"', '\''])
+                .trim()
+                .to_lowercase()
+        })
+        .filter(|family| !family.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_must_be_root_children() {
+        let facts = DocumentFacts::collect("<svg><title> Map </title></svg>").unwrap();
+        assert!(facts.has_title);
+        let facts = DocumentFacts::collect("<svg><g><title>Box</title></g></svg>").unwrap();
+        assert!(!facts.has_title);
+        let facts = DocumentFacts::collect("<svg><title/></svg>").unwrap();
+        assert!(!facts.has_title);
+    }
+
+    #[test]
+    fn fonts_are_declared_and_used() {
+        let svg = r#"<svg><style>
+            @font-face { font-family: "Brand Sans"; src: url(data:font/woff2;base64,AA); }
+            @font-face { font-family: 'Unused'; src: url(x.woff2); }
+            .label { font-family: "Brand Sans", sans-serif; }
+        </style><text style="font-family: Mono">a</text><text font-family="serif">b</text></svg>"#;
+        let facts = DocumentFacts::collect(svg).unwrap();
+
+        let unused: Vec<_> = facts.declared_fonts.difference(&facts.used_fonts).collect();
+        assert_eq!(unused, ["unused"]);
+        assert!(facts.used_fonts.contains("mono"));
+        assert!(facts.used_fonts.contains("serif"));
+        assert!(facts.used_fonts.contains("sans-serif"));
+        assert!(!facts.primary_fonts.contains("sans-serif"));
+    }
+
+    #[test]
+    fn pages_asking_for_missing_fonts_fall_back() {
+        let root = std::env::temp_dir().join(format!("svg-server-fonts-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("brand")).unwrap();
+        std::fs::write(
+            root.join("brand/logo.svg"),
+            r#"<svg><text font-family="'Brand Sans', Inter, sans-serif">Acme</text></svg>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("home.svg"),
+            r#"<svg><text style="font-family: Inter">Home</text><text font-family="monospace">$</text></svg>"#,
+        )
+        .unwrap();
+
+        let available = BTreeSet::from(["inter".to_owned()]);
+        let report = FontReport::with_fonts(&root, &available);
+        assert_eq!(report.files, 2);
+        let families: Vec<_> = report
+            .families
+            .iter()
+            .map(|usage| (usage.family.as_str(), usage.available, usage.pages.len()))
+            .collect();
+        assert_eq!(
+            families,
+            [
+                ("brand sans", false, 1),
+                ("inter", true, 2),
+                ("monospace", true, 1),
+                ("sans-serif", true, 1)
+            ]
+        );
+        assert_eq!(report.fallbacks.len(), 1);
+        assert_eq!(report.fallbacks[0].page, "brand:logo");
+        assert_eq!(report.fallbacks[0].families, ["brand sans"]);
+        assert!(!report.is_clean());
+        assert!(
+            report
+                .to_text()
+                .contains("  missing    brand sans  (1 pages)")
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    pages::{normalize_page, svg_files},
+    stats::rfc3339,
+};
+
+/// Keeps concurrent status changes from sharing a temporary file
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// Where a page is in its sign-off process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReviewStatus {
+    Draft,
+    InReview,
+    Approved,
+}
+
+impl ReviewStatus {
+    /// How the page banner names the status
+    pub fn label(self) -> &'static str {
+        match self {
+            ReviewStatus::Draft => "Draft",
+            ReviewStatus::InReview => "In review",
+            ReviewStatus::Approved => "Approved",
+        }
+    }
+}
+
+/// A page's latest review status and who set it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Review {
+    pub status: ReviewStatus,
+    /// Name of the user who set the status
+    pub changed_by: String,
+    /// When the status was set, as an RFC 3339 UTC timestamp
+    pub changed: String,
+}
+
+/// Who may move pages between review statuses
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReviewConfig {
+    /// Users who may mark pages as drafts or in review; with none, every signed-in user can
+    pub editors: Vec<String>,
+    /// Users who may approve pages, or take an approval back; with none, no one can
+    pub approvers: Vec<String>,
+}
+
+impl ReviewConfig {
+    /// Whether `user` may move a page from `from` (`None` before its first review) to `to`
+    pub fn may_change(&self, user: &str, from: Option<ReviewStatus>, to: ReviewStatus) -> bool {
+        let approver = self.approvers.iter().any(|name| name == user);
+        if from == Some(ReviewStatus::Approved) || to == ReviewStatus::Approved {
+            return approver;
+        }
+        approver || self.editors.is_empty() || self.editors.iter().any(|name| name == user)
+    }
+}
+
+/// Outcome of [`ReviewStore::transition`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transition {
+    /// The review as stored
+    Changed(Review),
+    /// The check refused the change from this status
+    Refused(Option<ReviewStatus>),
+}
+
+/// Review statuses of every page, one JSON file per page next to its comments
+/// (`network/core` is stored in `network/core.review.json`)
+#[derive(Debug, Clone)]
+pub struct ReviewStore {
+    dir: PathBuf,
+}
+
+impl ReviewStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ReviewStore { dir: dir.into() }
+    }
+
+    fn path(&self, page: &str) -> PathBuf {
+        self.dir.join(format!("{page}.review.json"))
+    }
+
+    /// Latest review of `page` (`/` separated), `None` if it never had one
+    pub fn get(&self, page: &str) -> Result<Option<Review>, String> {
+        let path = self.path(page);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read '{}': {e}", path.display())),
+        };
+        serde_json::from_str(&content)
+            .map(Some)
+            .map_err(|e| format!("Invalid review in '{}': {e}", path.display()))
+    }
+
+    /// Record that `user` moved `page` to `status`, returning the review as stored
+    pub fn set(&self, page: &str, user: &str, status: ReviewStatus) -> Result<Review, String> {
+        let _writing = WRITING.lock().unwrap_or_else(PoisonError::into_inner);
+        self.write(page, user, status)
+    }
+
+    /// Move `page` to `to` for `user` if `check` allows it from the current status, with
+    /// no other change landing in between
+    pub fn transition(
+        &self,
+        page: &str,
+        user: &str,
+        to: ReviewStatus,
+        check: impl FnOnce(Option<ReviewStatus>) -> bool,
+    ) -> Result<Transition, String> {
+        let _writing = WRITING.lock().unwrap_or_else(PoisonError::into_inner);
+        let current = self.get(page)?.map(|review| review.status);
+        if !check(current) {
+            return Ok(Transition::Refused(current));
+        }
+        self.write(page, user, to).map(Transition::Changed)
+    }
+
+    /// Store a new review of `page`, once holding [`WRITING`]
+    fn write(&self, page: &str, user: &str, status: ReviewStatus) -> Result<Review, String> {
+        let review = Review {
+            status,
+            changed_by: user.to_owned(),
+            changed: rfc3339(SystemTime::now()),
+        };
+        let path = self.path(page);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create '{}': {e}", parent.display()))?;
+        }
+
+        // Readers see either the old review or the new one, never half of it
+        let partial = path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(&review).map_err(|e| e.to_string())?;
+        std::fs::write(&partial, json)
+            .and_then(|()| std::fs::rename(&partial, &path))
+            .map_err(|e| format!("Failed to write '{}': {e}", path.display()))?;
+        Ok(review)
+    }
+
+    /// Pages under `root` that have a review, sorted by name, optionally only those
+    /// with `status`
+    pub fn list(
+        &self,
+        root: &Path,
+        status: Option<ReviewStatus>,
+    ) -> Result<Vec<(String, Review)>, String> {
+        let mut reviews = Vec::new();
+        for (name, _) in svg_files(root) {
+            let Ok(page) = normalize_page(&name) else {
+                continue;
+            };
+            match self.get(&page)? {
+                Some(review) if status.is_none_or(|status| review.status == status) => {
+                    reviews.push((page, review));
+                }
+                _ => {}
+            }
+        }
+        reviews.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Ok(reviews)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn approvals_take_an_approver() {
+        let config = ReviewConfig {
+            editors: vec!["alice".to_owned()],
+            approvers: vec!["carol".to_owned()],
+        };
+        assert!(config.may_change("alice", None, ReviewStatus::InReview));
+        assert!(!config.may_change(
+            "alice",
+            Some(ReviewStatus::InReview),
+            ReviewStatus::Approved
+        ));
+        assert!(config.may_change(
+            "carol",
+            Some(ReviewStatus::InReview),
+            ReviewStatus::Approved
+        ));
+        // Reopening an approved page takes the approval back
+        assert!(!config.may_change("alice", Some(ReviewStatus::Approved), ReviewStatus::Draft));
+        assert!(!config.may_change("mallory", None, ReviewStatus::Draft));
+
+        let open = ReviewConfig::default();
+        assert!(open.may_change("mallory", None, ReviewStatus::InReview));
+        assert!(!open.may_change("mallory", None, ReviewStatus::Approved));
+    }
+
+    #[test]
+    fn reviews_are_listed_by_status() {
+        let dir = std::env::temp_dir().join(format!("svg-server-review-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (root, sidecar) = (dir.join("diagrams"), dir.join("sidecar"));
+        std::fs::create_dir_all(root.join("Network")).unwrap();
+        for page in ["home.svg", "Network/core.svg", "network-old.svg"] {
+            std::fs::write(root.join(page), "<svg/>").unwrap();
+        }
+
+        let store = ReviewStore::new(&sidecar);
+        assert_eq!(store.get("home"), Ok(None));
+        store.set("home", "alice", ReviewStatus::InReview).unwrap();
+        let approved = store
+            .set("network/core", "carol", ReviewStatus::Approved)
+            .unwrap();
+        assert_eq!(store.get("network/core"), Ok(Some(approved.clone())));
+        // The check sees the status as it is when the change would be written
+        assert_eq!(
+            store.transition("network/core", "alice", ReviewStatus::Draft, |from| {
+                from != Some(ReviewStatus::Approved)
+            }),
+            Ok(Transition::Refused(Some(ReviewStatus::Approved)))
+        );
+        assert_eq!(store.get("network/core"), Ok(Some(approved.clone())));
+
+        let all = store.list(&root, None).unwrap();
+        let pages: Vec<_> = all.iter().map(|(page, _)| page.as_str()).collect();
+        assert_eq!(pages, ["home", "network/core"]);
+        assert_eq!(
+            store.list(&root, Some(ReviewStatus::Approved)),
+            Ok(vec![("network/core".to_owned(), approved)])
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! `[[rewrite]]` rules mapping request paths onto others before they are routed, e.g.
+//! to keep the URLs of a previous wiki working.
+
+use std::sync::Arc;
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{Uri, header},
+    middleware::Next,
+};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, de::Error as _};
+
+/// One `[[rewrite]]` rule of the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewriteRule {
+    /// Pattern matched against the request path inside the service, starting with `/`,
+    /// e.g. `^/wiki/(.+)\.html$`
+    #[serde(deserialize_with = "pattern")]
+    pub from: Regex,
+    /// Path the request is routed to instead, relative to where the service is mounted,
+    /// with `$1` or `${name}` standing for what the groups of `from` captured; for
+    /// redirects, an http(s) URL will do as well
+    pub to: String,
+    /// Whether to answer with a permanent redirect to `to` rather than serve it in place
+    #[serde(default)]
+    pub redirect: bool,
+}
+
+impl RewriteRule {
+    /// Where the rule sends `path`, if it matches
+    pub fn apply(&self, path: &str) -> Option<String> {
+        let captures = self.from.captures(path)?;
+        let mut to = String::new();
+        captures.expand(&self.to, &mut to);
+        Some(to)
+    }
+}
+
+/// First of `rules` matching `path`, and where it sends it; rewritten paths aren't
+/// matched again, so rules can't loop
+pub fn rewrite<'a>(rules: &'a [RewriteRule], path: &str) -> Option<(&'a RewriteRule, String)> {
+    rules
+        .iter()
+        .find_map(|rule| Some((rule, rule.apply(path)?)))
+}
+
+/// Middleware applying `rules` to requests before they reach the router, redirecting
+/// them or routing them to the rewritten path with their query string
+pub(crate) async fn apply(
+    rules: Arc<[RewriteRule]>,
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    // Relative to where the service is mounted
+    let path = req.match_info().unprocessed();
+    let Some((rule, to)) = rewrite(&rules, path) else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+    let full = req.match_info().as_str();
+    let mount = &full[..full.len() - path.len()];
+
+    let mut target = if to.starts_with('/') {
+        encode_uri(&format!("{mount}{to}"))
+    } else {
+        encode_uri(&to)
+    };
+    // The query string goes along, unless the rule gives one
+    if !req.query_string().is_empty() && !target.contains('?') {
+        target = format!("{target}?{}", req.query_string());
+    }
+
+    if rule.redirect {
+        println!("Redirecting {} to {target}", req.path());
+        let redirect = HttpResponse::PermanentRedirect()
+            .insert_header((header::LOCATION, target))
+            .finish();
+        return Ok(req.into_response(redirect).map_into_right_body());
+    }
+    let uri = match target.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(e) => {
+            eprintln!("Invalid rewrite of {} to {target}: {e}", req.path());
+            let invalid = HttpResponse::InternalServerError().body("Invalid rewrite");
+            return Ok(req.into_response(invalid).map_into_right_body());
+        }
+    };
+    println!("Rewriting {} to {target}", req.path());
+    req.match_info_mut().get_mut().update(&uri);
+    req.head_mut().uri = uri;
+    Ok(next.call(req).await?.map_into_left_body())
+}
+
+/// Percent-encode what can't appear in a URI as is, such as the spaces and non-ASCII
+/// characters of decoded request paths, leaving existing escapes alone
+fn encode_uri(target: &str) -> String {
+    target
+        .bytes()
+        .map(|b| match b {
+            b'!'..=b'~' if !b"\"<>\\^`{|}".contains(&b) => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn pattern<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern)
+        .map_err(|e| D::Error::custom(format!("invalid rewrite pattern '{pattern}': {e}")))
+}
+
+/// Rules in order, internal rewrites checked to stay inside the service
+pub(crate) fn rules<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<RewriteRule>, D::Error> {
+    let rules = Vec::<RewriteRule>::deserialize(deserializer)?;
+    for rule in &rules {
+        let url = rule.to.starts_with("https://") || rule.to.starts_with("http://");
+        let allowed = rule.to.starts_with('/') || (rule.redirect && url);
+        if !allowed {
+            return Err(D::Error::custom(format!(
+                "invalid rewrite target '{}', expected a path starting with /",
+                rule.to
+            )));
+        }
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn the_first_matching_rule_rewrites() {
+        let config = Config::from_toml(
+            r#"
+            [[rewrite]]
+            from = '^/wiki/(?<page>[^/]+)\.html$'
+            to = "/page/${page}"
+
+            [[rewrite]]
+            from = '^/wiki/(.+)$'
+            to = "https://wiki.example.com/$1"
+            redirect = true
+            "#,
+        )
+        .unwrap();
+        let rules = &config.rewrite;
+        let (rule, to) = rewrite(rules, "/wiki/Network Overview.html").unwrap();
+        assert_eq!(
+            (rule.redirect, to.as_str()),
+            (false, "/page/Network Overview")
+        );
+        let (rule, to) = rewrite(rules, "/wiki/a/b.html").unwrap();
+        assert_eq!(
+            (rule.redirect, to.as_str()),
+            (true, "https://wiki.example.com/a/b.html")
+        );
+        assert!(rewrite(rules, "/home").is_none());
+        assert_eq!(
+            encode_uri("/page/Réseau 1%2F2"),
+            "/page/R%C3%A9seau%201%2F2"
+        );
+
+        let invalid = [
+            "from = '(' \nto = '/home'",
+            "from = '^/x$'\nto = 'https://example.com'",
+        ];
+        for rule in invalid {
+            assert!(
+                Config::from_toml(&format!("[[rewrite]]\n{rule}")).is_err(),
+                "{rule}"
+            );
+        }
+    }
+}
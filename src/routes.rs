@@ -0,0 +1,1488 @@
+use std::{
+    convert::Infallible,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    HttpMessage, HttpRequest, HttpResponse, Responder, get,
+    http::{StatusCode, header},
+    web,
+};
+use handlebars::Handlebars;
+use serde::Deserialize;
+
+use crate::{
+    ListingEnabled, RedirectIndexTo, SvgPath,
+    api::{ApiError, listing_only},
+    archive::TarGz,
+    auth::User,
+    comments::{self, CommentStore},
+    compare, compress, conditional,
+    config::Config,
+    git::{self, GitSync, SyncTrigger},
+    guest::{GuestPass, MAX_HOURS},
+    index::{SiteIndex, age_days},
+    jobs::{Job, JobKind, JobRunner, JobState, Jobs},
+    pages::{normalize_page, page_svg_path, percent_encode, svg_files},
+    preferences::{SessionKey, ViewOptions},
+    raster::svg_to_pixmap,
+    render::{FONT_STYLESHEET, PageInfo, render_page, render_text},
+    resize::svg_size_fixed,
+    review::{ReviewStatus, ReviewStore, Transition},
+    search::SearchQuery,
+    sprite::svg_sprite,
+    standalone::svg_standalone,
+    statistics::SvgStatistics,
+    stats::{MAX_LIMIT, StatsQuery, ViewStats},
+};
+
+#[get("/")]
+pub(crate) async fn home_redirect(
+    req: HttpRequest,
+    redirect_to: web::Data<RedirectIndexTo>,
+) -> impl Responder {
+    // Keep absolute index routes inside whatever path the service is mounted under
+    let location = if redirect_to.0.starts_with('/') {
+        format!("{}{}", req.path().trim_end_matches('/'), redirect_to.0)
+    } else {
+        redirect_to.0.to_owned()
+    };
+
+    // Temporary redirect to /home
+    println!("Redirecting {} to {location}", req.path());
+    web::Redirect::to(location).temporary()
+}
+
+pub(crate) async fn render_svg(
+    req: HttpRequest,
+    page: web::Path<String>,
+    view: web::Query<ViewOptions>,
+    template_engine: web::Data<Handlebars<'_>>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+    session_key: web::Data<SessionKey>,
+) -> impl Responder {
+    // Query string options win over the visitor's pinned preferences
+    let view = view
+        .into_inner()
+        .or(ViewOptions::pinned(&req, &session_key));
+
+    let (page, full_svg_path) = match page_svg_path(&opt.0, &page) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    println!("Loading SVG at: {}", full_svg_path.display());
+
+    let svg_content = match std::fs::read_to_string(&full_svg_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("{e}");
+            return HttpResponse::InternalServerError().body("Failed to load SVG");
+        }
+    };
+
+    if let Some(view_stats) = req.app_data::<web::Data<ViewStats>>() {
+        view_stats.record(&page);
+    }
+
+    // Sibling pages live next to this one, under the same scope and route prefix
+    let base = &req.path()[..=req.path().rfind('/').unwrap_or_default()];
+    let page_link = |target: &str| {
+        let link = format!("{base}{}", percent_encode(target).replace('/', ":"));
+        link.replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;")
+            .replace('<', "&lt;")
+    };
+
+    // The copy route sits next to the page route, under the same scope
+    let copy_url = config.routes.copy.as_ref().map(|copy| {
+        let prefixed = config.routes.page.as_ref().and_then(|prefix| {
+            let route = format!("/{prefix}/{{page}}");
+            req.match_pattern()
+                .filter(|pattern| pattern.ends_with(&route))
+                .and(base.strip_suffix(&format!("{prefix}/")))
+        });
+        let scope = prefixed.unwrap_or(base);
+        format!("{scope}{copy}/{}", percent_encode(&page).replace('/', ":"))
+    });
+
+    let comments = match req.app_data::<web::Data<CommentStore>>() {
+        Some(store) => match store.list(&page) {
+            Ok(comments) => Some(comments),
+            Err(e) => {
+                eprintln!("{e}");
+                return HttpResponse::InternalServerError().body("Failed to load comments");
+            }
+        },
+        None => None,
+    };
+
+    let review = match req.app_data::<web::Data<ReviewStore>>() {
+        Some(store) => match store.get(&page) {
+            Ok(review) => review,
+            Err(e) => {
+                eprintln!("{e}");
+                return HttpResponse::InternalServerError().body("Failed to load review");
+            }
+        },
+        None => None,
+    };
+
+    let listing = ListingEnabled::of(&req);
+    let info = PageInfo {
+        referenced_by: match req.app_data::<web::Data<SiteIndex>>() {
+            Some(index) if listing => index.backlinks(&opt.0, &config.routes, &page),
+            _ => Vec::new(),
+        },
+        age_days: std::fs::metadata(&full_svg_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .map(age_days),
+        comments,
+        review,
+        user: req.extensions().get::<User>().map(|user| user.0.clone()),
+        copy_url,
+        standalone: false,
+    };
+    let rendered = match render_page(
+        &template_engine,
+        &config,
+        &page,
+        &svg_content,
+        &view,
+        &page_link,
+        &info,
+    ) {
+        Ok(rendered) => rendered,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    let etag = conditional::etag(rendered.as_bytes());
+    if let Some(not_modified) = conditional::not_modified(&req, &etag) {
+        return not_modified;
+    }
+
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header(header::ETag(etag))
+        .insert_header((header::LINK, preload_links()))
+        .body(rendered)
+}
+
+/// `Link` header letting clients fetch page subresources before parsing the HTML.
+///
+/// The SVG itself is inlined in the page, so there is nothing to preload for it.
+fn preload_links() -> String {
+    format!(
+        "<{FONT_STYLESHEET}>; rel=preload; as=style, \
+         <https://fonts.googleapis.com>; rel=preconnect, \
+         <https://fonts.gstatic.com>; rel=preconnect; crossorigin"
+    )
+}
+
+pub(crate) async fn raw_svg(
+    req: HttpRequest,
+    page: web::Path<String>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let (_, full_svg_path) = match page_svg_path(&opt.0, &page) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    println!("Loading raw SVG at: {}", full_svg_path.display());
+
+    match std::fs::read(&full_svg_path) {
+        Ok(content) => compress::svg_response(&req, config.license.stamp(content)),
+        Err(e) => {
+            eprintln!("{e}");
+            HttpResponse::InternalServerError().body("Failed to load SVG")
+        }
+    }
+}
+
+/// Standalone copy of a page's SVG, with its references inlined and scripts removed, for
+/// the "Copy SVG" button to paste into design tools
+pub(crate) async fn copy_svg(
+    req: HttpRequest,
+    page: web::Path<String>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let (_, full_svg_path) = match page_svg_path(&opt.0, &page) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    println!("Copying SVG at: {}", full_svg_path.display());
+
+    let svg_content = match std::fs::read_to_string(&full_svg_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("{e}");
+            return HttpResponse::InternalServerError().body("Failed to load SVG");
+        }
+    };
+    match svg_standalone(&svg_content) {
+        Ok(copy) => compress::svg_response(&req, config.license.stamp(copy.into_bytes())),
+        Err(e) => {
+            eprintln!("{e}");
+            HttpResponse::InternalServerError().body("Failed to copy SVG")
+        }
+    }
+}
+
+/// Page as a single HTML file holding everything it shows, downloaded to be emailed or
+/// attached to a ticket; its links lead back to this server
+pub(crate) async fn standalone_page(
+    req: HttpRequest,
+    page: web::Path<String>,
+    view: web::Query<ViewOptions>,
+    template_engine: web::Data<Handlebars<'_>>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let (page, full_svg_path) = match page_svg_path(&opt.0, &page) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    println!("Loading standalone SVG at: {}", full_svg_path.display());
+
+    let svg_content = match std::fs::read_to_string(&full_svg_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("{e}");
+            return HttpResponse::InternalServerError().body("Failed to load SVG");
+        }
+    };
+
+    // Opened from anywhere, so links spell out the pages' full URLs on this server
+    let connection = req.connection_info();
+    let route = &req.path()[..=req.path().rfind('/').unwrap_or_default()];
+    let scope = config
+        .routes
+        .standalone
+        .as_ref()
+        .and_then(|standalone| route.strip_suffix(&format!("{standalone}/")))
+        .unwrap_or(route);
+    let origin = format!("{}://{}{scope}", connection.scheme(), connection.host());
+    let page_link = |target: &str| {
+        let link = format!("{origin}{}", percent_encode(target).replace('/', ":"));
+        link.replace('&', "&amp;")
+            .replace('"', "&quot;")
+            .replace('\'', "&apos;")
+            .replace('<', "&lt;")
+    };
+
+    let listing = ListingEnabled::of(&req);
+    let info = PageInfo {
+        referenced_by: match req.app_data::<web::Data<SiteIndex>>() {
+            Some(index) if listing => index.backlinks(&opt.0, &config.routes, &page),
+            _ => Vec::new(),
+        },
+        standalone: true,
+        ..PageInfo::default()
+    };
+    let rendered = match render_page(
+        &template_engine,
+        &config,
+        &page,
+        &svg_content,
+        &view,
+        &page_link,
+        &info,
+    ) {
+        Ok(rendered) => rendered,
+        Err(e) => return HttpResponse::InternalServerError().body(e),
+    };
+
+    let name = page.rsplit('/').next().unwrap_or(&page);
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.html\"", percent_encode(name)),
+        ))
+        .body(rendered)
+}
+
+/// Text of a page as a plain HTML outline of its titles, labels and links, for screen
+/// readers and clients that can't show the drawing
+pub(crate) async fn text_page(
+    req: HttpRequest,
+    page: web::Path<String>,
+    template_engine: web::Data<Handlebars<'_>>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let (page, full_svg_path) = match page_svg_path(&opt.0, &page) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    println!("Loading SVG text at: {}", full_svg_path.display());
+
+    let svg_content = match std::fs::read_to_string(&full_svg_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("{e}");
+            return HttpResponse::InternalServerError().body("Failed to load SVG");
+        }
+    };
+
+    // Links to other pages stay in the text view, next to this one
+    let base = &req.path()[..=req.path().rfind('/').unwrap_or_default()];
+    let page_link = |target: &str| format!("{base}{}", percent_encode(target).replace('/', ":"));
+    let scope = config
+        .routes
+        .text
+        .as_ref()
+        .and_then(|text| base.strip_suffix(&format!("{text}/")))
+        .unwrap_or(base);
+    let diagram_url = format!("{scope}{}", percent_encode(&page).replace('/', ":"));
+
+    match render_text(
+        &template_engine,
+        &page,
+        &svg_content,
+        &page_link,
+        &diagram_url,
+    ) {
+        Ok(rendered) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(rendered),
+        Err(e) => HttpResponse::InternalServerError().body(e),
+    }
+}
+
+/// Size of a notebook embed
+#[derive(Debug, Deserialize)]
+pub(crate) struct NotebookOptions {
+    /// Width in pixels, the height following the aspect ratio; the SVG's own size by default
+    width: Option<f64>,
+}
+
+/// Bare SVG with an explicit size, for notebooks and other pages on any origin to embed
+/// and poll with `If-None-Match` for changes
+pub(crate) async fn notebook_svg(
+    req: HttpRequest,
+    page: web::Path<String>,
+    options: web::Query<NotebookOptions>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let (_, full_svg_path) = match page_svg_path(&opt.0, &page) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    if options
+        .width
+        .is_some_and(|width| !(1.0..=10_000.0).contains(&width))
+    {
+        return HttpResponse::BadRequest().body("width must be between 1 and 10000");
+    }
+    println!("Loading notebook SVG at: {}", full_svg_path.display());
+
+    let svg_content = match std::fs::read_to_string(&full_svg_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("{e}");
+            return HttpResponse::InternalServerError().body("Failed to load SVG");
+        }
+    };
+    let sized = match svg_size_fixed(&svg_content, options.width) {
+        Ok(sized) => sized,
+        Err(e) => return HttpResponse::UnprocessableEntity().body(e),
+    };
+
+    let mut response = compress::svg_response(&req, config.license.stamp(sized.into_bytes()));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        header::HeaderValue::from_static("*"),
+    );
+    headers.insert(
+        header::ACCESS_CONTROL_EXPOSE_HEADERS,
+        header::HeaderValue::from_static("etag"),
+    );
+    // Always revalidate, so embeds pick up edits on their next poll
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-cache"),
+    );
+    response
+}
+
+pub(crate) async fn page_statistics(
+    req: HttpRequest,
+    page: web::Path<String>,
+    opt: web::Data<SvgPath>,
+) -> Result<HttpResponse, ApiError> {
+    let (page, full_svg_path) = page_svg_path(&opt.0, &page).map_err(|e| {
+        ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_page", e)
+            .with_details(serde_json::json!({ "page": page.as_str() }))
+    })?;
+    println!(
+        "Collecting statistics for SVG at: {}",
+        full_svg_path.display()
+    );
+
+    let svg_content = std::fs::read_to_string(&full_svg_path).map_err(|e| {
+        eprintln!("{e}");
+        match e.kind() {
+            std::io::ErrorKind::NotFound => ApiError::new(
+                &req,
+                StatusCode::NOT_FOUND,
+                "page_not_found",
+                format!("No page named '{page}'"),
+            ),
+            _ => ApiError::new(
+                &req,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "read_failed",
+                "Failed to load SVG",
+            ),
+        }
+    })?;
+
+    let stats = SvgStatistics::collect(&svg_content).map_err(|e| {
+        eprintln!("{e}");
+        ApiError::new(&req, StatusCode::UNPROCESSABLE_ENTITY, "invalid_svg", e)
+            .with_details(serde_json::json!({ "page": page }))
+    })?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+/// How closely an uploaded PNG, such as a design tool's export, matches the page as this
+/// server renders it
+pub(crate) async fn compare_page(
+    req: HttpRequest,
+    page: web::Path<String>,
+    body: web::Bytes,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+    let (page, full_svg_path) = page_svg_path(&opt.0, &page).map_err(|e| {
+        ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_page", e)
+            .with_details(serde_json::json!({ "page": page.as_str() }))
+    })?;
+    let svg_content = std::fs::read_to_string(&full_svg_path).map_err(|e| {
+        eprintln!("{e}");
+        match e.kind() {
+            std::io::ErrorKind::NotFound => ApiError::new(
+                &req,
+                StatusCode::NOT_FOUND,
+                "page_not_found",
+                format!("No page named '{page}'"),
+            ),
+            _ => ApiError::new(
+                &req,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "read_failed",
+                "Failed to load SVG",
+            ),
+        }
+    })?;
+    let reference = compare::decode_reference(&body)
+        .map_err(|e| ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_reference", e))?;
+    println!(
+        "Comparing {page} with a {}x{} reference",
+        reference.width(),
+        reference.height()
+    );
+
+    // Rendering takes long enough on big diagrams to hold up other requests
+    let raster = config.raster.clone();
+    let similarity = web::block(move || {
+        let rendered = svg_to_pixmap(&svg_content, reference.width(), reference.height(), &raster)?;
+        Ok::<_, String>(compare::similarity(&rendered, &reference))
+    })
+    .await
+    .map_err(|e| {
+        eprintln!("{e}");
+        ApiError::new(
+            &req,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "compare_failed",
+            "Failed to render page",
+        )
+    })?
+    .map_err(|e| {
+        eprintln!("{e}");
+        ApiError::new(&req, StatusCode::UNPROCESSABLE_ENTITY, "invalid_svg", e)
+            .with_details(serde_json::json!({ "page": page }))
+    })?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "page": page.replace('/', ":"),
+        "width": similarity.width,
+        "height": similarity.height,
+        "pixel": similarity.pixel,
+        "structural": similarity.structural,
+        "differing": similarity.differing,
+    })))
+}
+
+pub(crate) async fn view_stats(
+    req: HttpRequest,
+    stats: web::Data<ViewStats>,
+) -> Result<HttpResponse, ApiError> {
+    listing_only(&req)?;
+
+    let invalid = |e: String| ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_parameter", e);
+    let query: StatsQuery =
+        serde_urlencoded::from_str(req.query_string()).map_err(|e| invalid(e.to_string()))?;
+    let page = stats.query(&query).map_err(invalid)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub(crate) async fn backlinks(
+    req: HttpRequest,
+    page: web::Path<String>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+    index: web::Data<SiteIndex>,
+) -> Result<HttpResponse, ApiError> {
+    listing_only(&req)?;
+
+    let (page, full_svg_path) = page_svg_path(&opt.0, &page).map_err(|e| {
+        ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_page", e)
+            .with_details(serde_json::json!({ "page": page.as_str() }))
+    })?;
+    if !full_svg_path.is_file() {
+        return Err(ApiError::new(
+            &req,
+            StatusCode::NOT_FOUND,
+            "page_not_found",
+            format!("No page named '{page}'"),
+        ));
+    }
+
+    let referenced_by: Vec<_> = index
+        .backlinks(&opt.0, &config.routes, &page)
+        .iter()
+        .map(|page| page.replace('/', ":"))
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "page": page.replace('/', ":"),
+        "referenced_by": referenced_by,
+    })))
+}
+
+/// A comment to leave on a page, as form fields or a JSON object
+#[derive(Debug, Deserialize)]
+pub(crate) struct NewComment {
+    body: String,
+}
+
+/// Resolve `page` for the comment and review APIs, which only know pages that exist
+fn existing_page(req: &HttpRequest, root: &Path, page: &str) -> Result<String, ApiError> {
+    let (page, full_svg_path) = page_svg_path(root, page).map_err(|e| {
+        ApiError::new(req, StatusCode::BAD_REQUEST, "invalid_page", e)
+            .with_details(serde_json::json!({ "page": page }))
+    })?;
+    if !full_svg_path.is_file() {
+        return Err(ApiError::new(
+            req,
+            StatusCode::NOT_FOUND,
+            "page_not_found",
+            format!("No page named '{page}'"),
+        ));
+    }
+    Ok(page)
+}
+
+pub(crate) async fn page_comments(
+    req: HttpRequest,
+    page: web::Path<String>,
+    opt: web::Data<SvgPath>,
+    store: web::Data<CommentStore>,
+) -> Result<HttpResponse, ApiError> {
+    let page = existing_page(&req, &opt.0, &page)?;
+    let comments = store.list(&page).map_err(|e| {
+        eprintln!("{e}");
+        ApiError::new(
+            &req,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "read_failed",
+            "Failed to load comments",
+        )
+    })?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "page": page.replace('/', ":"),
+        "items": comments,
+    })))
+}
+
+pub(crate) async fn add_comment(
+    req: HttpRequest,
+    page: web::Path<String>,
+    body: web::Bytes,
+    opt: web::Data<SvgPath>,
+    store: web::Data<CommentStore>,
+) -> Result<HttpResponse, ApiError> {
+    let user = signed_in_user(&req)?;
+    let page = existing_page(&req, &opt.0, &page)?;
+
+    let invalid = |e: String| ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_comment", e);
+    let comment: NewComment = serde_json::from_slice(&body).map_err(|e| invalid(e.to_string()))?;
+    let body = comments::check_body(&comment.body).map_err(invalid)?;
+    let comment = store.add(&page, &user.0, body).map_err(|e| {
+        eprintln!("{e}");
+        ApiError::new(
+            &req,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "write_failed",
+            "Failed to save comment",
+        )
+    })?;
+    println!("{} commented on {page}", user.0);
+    Ok(HttpResponse::Created().json(comment))
+}
+
+/// The user `req` was sent by, for API requests that write on their behalf
+fn signed_in_user(req: &HttpRequest) -> Result<User, ApiError> {
+    if cross_site(req) {
+        return Err(ApiError::new(
+            req,
+            StatusCode::FORBIDDEN,
+            "cross_site",
+            "Changes can't be made from another site",
+        ));
+    }
+    let user = req.extensions().get::<User>().cloned();
+    user.ok_or_else(|| {
+        ApiError::new(
+            req,
+            StatusCode::FORBIDDEN,
+            "sign_in_required",
+            "Only signed-in users can make changes",
+        )
+    })
+}
+
+/// The review status to move a page to
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReviewUpdate {
+    status: ReviewStatus,
+}
+
+pub(crate) async fn page_review(
+    req: HttpRequest,
+    page: web::Path<String>,
+    opt: web::Data<SvgPath>,
+    store: web::Data<ReviewStore>,
+) -> Result<HttpResponse, ApiError> {
+    let page = existing_page(&req, &opt.0, &page)?;
+    let review = store.get(&page).map_err(|e| {
+        eprintln!("{e}");
+        ApiError::new(
+            &req,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "read_failed",
+            "Failed to load review",
+        )
+    })?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "page": page.replace('/', ":"),
+        "review": review,
+    })))
+}
+
+/// Move a page to another review status, as far as `[review]` lets the user
+pub(crate) async fn set_review(
+    req: HttpRequest,
+    page: web::Path<String>,
+    body: web::Bytes,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+    store: web::Data<ReviewStore>,
+) -> Result<HttpResponse, ApiError> {
+    let user = signed_in_user(&req)?;
+    let page = existing_page(&req, &opt.0, &page)?;
+    let update: ReviewUpdate = serde_json::from_slice(&body).map_err(|e| {
+        ApiError::new(
+            &req,
+            StatusCode::BAD_REQUEST,
+            "invalid_status",
+            e.to_string(),
+        )
+    })?;
+
+    let failed = |code, message: &str, e: String| {
+        eprintln!("{e}");
+        ApiError::new(&req, StatusCode::INTERNAL_SERVER_ERROR, code, message)
+    };
+    // Checked against the status as it is when written, so a concurrent approval can't
+    // be overwritten by someone who may not take it back
+    let transition = store
+        .transition(&page, &user.0, update.status, |current| {
+            config.review.may_change(&user.0, current, update.status)
+        })
+        .map_err(|e| failed("write_failed", "Failed to save review", e))?;
+    let review = match transition {
+        Transition::Changed(review) => review,
+        Transition::Refused(current) => {
+            return Err(ApiError::new(
+                &req,
+                StatusCode::FORBIDDEN,
+                "not_permitted",
+                format!("{} may not change this page's review status", user.0),
+            )
+            .with_details(serde_json::json!({ "from": current, "to": update.status })));
+        }
+    };
+
+    println!("{} set {page} to {}", user.0, review.status.label());
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "page": page.replace('/', ":"),
+        "review": review,
+    })))
+}
+
+/// Which reviewed pages `/api/reviews` lists
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReviewsQuery {
+    status: Option<ReviewStatus>,
+}
+
+/// Pages that have a review status, by name
+pub(crate) async fn reviews(
+    req: HttpRequest,
+    query: web::Query<ReviewsQuery>,
+    opt: web::Data<SvgPath>,
+    store: web::Data<ReviewStore>,
+) -> Result<HttpResponse, ApiError> {
+    listing_only(&req)?;
+
+    let reviews = store.list(&opt.0, query.status).map_err(|e| {
+        eprintln!("{e}");
+        ApiError::new(
+            &req,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "read_failed",
+            "Failed to load reviews",
+        )
+    })?;
+    let items: Vec<_> = reviews
+        .into_iter()
+        .map(|(page, review)| {
+            serde_json::json!({
+                "page": page.replace('/', ":"),
+                "status": review.status,
+                "changed_by": review.changed_by,
+                "changed": review.changed,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "items": items })))
+}
+
+/// Leave a comment from the form under a page, then show it there
+pub(crate) async fn comment_form(
+    req: HttpRequest,
+    page: web::Path<String>,
+    form: web::Form<NewComment>,
+    opt: web::Data<SvgPath>,
+    store: web::Data<CommentStore>,
+) -> impl Responder {
+    if cross_site(&req) {
+        return HttpResponse::Forbidden().body("Comments can't be left from another site");
+    }
+    let Some(user) = req.extensions().get::<User>().cloned() else {
+        return HttpResponse::Forbidden().body("Only signed-in users can comment");
+    };
+    let (page, full_svg_path) = match page_svg_path(&opt.0, &page) {
+        Ok(resolved) => resolved,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    if !full_svg_path.is_file() {
+        return HttpResponse::NotFound().body(format!("No page named '{page}'"));
+    }
+    let body = match comments::check_body(&form.body) {
+        Ok(body) => body,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    if let Err(e) = store.add(&page, &user.0, body) {
+        eprintln!("{e}");
+        return HttpResponse::InternalServerError().body("Failed to save comment");
+    }
+    println!("{} commented on {page}", user.0);
+
+    // Back to the page as it was viewed, query string included
+    let location = req
+        .uri()
+        .path_and_query()
+        .map_or(req.path(), |path| path.as_str());
+    HttpResponse::SeeOther()
+        .insert_header((header::LOCATION, format!("{location}#comments")))
+        .finish()
+}
+
+/// Whether a browser sent `req` on behalf of another site, which signed-in users'
+/// credentials must not be used for.
+///
+/// Browsers say so in `Sec-Fetch-Site`, or at least name the page's `Origin`; requests
+/// with neither don't come from a browser.
+fn cross_site(req: &HttpRequest) -> bool {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    if let Some(site) = header("sec-fetch-site") {
+        return !matches!(site, "same-origin" | "none");
+    }
+    header("origin").is_some_and(|origin| {
+        let host = origin.split_once("://").map_or(origin, |(_, host)| host);
+        host != req.connection_info().host()
+    })
+}
+
+/// Pull the served Git checkout when GitHub or GitLab report a push, so the pages
+/// follow the diagram repository
+pub(crate) async fn git_webhook(
+    req: HttpRequest,
+    body: web::Bytes,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+    index: web::Data<SiteIndex>,
+    sync: web::Data<GitSync>,
+) -> Result<HttpResponse, ApiError> {
+    let secret = config.webhook.secret().unwrap_or_default();
+    if !git::webhook_authentic(secret, &req, &body) {
+        return Err(ApiError::new(
+            &req,
+            StatusCode::UNAUTHORIZED,
+            "invalid_signature",
+            "Webhook delivery is not signed with the configured secret",
+        ));
+    }
+    if !git::webhook_is_push(&req) {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({ "updated": false })));
+    }
+
+    let root = opt.0.clone();
+    let pulled = {
+        let sync = sync.clone();
+        web::block(move || sync.pull(&root, SyncTrigger::Webhook))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|pulled| pulled)
+    };
+    let pull = pulled.map_err(|e| {
+        eprintln!("Webhook pull failed: {e}");
+        let conflict = sync.latest().and_then(|report| report.conflict);
+        ApiError::new(&req, StatusCode::BAD_GATEWAY, "pull_failed", e)
+            .with_details(serde_json::json!({ "conflict": conflict }))
+    })?;
+
+    if pull.updated() {
+        println!(
+            "Pulled '{}' from {} to {}",
+            opt.0.display(),
+            pull.before,
+            pull.head
+        );
+        index.invalidate();
+    }
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "updated": pull.updated(),
+        "before": pull.before,
+        "head": pull.head,
+    })))
+}
+
+/// Structured query of `/api/search`, see [`SearchQuery`]
+#[derive(Debug, Deserialize)]
+pub(crate) struct SearchParams {
+    q: Option<String>,
+}
+
+/// Pages matching a structured query, e.g. every one painting with a deprecated color
+pub(crate) async fn search(
+    req: HttpRequest,
+    params: web::Query<SearchParams>,
+    opt: web::Data<SvgPath>,
+    index: web::Data<SiteIndex>,
+) -> Result<HttpResponse, ApiError> {
+    listing_only(&req)?;
+
+    let q = params.q.as_deref().unwrap_or_default();
+    let query = SearchQuery::parse(q).map_err(|e| {
+        ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_query", e)
+            .with_details(serde_json::json!({ "q": q }))
+    })?;
+    let items: Vec<_> = index
+        .search(&opt.0, &query)
+        .into_iter()
+        .map(|(page, metadata)| {
+            serde_json::json!({
+                "page": page.replace('/', ":"),
+                "width": metadata.width,
+                "height": metadata.height,
+            })
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "q": q, "items": items })))
+}
+
+/// How many of the stalest pages `/api/stale` lists, optionally only those with a
+/// review status
+#[derive(Debug, Deserialize)]
+pub(crate) struct StaleQuery {
+    limit: Option<usize>,
+    status: Option<ReviewStatus>,
+}
+
+/// Pages by how long their file went unchanged, oldest first
+pub(crate) async fn stale_pages(
+    req: HttpRequest,
+    query: web::Query<StaleQuery>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+    index: web::Data<SiteIndex>,
+) -> Result<HttpResponse, ApiError> {
+    listing_only(&req)?;
+
+    let limit = query.limit.unwrap_or(20);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(ApiError::new(
+            &req,
+            StatusCode::BAD_REQUEST,
+            "invalid_parameter",
+            format!("limit must be between 1 and {MAX_LIMIT}"),
+        ));
+    }
+    let reviews = req.app_data::<web::Data<ReviewStore>>();
+    let with_status = |page: &str| match (query.status, reviews) {
+        (None, _) => true,
+        (Some(status), Some(reviews)) => reviews
+            .get(page)
+            .inspect_err(|e| eprintln!("{e}"))
+            .is_ok_and(|review| review.is_some_and(|review| review.status == status)),
+        // Without a sidecar directory no page has a review
+        (Some(_), None) => false,
+    };
+    let items = page_ages(&index, &opt.0, &config, limit, &with_status);
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "items": items })))
+}
+
+/// The `limit` least recently modified pages that `keep` accepts, with the threshold
+/// they are held to
+fn page_ages(
+    index: &SiteIndex,
+    root: &Path,
+    config: &Config,
+    limit: usize,
+    keep: &dyn Fn(&str) -> bool,
+) -> Vec<serde_json::Value> {
+    index
+        .ages(root)
+        .into_iter()
+        .filter(|(page, _)| keep(page))
+        .take(limit)
+        .map(|(page, days)| {
+            serde_json::json!({
+                "page": page.replace('/', ":"),
+                "days": days,
+                "threshold": config.stale.threshold(&page),
+                "stale": config.stale.is_stale(&page, days),
+            })
+        })
+        .collect()
+}
+
+/// Server status for whoever runs it: how the Git checkout last synced, what conflicts
+/// if it couldn't, and the diagrams longest left unchanged
+pub(crate) async fn admin_page(
+    req: HttpRequest,
+    template_engine: web::Data<Handlebars<'_>>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let admin = req.path().trim_end_matches('/');
+    let mut data = admin_status(&req, &opt.0, &config);
+    data["guest_action"] = format!("{admin}/guest").into();
+    data["jobs_api"] = format!("{admin}/api/jobs").into();
+    render_admin(&template_engine, &data)
+}
+
+/// What the admin page shows in any case
+fn admin_status(req: &HttpRequest, root: &Path, config: &Config) -> serde_json::Value {
+    let sync = req.app_data::<web::Data<GitSync>>();
+    let git = sync.and_then(|sync| sync.latest()).map(|report| {
+        let (pull, error) = match &report.result {
+            Ok(pull) => (Some(pull), None),
+            Err(e) => (None, Some(e)),
+        };
+        serde_json::json!({
+            "trigger": report.trigger,
+            "ago": ago(report.at),
+            "updated": pull.is_some_and(|pull| pull.updated()),
+            "pull": pull,
+            "error": error,
+            "conflict": report.conflict,
+        })
+    });
+
+    let listing = ListingEnabled::of(req);
+    let stalest = match req.app_data::<web::Data<SiteIndex>>() {
+        Some(index) if listing => page_ages(index, root, config, 10, &|_| true),
+        _ => Vec::new(),
+    };
+    let jobs = req
+        .app_data::<web::Data<Jobs>>()
+        .map(|jobs| jobs.list())
+        .unwrap_or_default();
+    let exports = req
+        .app_data::<web::Data<JobRunner>>()
+        .is_some_and(|runner| runner.export_out.is_some());
+    serde_json::json!({
+        "git": git,
+        "listing": listing,
+        "stalest": stalest,
+        "guests": config.routes.guest.is_some(),
+        "max_guest_hours": MAX_HOURS,
+        "jobs": jobs,
+        "exports": exports,
+    })
+}
+
+fn render_admin(template_engine: &Handlebars<'_>, data: &serde_json::Value) -> HttpResponse {
+    match template_engine.render("admin", data) {
+        Ok(html) => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .body(html),
+        Err(e) => {
+            eprintln!("{e}");
+            HttpResponse::InternalServerError().body("Template rendering error")
+        }
+    }
+}
+
+/// How long a guest link lasts and what it opens, `:` separated like page names
+#[derive(Debug, Deserialize)]
+pub(crate) struct GuestLinkForm {
+    hours: u64,
+    /// Directory the link is limited to, the whole site if empty
+    dir: Option<String>,
+}
+
+/// Mint a guest link from the admin page, shown on the page it returns
+pub(crate) async fn mint_guest_link(
+    req: HttpRequest,
+    form: web::Form<GuestLinkForm>,
+    template_engine: web::Data<Handlebars<'_>>,
+    session_key: web::Data<SessionKey>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    if cross_site(&req) {
+        return HttpResponse::Forbidden().body("Guest links can't be minted from another site");
+    }
+    let Some(user) = req.extensions().get::<User>().cloned() else {
+        return HttpResponse::Forbidden().body("Only signed-in users can mint guest links");
+    };
+    if !(1..=MAX_HOURS).contains(&form.hours) {
+        return HttpResponse::BadRequest().body(format!("Guest links last 1 to {MAX_HOURS} hours"));
+    }
+    let dir = match form
+        .dir
+        .as_deref()
+        .map(str::trim)
+        .filter(|dir| !dir.is_empty())
+    {
+        None => None,
+        Some(dir) => match normalize_page(dir) {
+            Ok(dir) if opt.0.join(&dir).is_dir() => Some(dir),
+            _ => return HttpResponse::BadRequest().body(format!("No directory named '{dir}'")),
+        },
+    };
+    let Some(guest) = &config.routes.guest else {
+        return HttpResponse::NotFound().body("Guest links are turned off");
+    };
+
+    let pass = GuestPass::new(form.hours, dir);
+    // The landing route sits next to the admin page, wherever the service is mounted
+    let base = req.path().rsplitn(3, '/').last().unwrap_or_default();
+    let link = format!(
+        "{}://{}{base}/{guest}?{}",
+        req.connection_info().scheme(),
+        req.connection_info().host(),
+        serde_urlencoded::to_string([("token", pass.token(&session_key))]).unwrap_or_default()
+    );
+    let opens = pass
+        .dir
+        .as_ref()
+        .map_or("the whole site".to_owned(), |dir| dir.replace('/', ":"));
+    println!(
+        "{} minted a guest link to {opens} for {} hours",
+        user.0, form.hours
+    );
+
+    let admin = req.path().strip_suffix("/guest").unwrap_or_default();
+    let mut data = admin_status(&req, &opt.0, &config);
+    data["guest_action"] = req.path().into();
+    data["jobs_api"] = format!("{admin}/api/jobs").into();
+    data["guest_link"] = serde_json::json!({
+        "link": link,
+        "hours": form.hours,
+        "dir": pass.dir.as_ref().map(|dir| dir.replace('/', ":")),
+    });
+    render_admin(&template_engine, &data)
+}
+
+/// The token of a guest link
+#[derive(Debug, Deserialize)]
+pub(crate) struct GuestLanding {
+    token: String,
+}
+
+/// Where guest links land: keep the pass in a cookie, then start on the first page it
+/// opens
+pub(crate) async fn guest_landing(
+    landing: web::Query<GuestLanding>,
+    session_key: web::Data<SessionKey>,
+    opt: web::Data<SvgPath>,
+) -> impl Responder {
+    let Some(pass) = GuestPass::from_token(&landing.token, &session_key) else {
+        return HttpResponse::Forbidden().body("This guest link is invalid or has expired");
+    };
+
+    let first = pass.dir.as_ref().and_then(|dir| {
+        let (page, _) = svg_files(&opt.0.join(dir)).into_iter().next()?;
+        Some(format!("{}:{page}", dir.replace('/', ":")))
+    });
+    let location = match &first {
+        // Colons kept, as the pass checks page names the way links write them
+        Some(page) => format!(
+            "./{}",
+            page.split(':')
+                .map(percent_encode)
+                .collect::<Vec<_>>()
+                .join(":")
+        ),
+        None => "./".to_owned(),
+    };
+    println!(
+        "Guest link opened, for {}",
+        pass.dir.as_deref().unwrap_or("the whole site")
+    );
+    HttpResponse::SeeOther()
+        .cookie(pass.cookie(&session_key))
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// Which job `POST /admin/api/jobs` starts
+#[derive(Debug, Deserialize)]
+pub(crate) struct NewJob {
+    kind: JobKind,
+}
+
+/// Every job started since the server did, or at least the latest ones
+pub(crate) async fn list_jobs(jobs: web::Data<Jobs>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(serde_json::json!({ "items": jobs.list() }))
+}
+
+/// Start an export or a pull in the background, to follow at the returned `Location`
+pub(crate) async fn start_job(
+    req: HttpRequest,
+    body: web::Bytes,
+    jobs: web::Data<Jobs>,
+    runner: web::Data<JobRunner>,
+) -> Result<HttpResponse, ApiError> {
+    let user = signed_in_user(&req)?;
+    let new: NewJob = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::new(&req, StatusCode::BAD_REQUEST, "invalid_job", e.to_string()))?;
+    if new.kind == JobKind::Export && runner.export_out.is_none() {
+        return Err(ApiError::new(
+            &req,
+            StatusCode::BAD_REQUEST,
+            "export_not_configured",
+            "Export jobs need the server started with --export-out",
+        ));
+    }
+    let job = jobs.start(new.kind).map_err(|running| {
+        ApiError::new(
+            &req,
+            StatusCode::CONFLICT,
+            "job_running",
+            format!("A {} job is already running", new.kind.label()),
+        )
+        .with_details(serde_json::json!({ "job": running.status() }))
+    })?;
+
+    let status = job.status();
+    println!("{} started {} job {}", user.0, new.kind.label(), status.id);
+    runner.spawn(job);
+    Ok(HttpResponse::Accepted()
+        .insert_header((
+            header::LOCATION,
+            format!("{}/{}", req.path().trim_end_matches('/'), status.id),
+        ))
+        .json(status))
+}
+
+/// The job `id`, or a 404 once it is forgotten
+fn find_job(req: &HttpRequest, jobs: &Jobs, id: u64) -> Result<Arc<Job>, ApiError> {
+    jobs.get(id).ok_or_else(|| {
+        ApiError::new(
+            req,
+            StatusCode::NOT_FOUND,
+            "not_found",
+            format!("No job {id}"),
+        )
+    })
+}
+
+pub(crate) async fn job_status(
+    req: HttpRequest,
+    id: web::Path<u64>,
+    jobs: web::Data<Jobs>,
+) -> Result<HttpResponse, ApiError> {
+    let job = find_job(&req, &jobs, *id)?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(job.status()))
+}
+
+/// Ask a running job to stop, which exports do before their next page
+pub(crate) async fn cancel_job(
+    req: HttpRequest,
+    id: web::Path<u64>,
+    jobs: web::Data<Jobs>,
+) -> Result<HttpResponse, ApiError> {
+    let user = signed_in_user(&req)?;
+    let job = find_job(&req, &jobs, *id)?;
+    if !job.cancel() {
+        return Err(ApiError::new(
+            &req,
+            StatusCode::CONFLICT,
+            "job_finished",
+            format!("Job {id} is already over"),
+        )
+        .with_details(serde_json::json!({ "job": job.status() })));
+    }
+    println!("{} cancelled job {id}", user.0);
+    Ok(HttpResponse::Accepted().json(job.status()))
+}
+
+/// How often a job's event stream checks it for progress
+const JOB_EVENTS_POLL: Duration = Duration::from_millis(250);
+
+/// A job's status as server-sent events: a `status` event now and whenever it
+/// changes, the stream ending with the job
+pub(crate) async fn job_events(
+    req: HttpRequest,
+    id: web::Path<u64>,
+    jobs: web::Data<Jobs>,
+) -> Result<HttpResponse, ApiError> {
+    let job = find_job(&req, &jobs, *id)?;
+    let events = futures_util::stream::unfold(Some((job, None)), |state| async move {
+        let (job, sent) = state?;
+        loop {
+            let status = job.status();
+            if sent.as_ref() != Some(&status) {
+                let data = serde_json::to_string(&status).unwrap_or_default();
+                let event = web::Bytes::from(format!("event: status\ndata: {data}\n\n"));
+                let next = (status.state == JobState::Running).then_some((job, Some(status)));
+                return Some((Ok::<_, Infallible>(event), next));
+            }
+            actix_web::rt::time::sleep(JOB_EVENTS_POLL).await;
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        // Compressing would hold events back until enough of them fill a block
+        .insert_header((header::CONTENT_ENCODING, "identity"))
+        .streaming(events))
+}
+
+/// How long ago `at` was, roughly
+fn ago(at: SystemTime) -> String {
+    let secs = at.elapsed().map_or(0, |elapsed| elapsed.as_secs());
+    match secs {
+        0..60 => "just now".to_owned(),
+        60..3600 => format!("{} min ago", secs / 60),
+        3600..86_400 => format!("{} h ago", secs / 3600),
+        _ => format!("{} days ago", secs / 86_400),
+    }
+}
+
+/// Preferences to pin for the visitor, and where to send them afterwards
+#[derive(Debug, Deserialize)]
+pub(crate) struct PreferencesUpdate {
+    #[serde(flatten)]
+    view: ViewOptions,
+    /// Local path to redirect to once the preferences are saved
+    return_to: Option<String>,
+}
+
+pub(crate) async fn set_preferences(
+    req: HttpRequest,
+    update: web::Query<PreferencesUpdate>,
+    session_key: web::Data<SessionKey>,
+) -> impl Responder {
+    // A GET, so other sites could otherwise pin options with an <img> or a link
+    if cross_site(&req) {
+        return HttpResponse::Forbidden().body("Preferences can't be pinned from another site");
+    }
+    let update = update.into_inner();
+
+    // Only follow local paths, never another origin; default to the service root
+    let location = match update.return_to {
+        Some(path) if path.starts_with('/') && !path.starts_with("//") && !path.contains('\\') => {
+            path
+        }
+        _ => "./".to_owned(),
+    };
+
+    println!("Pinning view preferences {:?}", update.view);
+    HttpResponse::SeeOther()
+        .cookie(update.view.cookie(&session_key))
+        .insert_header((header::LOCATION, location))
+        .finish()
+}
+
+/// Pages to merge into a sprite, either listed or every page in a directory
+#[derive(Debug, Deserialize)]
+pub(crate) struct SpriteOptions {
+    /// Comma-separated page names
+    icons: Option<String>,
+    /// Directory whose SVG files are all included, `:` separated like page names
+    dir: Option<String>,
+}
+
+pub(crate) async fn sprite_svg(
+    req: HttpRequest,
+    options: web::Query<SpriteOptions>,
+    opt: web::Data<SvgPath>,
+) -> impl Responder {
+    if options.dir.is_some() && !ListingEnabled::of(&req) {
+        return HttpResponse::NotFound().finish();
+    }
+
+    // Pair each symbol id with the file it is read from
+    let files = match (&options.icons, &options.dir) {
+        (Some(icons), None) => icons
+            .split(',')
+            .filter(|icon| !icon.is_empty())
+            .map(|icon| page_svg_path(&opt.0, icon).map(|(_, path)| (icon.to_owned(), path)))
+            .collect::<Result<Vec<_>, _>>(),
+        (None, Some(dir)) => sprite_dir_files(&opt.0, dir),
+        _ => Err("Expected exactly one of 'icons' or 'dir'".to_owned()),
+    };
+    let files = match files {
+        Ok(files) => files,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let mut icons = Vec::with_capacity(files.len());
+    for (name, path) in files {
+        println!("Loading sprite SVG at: {}", path.display());
+        match std::fs::read_to_string(&path) {
+            Ok(content) => icons.push((name, content)),
+            Err(e) => {
+                eprintln!("{e}");
+                return HttpResponse::InternalServerError()
+                    .body(format!("Failed to load SVG '{name}'"));
+            }
+        }
+    }
+
+    match svg_sprite(&icons) {
+        Ok(sprite) => compress::svg_response(&req, sprite.into_bytes()),
+        Err(e) => {
+            eprintln!("{e}");
+            HttpResponse::InternalServerError().body(e)
+        }
+    }
+}
+
+/// Every SVG under a directory as a gzipped tarball, each file compressed as the
+/// response reaches it rather than the whole archive up front
+pub(crate) async fn archive_dir(
+    req: HttpRequest,
+    dir: web::Path<String>,
+    opt: web::Data<SvgPath>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    if !ListingEnabled::of(&req) {
+        return HttpResponse::NotFound().finish();
+    }
+    let dir = match normalize_page(&dir) {
+        Ok(dir) if opt.0.join(&dir).is_dir() => dir,
+        _ => return HttpResponse::NotFound().body(format!("No directory named '{dir}'")),
+    };
+    let files = svg_files(&opt.0.join(&dir));
+    println!("Archiving {} SVGs under {dir}", files.len());
+    let name = dir.rsplit('/').next().unwrap_or_default().to_owned();
+
+    let chunks =
+        futures_util::stream::unfold(Some((TarGz::new(), files.into_iter())), move |state| {
+            let (dir, config) = (dir.clone(), config.clone());
+            async move {
+                let (mut tarball, mut files) = state?;
+                let Some((page, path)) = files.next() else {
+                    return Some((tarball.finish().map(web::Bytes::from), None));
+                };
+                let appended =
+                    std::fs::read(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|content| {
+                            let mtime = std::fs::metadata(&path)
+                                .and_then(|metadata| metadata.modified())
+                                .ok()
+                                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                                .map_or(0, |since| since.as_secs());
+                            // Paths relative to the served directory, extracting as it is laid out
+                            let entry = format!("{dir}/{}.svg", page.replace(':', "/"));
+                            tarball.append(&entry, &config.license.stamp(content), mtime)
+                        });
+                match appended {
+                    Ok(chunk) => Some((Ok(web::Bytes::from(chunk)), Some((tarball, files)))),
+                    // Cut short, which clients notice from the missing end of the archive
+                    Err(e) => {
+                        eprintln!("Failed to archive '{}': {e}", path.display());
+                        Some((Err(e), None))
+                    }
+                }
+            }
+        });
+    HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.tar.gz\"", percent_encode(&name)),
+        ))
+        // Already compressed
+        .insert_header((header::CONTENT_ENCODING, "identity"))
+        .streaming(chunks)
+}
+
+/// Every SVG file directly inside a directory, named by file stem
+fn sprite_dir_files(root: &Path, dir: &str) -> Result<Vec<(String, PathBuf)>, String> {
+    let dir_path = if dir.is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(normalize_page(dir)?)
+    };
+
+    let entries = std::fs::read_dir(&dir_path)
+        .map_err(|e| format!("Failed to read directory '{dir}': {e}"))?;
+
+    let mut files: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "svg"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_lowercase();
+            Some((name, path))
+        })
+        .collect();
+    files.sort();
+
+    Ok(files)
+}
@@ -0,0 +1,283 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned, pki_types::ServerName};
+use serde::Deserialize;
+
+/// How long to wait on the mail server before giving up
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+static TLS_CONFIG: LazyLock<Arc<ClientConfig>> = LazyLock::new(|| {
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    Arc::new(
+        ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default protocol versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+});
+
+/// How the connection to the mail server is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Upgrade a plain connection with `STARTTLS`, usually on port 587
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// No encryption, for a relay on the same host or network
+    None,
+}
+
+/// Mail server that digests are sent through
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: Option<String>,
+    /// Port to connect to [default: 587, 465 with `tls = "tls"`, 25 with `tls = "none"`]
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    /// User to sign in as with `AUTH PLAIN`; without one no authentication is attempted
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+/// A conversation with the mail server, one command and reply at a time
+struct Session {
+    stream: BufReader<Box<dyn Stream>>,
+}
+
+impl Session {
+    /// Read a reply, joining the lines of multi-line ones, and check its code
+    fn expect(&mut self, code: u16) -> Result<String, String> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            self.stream
+                .read_line(&mut line)
+                .map_err(|e| format!("Failed to read from the mail server: {e}"))?;
+            let line = line.trim_end();
+            let (reply_code, rest) = (line.get(..3), line.get(3..).unwrap_or_default());
+            if reply_code != Some(&*code.to_string()) {
+                return Err(format!("Mail server replied '{line}'"));
+            }
+            text.push_str(rest.get(1..).unwrap_or_default());
+            text.push('\n');
+            if !rest.starts_with('-') {
+                return Ok(text);
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str, code: u16) -> Result<String, String> {
+        self.stream
+            .get_mut()
+            .write_all(format!("{command}\r\n").as_bytes())
+            .map_err(|e| format!("Failed to write to the mail server: {e}"))?;
+        self.expect(code)
+    }
+}
+
+fn tls(host: &str, stream: TcpStream) -> Result<Box<dyn Stream>, String> {
+    let name = ServerName::try_from(host.to_owned())
+        .map_err(|e| format!("Invalid mail server name '{host}': {e}"))?;
+    let connection = ClientConnection::new(Arc::clone(&TLS_CONFIG), name)
+        .map_err(|e| format!("Failed to set up TLS: {e}"))?;
+    Ok(Box::new(StreamOwned::new(connection, stream)))
+}
+
+/// Send `message`, a complete RFC 5322 message with CRLF line endings, from `from` to
+/// every address in `to`
+pub(crate) fn send(
+    config: &SmtpConfig,
+    from: &str,
+    to: &[String],
+    message: &str,
+) -> Result<(), String> {
+    let host = config
+        .host
+        .as_deref()
+        .ok_or("No mail server configured, set [smtp] host")?;
+    let port = config.port.unwrap_or(match config.tls {
+        SmtpTls::Starttls => 587,
+        SmtpTls::Tls => 465,
+        SmtpTls::None => 25,
+    });
+
+    let tcp = TcpStream::connect((host, port))
+        .map_err(|e| format!("Failed to connect to {host}:{port}: {e}"))?;
+    tcp.set_read_timeout(Some(TIMEOUT))
+        .and_then(|()| tcp.set_write_timeout(Some(TIMEOUT)))
+        .map_err(|e| e.to_string())?;
+
+    let plain = tcp.try_clone().map_err(|e| e.to_string())?;
+    let mut session = Session {
+        stream: BufReader::new(match config.tls {
+            SmtpTls::Tls => tls(host, plain)?,
+            SmtpTls::Starttls | SmtpTls::None => Box::new(plain),
+        }),
+    };
+    session.expect(220)?;
+    session.command("EHLO svg-server", 250)?;
+
+    if config.tls == SmtpTls::Starttls {
+        session.command("STARTTLS", 220)?;
+        // Nothing more arrives before the handshake, so no buffered bytes are lost
+        session = Session {
+            stream: BufReader::new(tls(host, tcp)?),
+        };
+        session.command("EHLO svg-server", 250)?;
+    }
+
+    if let Some(username) = &config.username {
+        let password = config.password.as_deref().unwrap_or_default();
+        let credentials = STANDARD.encode(format!("\0{username}\0{password}"));
+        session.command(&format!("AUTH PLAIN {credentials}"), 235)?;
+    }
+
+    session.command(&format!("MAIL FROM:<{from}>"), 250)?;
+    for recipient in to {
+        session.command(&format!("RCPT TO:<{recipient}>"), 250)?;
+    }
+    session.command("DATA", 354)?;
+    // Lines starting with a dot would otherwise end the message early
+    let mut data = String::with_capacity(message.len() + 5);
+    for line in message.split_inclusive("\r\n") {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+    }
+    if !data.ends_with("\r\n") {
+        data.push_str("\r\n");
+    }
+    data.push('.');
+    session.command(&data, 250)?;
+    session.command("QUIT", 221)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn messages_are_delivered() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut received = Vec::new();
+            writer.write_all(b"220 mail.example.com ESMTP\r\n").unwrap();
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end().to_owned();
+                if in_data {
+                    if line == "." {
+                        in_data = false;
+                        writer.write_all(b"250 Queued\r\n").unwrap();
+                    } else {
+                        received.push(line);
+                    }
+                    continue;
+                }
+                let reply: &[u8] = match line.split(' ').next().unwrap() {
+                    "EHLO" => b"250-mail.example.com\r\n250 AUTH PLAIN\r\n",
+                    "AUTH" => b"235 Authenticated\r\n",
+                    "DATA" => b"354 Go ahead\r\n",
+                    "QUIT" => b"221 Bye\r\n",
+                    _ => b"250 OK\r\n",
+                };
+                in_data = line == "DATA";
+                let quit = line == "QUIT";
+                received.push(line);
+                writer.write_all(reply).unwrap();
+                if quit {
+                    break;
+                }
+            }
+            received
+        });
+
+        let config = SmtpConfig {
+            host: Some("127.0.0.1".to_owned()),
+            port: Some(port),
+            tls: SmtpTls::None,
+            username: Some("digest".to_owned()),
+            password: Some("s3cret".to_owned()),
+        };
+        let to = ["a@example.com".to_owned(), "b@example.com".to_owned()];
+        send(
+            &config,
+            "svg-server@example.com",
+            &to,
+            "Subject: Hi\r\n\r\n.hidden\r\nBye",
+        )
+        .unwrap();
+
+        assert_eq!(
+            server.join().unwrap(),
+            [
+                "EHLO svg-server",
+                // \0digest\0s3cret
+                "AUTH PLAIN AGRpZ2VzdABzM2NyZXQ=",
+                "MAIL FROM:<svg-server@example.com>",
+                "RCPT TO:<a@example.com>",
+                "RCPT TO:<b@example.com>",
+                "DATA",
+                "Subject: Hi",
+                "",
+                "..hidden",
+                "Bye",
+                "QUIT",
+            ]
+        );
+    }
+
+    #[test]
+    fn tls_trusts_the_public_roots() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert!(tls("smtp.example.com", tcp).is_ok());
+    }
+
+    #[test]
+    fn refusals_are_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"554 No SMTP service here\r\n").unwrap();
+        });
+        let config = SmtpConfig {
+            host: Some("127.0.0.1".to_owned()),
+            port: Some(port),
+            tls: SmtpTls::None,
+            ..SmtpConfig::default()
+        };
+        let error = send(&config, "a@example.com", &[], "").unwrap_err();
+        assert_eq!(error, "Mail server replied '554 No SMTP service here'");
+    }
+}
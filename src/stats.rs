@@ -0,0 +1,323 @@
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+
+/// Largest number of rows one stats page returns
+pub const MAX_LIMIT: usize = 100;
+
+/// Page views counted per UTC day, shared by every worker of a server
+#[derive(Debug, Default)]
+pub struct ViewStats {
+    /// View counts keyed by (day since the Unix epoch, page name)
+    views: Mutex<BTreeMap<(u32, String), u64>>,
+}
+
+/// What to count, and which slice of the sorted result to return
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatsQuery {
+    /// First day counted, `YYYY-MM-DD` (UTC)
+    pub since: Option<String>,
+    /// Last day counted, `YYYY-MM-DD` (UTC)
+    pub until: Option<String>,
+    /// Only count pages under this directory, `:` or `/` separated
+    pub dir: Option<String>,
+    /// Count per page, or per directory
+    #[serde(default)]
+    pub group: StatsGroup,
+    /// Number of rows to return, at most [`MAX_LIMIT`]
+    pub limit: Option<usize>,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGroup {
+    #[default]
+    Page,
+    Dir,
+}
+
+/// Rows sorted by views, most viewed first
+#[derive(Debug, Serialize)]
+pub struct StatsPage {
+    pub items: Vec<StatsRow>,
+    /// Cursor fetching the rows after these, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsRow {
+    /// Page name, or directory name (`""` for the root) when grouping by directory
+    pub name: String,
+    pub views: u64,
+}
+
+impl ViewStats {
+    /// Count a view of `page` today
+    pub fn record(&self, page: &str) {
+        self.record_on(today(), page);
+    }
+
+    fn record_on(&self, day: u32, page: &str) {
+        let mut views = self.views.lock().unwrap();
+        *views.entry((day, page.replace('/', ":"))).or_default() += 1;
+    }
+
+    /// Views matching `query`, or why the query is invalid
+    pub fn query(&self, query: &StatsQuery) -> Result<StatsPage, String> {
+        let since = query.since.as_deref().map(parse_day).transpose()?;
+        let until = query.until.as_deref().map(parse_day).transpose()?;
+        let dir = query
+            .dir
+            .as_deref()
+            .map(|dir| {
+                dir.trim_matches(['/', ':'])
+                    .replace('/', ":")
+                    .to_lowercase()
+            })
+            .filter(|dir| !dir.is_empty());
+        let limit = query.limit.unwrap_or(20);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(format!("limit must be between 1 and {MAX_LIMIT}"));
+        }
+        let after = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+        let mut totals: BTreeMap<String, u64> = BTreeMap::new();
+        for ((day, page), count) in self.views.lock().unwrap().iter() {
+            let in_window =
+                since.is_none_or(|since| *day >= since) && until.is_none_or(|until| *day <= until);
+            let in_dir = dir.as_ref().is_none_or(|dir| {
+                page.strip_prefix(dir.as_str())
+                    .is_some_and(|rest| rest.starts_with(':'))
+            });
+            if !in_window || !in_dir {
+                continue;
+            }
+
+            let name = match query.group {
+                StatsGroup::Page => page.clone(),
+                StatsGroup::Dir => page.rsplit_once(':').map_or("", |(dir, _)| dir).to_owned(),
+            };
+            *totals.entry(name).or_default() += count;
+        }
+
+        let mut rows: Vec<_> = totals
+            .into_iter()
+            .map(|(name, views)| StatsRow { name, views })
+            .collect();
+        rows.sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.name.cmp(&b.name)));
+
+        // Resume strictly after the last row returned, so new views don't repeat rows
+        if let Some((views, name)) = after {
+            rows.retain(|row| row.views < views || (row.views == views && row.name > name));
+        }
+
+        let next_cursor = (rows.len() > limit).then(|| encode_cursor(&rows[limit - 1]));
+        rows.truncate(limit);
+        Ok(StatsPage {
+            items: rows,
+            next_cursor,
+        })
+    }
+}
+
+fn today() -> u32 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    (secs / 86_400) as u32
+}
+
+/// Days since the Unix epoch of a `YYYY-MM-DD` date
+fn parse_day(date: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid date '{date}', expected YYYY-MM-DD");
+    let mut parts = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+
+    // Howard Hinnant's days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Ok((era * 146_097 + day_of_era - 719_468) as u32)
+}
+
+/// Year, month and day of a number of days since the Unix epoch
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    // Howard Hinnant's civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// `time` as an RFC 3339 UTC timestamp, to the second
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let secs_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+fn encode_cursor(row: &StatsRow) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", row.views, row.name))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(u64, String), String> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|decoded| {
+            let (views, name) = decoded.split_once(':')?;
+            Some((views.parse().ok()?, name.to_owned()))
+        })
+        .ok_or_else(|| "Invalid cursor".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query() -> StatsQuery {
+        StatsQuery {
+            since: None,
+            until: None,
+            dir: None,
+            group: StatsGroup::Page,
+            limit: None,
+            cursor: None,
+        }
+    }
+
+    fn stats() -> ViewStats {
+        let stats = ViewStats::default();
+        let march = parse_day("2026-03-10").unwrap();
+        let april = parse_day("2026-04-02").unwrap();
+        for (day, page, views) in [
+            (march, "architecture/overview", 5),
+            (april, "architecture/overview", 2),
+            (april, "architecture/storage/db", 3),
+            (april, "home", 4),
+        ] {
+            for _ in 0..views {
+                stats.record_on(day, page);
+            }
+        }
+        stats
+    }
+
+    #[test]
+    fn dates_count_days_since_the_epoch() {
+        assert_eq!(parse_day("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_day("2000-03-01").unwrap(), 11_017);
+        assert!(parse_day("2026-13-01").is_err());
+        assert!(parse_day("yesterday").is_err());
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(951_782_400 + 3_723);
+        assert_eq!(rfc3339(time), "2000-02-29T01:02:03Z");
+    }
+
+    #[test]
+    fn views_are_filtered_by_window_and_directory() {
+        let page = stats()
+            .query(&StatsQuery {
+                since: Some("2026-04-01".to_owned()),
+                until: Some("2026-04-30".to_owned()),
+                dir: Some("/architecture".to_owned()),
+                ..query()
+            })
+            .unwrap();
+        let names: Vec<_> = page
+            .items
+            .iter()
+            .map(|row| (row.name.as_str(), row.views))
+            .collect();
+        assert_eq!(
+            names,
+            [("architecture:storage:db", 3), ("architecture:overview", 2)]
+        );
+    }
+
+    #[test]
+    fn views_can_be_grouped_by_directory() {
+        let page = stats()
+            .query(&StatsQuery {
+                group: StatsGroup::Dir,
+                ..query()
+            })
+            .unwrap();
+        let names: Vec<_> = page
+            .items
+            .iter()
+            .map(|row| (row.name.as_str(), row.views))
+            .collect();
+        assert_eq!(
+            names,
+            [("architecture", 7), ("", 4), ("architecture:storage", 3)]
+        );
+    }
+
+    #[test]
+    fn cursors_page_through_every_row_once() {
+        let stats = stats();
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = stats
+                .query(&StatsQuery {
+                    limit: Some(1),
+                    cursor,
+                    ..query()
+                })
+                .unwrap();
+            seen.extend(page.items.into_iter().map(|row| row.name));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(
+            seen,
+            ["architecture:overview", "home", "architecture:storage:db"]
+        );
+
+        assert!(
+            stats
+                .query(&StatsQuery {
+                    cursor: Some("!".to_owned()),
+                    ..query()
+                })
+                .is_err()
+        );
+    }
+}
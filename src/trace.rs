@@ -0,0 +1,183 @@
+//! W3C Trace Context: continuing the caller's trace, so outbound calls made for a request
+//! show up in the same distributed trace.
+
+use actix_web::{
+    HttpMessage, HttpRequest,
+    http::header::{HeaderMap, HeaderName, HeaderValue},
+};
+
+pub(crate) const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+pub(crate) const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+/// Longest `tracestate` passed on; the specification asks for at least 512 characters
+const MAX_TRACESTATE: usize = 512;
+
+/// The trace a request belongs to, and the span this server handles it in
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TraceContext {
+    /// 32 lowercase hex digits shared by every span in the trace
+    pub(crate) trace_id: String,
+    /// 16 lowercase hex digits naming this server's span
+    pub(crate) span_id: String,
+    /// Whether the caller records the trace
+    sampled: bool,
+    /// Vendor state, passed on untouched
+    state: Option<String>,
+}
+
+impl TraceContext {
+    /// Continue the trace in the request's `traceparent`, or start a new one when it is
+    /// missing or invalid (dropping any `tracestate` along with it)
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let parent = headers
+            .get(&TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_traceparent);
+        let Some((trace_id, sampled)) = parent else {
+            return TraceContext {
+                trace_id: format!("{:032x}", rand::random::<u128>() | 1),
+                span_id: span_id(),
+                sampled: false,
+                state: None,
+            };
+        };
+
+        // Multiple tracestate headers form one list
+        let state = headers
+            .get_all(&TRACESTATE)
+            .map(|value| value.to_str().map(str::trim))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()
+            .map(|parts| parts.join(","))
+            .filter(|state| !state.is_empty() && state.len() <= MAX_TRACESTATE);
+        TraceContext {
+            trace_id,
+            span_id: span_id(),
+            sampled,
+            state,
+        }
+    }
+
+    /// Context of `req`, taken from its headers the first time it is asked for so every
+    /// part of the server sees the same span
+    pub(crate) fn of(req: &HttpRequest) -> Self {
+        if let Some(context) = req.extensions().get::<TraceContext>() {
+            return context.clone();
+        }
+        let context = TraceContext::from_headers(req.headers());
+        req.extensions_mut().insert(context.clone());
+        context
+    }
+
+    /// Headers for calls made on behalf of the request, with this server's span as
+    /// their parent
+    pub(crate) fn outbound(&self) -> Vec<(HeaderName, HeaderValue)> {
+        let traceparent = format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            u8::from(self.sampled)
+        );
+        let mut headers = vec![(
+            TRACEPARENT,
+            HeaderValue::from_str(&traceparent).expect("hex digits are valid header values"),
+        )];
+        if let Some(state) = self
+            .state
+            .as_deref()
+            .and_then(|state| HeaderValue::from_str(state).ok())
+        {
+            headers.push((TRACESTATE, state));
+        }
+        headers
+    }
+}
+
+fn span_id() -> String {
+    // An all-zero id is invalid
+    format!("{:016x}", rand::random::<u64>() | 1)
+}
+
+/// Trace id and sampled flag of a valid `traceparent`
+fn parse_traceparent(value: &str) -> Option<(String, bool)> {
+    let is_hex = |part: &str, len: usize| {
+        part.len() == len && part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+    };
+    let parts: Vec<_> = value.trim().split('-').collect();
+    let [version, trace_id, parent_id, flags, rest @ ..] = parts.as_slice() else {
+        return None;
+    };
+    // Later versions may append fields, but must keep these first four
+    if !is_hex(version, 2) || *version == "ff" || (*version == "00" && !rest.is_empty()) {
+        return None;
+    }
+    if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+    if trace_id.bytes().all(|b| b == b'0') || parent_id.bytes().all(|b| b == b'0') {
+        return None;
+    }
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    Some((trace_id.to_string(), flags & 1 == 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn context(headers: &[(&str, &str)]) -> TraceContext {
+        let mut req = TestRequest::default();
+        for &header in headers {
+            req = req.append_header(header);
+        }
+        TraceContext::of(&req.to_http_request())
+    }
+
+    #[test]
+    fn callers_traces_are_continued() {
+        let trace = context(&[
+            (
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            ),
+            ("tracestate", "rojo=00f067aa0ba902b7"),
+            ("tracestate", "congo=t61rcWkgMzE"),
+        ]);
+        assert_eq!(trace.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_ne!(trace.span_id, "00f067aa0ba902b7");
+
+        let outbound = trace.outbound();
+        assert_eq!(
+            outbound[0].1,
+            format!("00-4bf92f3577b34da6a3ce929d0e0e4736-{}-01", trace.span_id)
+        );
+        assert_eq!(outbound[1].1, "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE");
+    }
+
+    #[test]
+    fn invalid_traceparents_start_a_new_trace() {
+        for traceparent in [
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00F067AA0BA902B7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "garbage",
+        ] {
+            let trace = context(&[("traceparent", traceparent), ("tracestate", "rojo=1")]);
+            assert_ne!(trace.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+            assert_eq!(trace.trace_id.len(), 32);
+            let outbound = trace.outbound();
+            assert!(outbound[0].1.to_str().unwrap().ends_with("-00"));
+            assert_eq!(outbound.len(), 1, "{traceparent}");
+        }
+
+        // Later versions are read for the fields version 00 defines
+        let trace = context(&[(
+            "traceparent",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        )]);
+        assert_eq!(trace.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+}
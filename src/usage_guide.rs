@@ -5,12 +5,63 @@ A simple static SVG file serving command-line tool.
 
 USAGE:
     svg-server [OPTIONS] [path]
+    svg-server report [OPTIONS] [path]
+    svg-server export [OPTIONS] [path]
+    svg-server validate --links [OPTIONS] [path]
+    svg-server digest [OPTIONS] [path]
+    svg-server recolor --map old=<color>:new=<color> [OPTIONS] [path]
+    svg-server fonts [OPTIONS] [path]
 
 OPTIONS:
     -b, --bind <address>        Specify bind address [default: 127.0.0.1]
     -p, --port <port>           Specify port to listen on [default: 5000]
     -i, --index <index>         Specify route to redirect / to [default: /home]
+    -w, --workers <workers>     Specify number of worker threads [default: number of physical CPU cores]
+    -k, --keep-alive <seconds>  Specify idle connection keep-alive timeout, 0 disables [default: 5]
+    -c, --config <file>         Specify a TOML configuration file
+        --no-listing            Disable page enumeration, pages are only reachable by exact name
+        --mirror-to <url>       Mirror a sample of GET requests to a secondary http:// server and
+                                log differing statuses or content
+        --mirror-sample <fraction>
+                                Specify fraction of GET requests to mirror [default: 0.1]
+        --export-every <interval>
+                                Re-export changed pages as static HTML this often (e.g. 30m, 1h)
+        --export-out <dir>      Specify directory the scheduled export and the admin page's
+                                export jobs write to
+        --git-pull-interval <interval>
+                                Fast-forward the served Git checkout this often (e.g. 5m)
+        --reuse-port            Share the port with another server started the same way, to
+                                upgrade without dropping connections (Unix only)
 
 ARGS:
     <path>    Path to a directory containing the SVG files to be served [default: .]
+
+SUBCOMMANDS:
+    report    Report sizes, missing titles, unused fonts and duplicates across a directory tree
+              (-f, --format <html|json>  -o, --output <file>  -n, --largest <count>)
+    export    Write every page as static HTML, next to a copy of its SVG, as Confluence
+              storage-format XHTML with a PNG attachment, or as standalone HTML
+              (-o, --output <dir> [default: public]
+              -f, --format <html|confluence|standalone>  -c, --config <file>)
+              With the s3 feature: --publish s3://bucket/prefix  --publish-all
+              --cache-control <value>  --invalidate <cloudfront-distribution-id>
+    validate  Check every href inside the SVGs (ids, pages and http(s) URLs) and report broken
+              links per diagram, exiting with status 1 if there are any
+              (--links  -f, --format <text|json>  -o, --output <file>  -c, --config <file>)
+    digest    Email the [digest] recipients the pages added, changed and approved since the
+              last digest, right away instead of on the configured schedule
+              (--dry-run  -c, --config <file>)
+    recolor   Replace colors wherever fills, strokes and other color properties set them,
+              printing a diff of every change unless --write is given
+              (-m, --map old=<color>:new=<color>, repeatable  --write)
+    fonts     List the font families used across the SVGs, whether each is installed or in the
+              [raster] fonts directory, and the pages that would fall back, exiting with status
+              1 if there are any (-f, --format <text|json>  -o, --output <file>
+              -c, --config <file>)
+
+QUERY PARAMETERS:
+    simulate=<deficiency>    Simulate color-blindness on the served SVG
+                             [possible values: deuteranopia, protanopia, tritanopia]
+    contrast=high            Map low-contrast fills/strokes to a high-contrast palette
+                             and thicken sub-pixel strokes
 "#;
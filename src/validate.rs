@@ -0,0 +1,226 @@
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use serde::Serialize;
+
+use crate::{
+    config::RoutesConfig,
+    links::{LinkTarget, SvgLinks},
+    pages::{page_svg_path, svg_files},
+    report::FileError,
+};
+
+/// Broken links across every SVG under a directory
+#[derive(Debug, Default, Serialize)]
+pub struct LinkReport {
+    /// Number of SVG files checked
+    pub files: usize,
+    /// Number of links checked, including repeats
+    pub links: usize,
+    /// Pages with at least one broken link
+    pub broken: Vec<BrokenLinks>,
+    /// Pages that could not be read or parsed
+    pub errors: Vec<FileError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenLinks {
+    pub page: String,
+    pub links: Vec<BrokenLink>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenLink {
+    pub href: String,
+    /// Status the URL answered with, or why the target doesn't exist
+    pub reason: String,
+}
+
+impl LinkReport {
+    /// Check every link in the SVGs under `root`: fragments against the ids of the same
+    /// document, page links against the SVG files, with the `/page/` and `/raw/` prefixes
+    /// of `routes`, and http(s) URLs by requesting them.
+    pub async fn collect(root: &Path, routes: &RoutesConfig) -> Self {
+        let mut report = LinkReport::default();
+        let client = awc::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .finish();
+        // Diagrams of one collection tend to link the same runbooks and dashboards
+        let mut checked: BTreeMap<String, Result<(), String>> = BTreeMap::new();
+
+        for (page, path) in svg_files(root) {
+            let document = match std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| SvgLinks::collect(&content))
+            {
+                Ok(document) => document,
+                Err(error) => {
+                    report.errors.push(FileError { page, error });
+                    continue;
+                }
+            };
+            report.files += 1;
+
+            let mut broken = Vec::new();
+            for link in &document.links {
+                report.links += 1;
+                let result = match link.target() {
+                    LinkTarget::Fragment(id) if document.ids.contains(id) => Ok(()),
+                    LinkTarget::Fragment(_) => Err("No element with this id".to_owned()),
+                    LinkTarget::Page(name) => check_page(root, routes, name),
+                    LinkTarget::External(url) => match checked.get(url) {
+                        Some(result) => result.clone(),
+                        None => {
+                            let result = check_url(&client, url).await;
+                            checked.insert(url.to_owned(), result.clone());
+                            result
+                        }
+                    },
+                    LinkTarget::Other => Ok(()),
+                };
+                if let Err(reason) = result {
+                    broken.push(BrokenLink {
+                        href: link.href.clone(),
+                        reason,
+                    });
+                }
+            }
+            if !broken.is_empty() {
+                report.broken.push(BrokenLinks {
+                    page,
+                    links: broken,
+                });
+            }
+        }
+
+        report
+    }
+
+    /// Whether every link resolved and every file could be checked
+    pub fn is_clean(&self) -> bool {
+        self.broken.is_empty() && self.errors.is_empty()
+    }
+
+    /// Broken links grouped by page, one per line
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "Checked {} links in {} files, found broken ones in {}\n",
+            self.links,
+            self.files,
+            self.broken.len()
+        );
+        for page in &self.broken {
+            text.push_str(&format!("\n{}\n", page.page));
+            for link in &page.links {
+                text.push_str(&format!("  {}  {}\n", link.href, link.reason));
+            }
+        }
+        for error in &self.errors {
+            text.push_str(&format!(
+                "\nFailed to check '{}': {}\n",
+                error.page, error.error
+            ));
+        }
+        text
+    }
+}
+
+fn check_page(root: &Path, routes: &RoutesConfig, name: &str) -> Result<(), String> {
+    let name = [&routes.page, &routes.raw]
+        .into_iter()
+        .flatten()
+        .find_map(|prefix| name.strip_prefix(prefix.as_str())?.strip_prefix('/'))
+        .unwrap_or(name);
+    let (_, path) = page_svg_path(root, name)?;
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err("No such page".to_owned())
+    }
+}
+
+/// Request `url` with `HEAD`, confirming failures with `GET` since some servers only
+/// route `GET`
+async fn check_url(client: &awc::Client, url: &str) -> Result<(), String> {
+    let mut status = client
+        .head(url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .status();
+    if status.is_client_error() || status.is_server_error() {
+        status = client
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .status();
+    }
+
+    if status.is_client_error() || status.is_server_error() {
+        Err(status.to_string())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, HttpResponse, HttpServer, web};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn broken_links_are_reported_per_page() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        // Only routes GET, as some servers do
+        let server = HttpServer::new(|| App::new().route("/ok", web::get().to(HttpResponse::Ok)))
+            .workers(1)
+            .listen(listener)
+            .unwrap()
+            .run();
+        actix_web::rt::spawn(server);
+
+        let root = std::env::temp_dir().join(format!("svg-server-links-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("network")).unwrap();
+        std::fs::write(
+            root.join("network").join("db.svg"),
+            r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("home.svg"),
+            format!(
+                r##"<svg xmlns="http://www.w3.org/2000/svg">
+                    <a href="{base}/ok"/><a href="{base}/gone"/>
+                    <a href="/network:db"/><a href="page/network:db"/><a href="network:cache"/>
+                    <g id="legend"/><use href="#legend"/><use href="#missing"/>
+                    <a href="mailto:ops@example.com"/>
+                </svg>"##
+            ),
+        )
+        .unwrap();
+
+        let report = LinkReport::collect(&root, &RoutesConfig::default()).await;
+        assert_eq!((report.files, report.links), (2, 8));
+        assert_eq!(report.broken.len(), 1);
+        assert_eq!(report.broken[0].page, "home");
+        let broken: Vec<_> = report.broken[0]
+            .links
+            .iter()
+            .map(|link| (link.href.as_str(), link.reason.as_str()))
+            .collect();
+        assert_eq!(
+            broken,
+            [
+                (format!("{base}/gone").as_str(), "404 Not Found"),
+                ("network:cache", "No such page"),
+                ("#missing", "No element with this id"),
+            ]
+        );
+        assert!(!report.is_clean());
+        let text = report.to_text();
+        assert!(text.starts_with("Checked 8 links in 2 files, found broken ones in 1\n\nhome\n"));
+        assert!(text.contains("\n  network:cache  No such page\n"));
+    }
+}
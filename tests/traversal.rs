@@ -0,0 +1,92 @@
+//! Requests built from traversal fragments must never serve a file outside the root.
+
+use actix_web::{
+    App,
+    rt::System,
+    test::{TestRequest, call_and_read_body, init_service},
+};
+use proptest::{prelude::*, test_runner::TestRunner};
+use svg_server::Settings;
+
+const SECRET: &str = "outside-the-served-root";
+
+/// URL-safe fragments, so they reach the router exactly as generated
+fn url_fragment() -> impl Strategy<Value = &'static str> {
+    prop::sample::select(vec![
+        "/",
+        "..",
+        ".",
+        ":",
+        "%2e",
+        "%2E%2e",
+        "%2f",
+        "%2F",
+        "%5c",
+        "%5C",
+        "%3A",
+        "%00",
+        "%25",
+        "%252e%252e",
+        "%c0%ae",
+        "%EF%BC%8E",
+        "%EF%BC%8F",
+        "%E2%80%A5",
+        "secret",
+        "Secret",
+        "home",
+        "root",
+        "sub",
+        "inner",
+        ".svg",
+        "~",
+        ";",
+        "?",
+        "#",
+    ])
+}
+
+#[test]
+fn requests_never_escape_the_root() {
+    // <base>/secret.svg sits next to the served <base>/root
+    let base = std::env::temp_dir().join(format!("svg-server-traversal-{}", std::process::id()));
+    let root = base.join("root");
+    std::fs::create_dir_all(root.join("sub")).unwrap();
+    let page = r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1"/>"#;
+    std::fs::write(root.join("home.svg"), page).unwrap();
+    std::fs::write(root.join("sub").join("inner.svg"), page).unwrap();
+    std::fs::write(
+        base.join("secret.svg"),
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg"><title>{SECRET}</title></svg>"#),
+    )
+    .unwrap();
+
+    let system = System::new();
+    let app = system.block_on(init_service(
+        App::new().service(svg_server::service(Settings::new(&root))),
+    ));
+
+    let tails = prop::collection::vec(url_fragment(), 1..12).prop_map(|parts| parts.concat());
+    let mut runner = TestRunner::new(ProptestConfig::with_cases(512));
+    runner
+        .run(&tails, |tail| {
+            for uri in [
+                format!("/{tail}"),
+                format!("/raw/{tail}"),
+                format!("/page/{tail}"),
+                format!("/api/statistics/{tail}"),
+                format!("/sprite.svg?icons={tail}"),
+                format!("/sprite.svg?dir={tail}"),
+            ] {
+                let body = system.block_on(call_and_read_body(
+                    &app,
+                    TestRequest::get().uri(&uri).to_request(),
+                ));
+                prop_assert!(
+                    !String::from_utf8_lossy(&body).contains(SECRET),
+                    "{uri} served a file outside the root"
+                );
+            }
+            Ok(())
+        })
+        .unwrap();
+}